@@ -1,15 +1,30 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::broadcast;
 
+/// Total time spent inside the capture callback, for the audio-thread CPU
+/// share in system telemetry.
+pub static CALLBACK_BUSY_NANOS: AtomicU64 = AtomicU64::new(0);
+
 pub struct AudioEngine {
     stream: cpal::Stream,
 }
 
+use crate::recorder::Recorder;
+use crate::spectrum::SpectrumFrame;
 use crate::state_machine::AudioMetadata;
 
 impl AudioEngine {
+    /// Opens the first loopback device, or the first one whose name contains
+    /// `wanted_device` when a zone names one. Either way only loopback devices qualify.
     pub fn new(
+        wanted_device: Option<&str>,
         tx: broadcast::Sender<AudioFeatures>,
+        tx_spectrum: broadcast::Sender<Arc<SpectrumFrame>>,
+        recorder: Arc<Recorder>,
     ) -> Result<(Self, AudioMetadata), Box<dyn std::error::Error>> {
         let host = cpal::default_host();
 
@@ -33,8 +48,9 @@ impl AudioEngine {
             println!("   - {}", name);
 
             // Check if device name contains any loopback keyword
+            let requested = wanted_device.is_none_or(|wanted| name.contains(wanted));
             for keyword in &loopback_keywords {
-                if name.contains(keyword) {
+                if requested && name.contains(keyword) {
                     target_device = Some(device);
                     println!("   ✓ SELECTED: {}", name);
                     break;
@@ -47,6 +63,9 @@ impl AudioEngine {
         }
 
         // STRICT: Only use loopback device, fail if not found
+        if let (None, Some(wanted)) = (&target_device, wanted_device) {
+            return Err(format!("No loopback device matching '{}' found", wanted).into());
+        }
         let device = target_device.ok_or(
             "❌ CRITICAL: No system audio loopback device found! \
              Please enable 'Stereo Mix' in Windows Sound Settings. \
@@ -66,33 +85,32 @@ impl AudioEngine {
             channels: config.channels(),
         };
 
-        // Create the stream
-        let tx_clone = tx.clone();
-        let sr_u32 = config.sample_rate().0;
-
         let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
 
-        // State for spectral flux (persistence across callback fires)
-        let mut prev_flux_f32 = Vec::new();
-        let mut prev_flux_i16 = Vec::new();
-        let mut prev_flux_u16 = Vec::new();
+        // Per-stream analysis state (persists across callback fires)
+        let mut analyzer = StreamAnalyzer {
+            tx,
+            tx_spectrum,
+            recorder,
+            frames: FrameAnalyzer::new(config.sample_rate().0, config.channels()),
+        };
 
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => device.build_input_stream(
                 &config.into(),
-                move |data: &[f32], _: &_| write_input_data(data, &tx_clone, sr_u32, &mut prev_flux_f32),
+                move |data: &[f32], _: &_| analyzer.write_input_data(data),
                 err_fn,
                 None,
             )?,
             cpal::SampleFormat::I16 => device.build_input_stream(
                 &config.into(),
-                move |data: &[i16], _: &_| write_input_data(data, &tx_clone, sr_u32, &mut prev_flux_i16),
+                move |data: &[i16], _: &_| analyzer.write_input_data(data),
                 err_fn,
                 None,
             )?,
             cpal::SampleFormat::U16 => device.build_input_stream(
                 &config.into(),
-                move |data: &[u16], _: &_| write_input_data(data, &tx_clone, sr_u32, &mut prev_flux_u16),
+                move |data: &[u16], _: &_| analyzer.write_input_data(data),
                 err_fn,
                 None,
             )?,
@@ -113,12 +131,19 @@ impl Drop for AudioEngine {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AudioFeatures {
     pub low_energy: f32,
     pub mid_energy: f32,
     pub high_energy: f32,
     pub spectral_flux: f32,
+    #[serde(default)]
+    pub spectral_centroid: f32, // Magnitude-weighted mean frequency, 0..1 of Nyquist
+    #[serde(default)]
+    pub spectral_rolloff: f32, // Frequency below which 85% of the magnitude lies, 0..1 of Nyquist
+    #[serde(default)]
+    pub chroma: [f32; 12], // Magnitude share per pitch class, C first (mood estimation)
+    pub sample_clock: u64, // Frames captured since stream start (end of this buffer)
 }
 
 // use cpal::Sample; // Removed to avoid warning if not needed, but generic constraint uses it path-wise.
@@ -152,95 +177,202 @@ thread_local! {
     static FFT_PLANNER: RefCell<FftPlanner<f32>> = RefCell::new(FftPlanner::new());
 }
 
-fn write_input_data<T>(
-    input: &[T],
-    tx: &broadcast::Sender<AudioFeatures>,
-    sample_rate: u32,
-    prev_spectrum: &mut Vec<f32>,
-) where
-    T: AudioSample,
-{
-    if input.is_empty() {
-        return;
+/// Everything the capture callback needs to carry between buffers.
+struct StreamAnalyzer {
+    tx: broadcast::Sender<AudioFeatures>,
+    tx_spectrum: broadcast::Sender<Arc<SpectrumFrame>>,
+    recorder: Arc<Recorder>,
+    frames: FrameAnalyzer,
+}
+
+impl StreamAnalyzer {
+    fn write_input_data<T>(&mut self, input: &[T])
+    where
+        T: AudioSample,
+    {
+        if input.is_empty() {
+            return;
+        }
+        let started = Instant::now();
+
+        // Convert to F32 for analysis (optimized: pre-allocate capacity)
+        let mut samples = Vec::with_capacity(input.len());
+        for s in input.iter() {
+            samples.push(s.to_f32_custom());
+        }
+
+        let sample_rate = self.frames.sample_rate;
+        let features = self.frames.analyze(&samples);
+
+        // Raw capture tap (no-op unless a recording is running)
+        self.recorder.push_audio(features.sample_clock, &samples);
+
+        // Raw frame for oscilloscope / spectrogram clients (skipped when nobody listens)
+        if self.tx_spectrum.receiver_count() > 0 {
+            let frame = SpectrumFrame::new(
+                features.sample_clock,
+                sample_rate,
+                self.frames.mono(),
+                self.frames.spectrum(),
+            );
+            let _ = self.tx_spectrum.send(Arc::new(frame));
+        }
+
+        // Send with error handling (avoid silent failures)
+        if let Err(e) = self.tx.send(features) {
+            // Only log if there are no receivers (not just lagging)
+            if self.tx.receiver_count() == 0 {
+                eprintln!("⚠️ [Audio] No active subscribers for audio features: {}", e);
+            }
+        }
+
+        CALLBACK_BUSY_NANOS.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
     }
+}
+
+const CHROMA_MIN_HZ: f32 = 65.0;
+const CHROMA_MAX_HZ: f32 = 4200.0;
+
+/// Turns interleaved sample buffers into `AudioFeatures`. Shared by live
+/// capture and offline tools so both see exactly the same features.
+pub struct FrameAnalyzer {
+    pub sample_rate: u32,
+    pub channels: u16,
+    clock: u64,              // Sample clock (frames since stream start)
+    mono: Vec<f32>,          // Downmix of the last buffer
+    prev_spectrum: Vec<f32>, // Previous magnitudes for spectral flux
+}
 
-    // Convert to F32 for analysis (optimized: pre-allocate capacity)
-    let mut samples = Vec::with_capacity(input.len());
-    for s in input.iter() {
-        samples.push(s.to_f32_custom());
+impl FrameAnalyzer {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self { sample_rate, channels, clock: 0, mono: Vec::new(), prev_spectrum: Vec::new() }
     }
 
-    // 1. FFT Analysis (optimized: reuse thread-local planner)
-    let mut buffer: Vec<Complex<f32>> = Vec::with_capacity(samples.len());
-    for &x in samples.iter() {
-        buffer.push(Complex { re: x, im: 0.0 });
+    /// Mono downmix of the last analysed buffer.
+    pub fn mono(&self) -> &[f32] {
+        &self.mono
     }
 
-    FFT_PLANNER.with(|planner| {
-        let mut planner = planner.borrow_mut();
-        let fft = planner.plan_fft_forward(samples.len());
-        fft.process(&mut buffer);
-    });
-
-    // Calculate bands (0-150Hz, 150-2500Hz, 2500Hz+)
-    let bin_size = sample_rate as f32 / samples.len() as f32;
-
-    let mut low = 0.0f32;
-    let mut mid = 0.0f32;
-    let mut high = 0.0f32;
-    
-    // Store current magnitudes for flux calculation
-    let half_len = samples.len() / 2;
-    let mut current_spectrum = Vec::with_capacity(half_len);
-
-    for (i, complex) in buffer.iter().enumerate().take(half_len) {
-        let freq = i as f32 * bin_size;
-        // Manual norm calculation to avoid trait issues
-        let mag = (complex.re * complex.re + complex.im * complex.im).sqrt();
-        
-        current_spectrum.push(mag);
-
-        if freq < 150.0 {
-            low += mag;
-        } else if freq < 2500.0 {
-            mid += mag;
-        } else {
-            high += mag;
-        }
+    /// Magnitude spectrum of the last analysed buffer, up to Nyquist.
+    pub fn spectrum(&self) -> &[f32] {
+        &self.prev_spectrum
     }
 
-    // 2. Spectral Flux (Onset Detection)
-    // Sum of positive differences between current and previous frame bins
-    let mut flux = 0.0f32;
-    if prev_spectrum.len() == current_spectrum.len() {
-        for (curr, prev) in current_spectrum.iter().zip(prev_spectrum.iter()) {
-            let diff = curr - prev;
-            if diff > 0.0 {
-                flux += diff;
+    pub fn analyze(&mut self, samples: &[f32]) -> AudioFeatures {
+        // Interleaved channels would fold L/R into mirror images; analyse the downmix
+        let channels = self.channels.max(1) as usize;
+        self.mono.clear();
+        self.mono.extend(
+            samples.chunks(channels).map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+        );
+        let len = self.mono.len();
+        self.clock += len as u64;
+        if len == 0 {
+            return AudioFeatures { sample_clock: self.clock, ..Default::default() };
+        }
+
+        // 1. FFT Analysis (optimized: reuse thread-local planner)
+        let mut buffer: Vec<Complex<f32>> = Vec::with_capacity(len);
+        for &x in self.mono.iter() {
+            buffer.push(Complex { re: x, im: 0.0 });
+        }
+
+        FFT_PLANNER.with(|planner| {
+            let mut planner = planner.borrow_mut();
+            let fft = planner.plan_fft_forward(len);
+            fft.process(&mut buffer);
+        });
+
+        // Calculate bands (0-150Hz, 150-2500Hz, 2500Hz+)
+        let bin_size = self.sample_rate as f32 / len as f32;
+
+        let mut low = 0.0f32;
+        let mut mid = 0.0f32;
+        let mut high = 0.0f32;
+        let mut weighted = 0.0f32;
+
+        // Store current magnitudes for flux calculation
+        let half_len = len / 2;
+        let mut current_spectrum = Vec::with_capacity(half_len);
+
+        for (i, complex) in buffer.iter().enumerate().take(half_len) {
+            let freq = i as f32 * bin_size;
+            // Manual norm calculation to avoid trait issues
+            let mag = (complex.re * complex.re + complex.im * complex.im).sqrt();
+
+            current_spectrum.push(mag);
+            weighted += i as f32 * mag;
+
+            if freq < 150.0 {
+                low += mag;
+            } else if freq < 2500.0 {
+                mid += mag;
+            } else {
+                high += mag;
             }
         }
-    }
-    
-    // Update previous spectrum
-    *prev_spectrum = current_spectrum;
-
-    // Normalize by buffer size
-    let norm = samples.len() as f32;
-    // Normalize flux (heuristic scaling)
-    let flux_norm = (flux / norm).min(1.0f32);
-
-    let features = AudioFeatures {
-        low_energy: (low / norm).min(1.0f32),
-        mid_energy: (mid / norm).min(1.0f32),
-        high_energy: (high / norm).min(1.0f32),
-        spectral_flux: flux_norm,
-    };
-
-    // Send with error handling (avoid silent failures)
-    if let Err(e) = tx.send(features) {
-        // Only log if there are no receivers (not just lagging)
-        if tx.receiver_count() == 0 {
-            eprintln!("⚠️ [Audio] No active subscribers for audio features: {}", e);
+
+        // 2. Spectral Flux (Onset Detection)
+        // Sum of positive differences between current and previous frame bins
+        let mut flux = 0.0f32;
+        if self.prev_spectrum.len() == current_spectrum.len() {
+            for (curr, prev) in current_spectrum.iter().zip(self.prev_spectrum.iter()) {
+                let diff = curr - prev;
+                if diff > 0.0 {
+                    flux += diff;
+                }
+            }
+        }
+
+        // 3. Spectral shape (timbre descriptors for the genre classifier)
+        let total = low + mid + high;
+        let bins = half_len.max(1) as f32;
+        let mut centroid = 0.0f32;
+        let mut rolloff = 0.0f32;
+        if total > 0.0 {
+            centroid = weighted / total / bins;
+            let mut cumulative = 0.0f32;
+            for (i, mag) in current_spectrum.iter().enumerate() {
+                cumulative += mag;
+                if cumulative >= 0.85 * total {
+                    rolloff = i as f32 / bins;
+                    break;
+                }
+            }
+        }
+
+        // 4. Chroma (pitch-class profile, C2..C8)
+        let mut chroma = [0.0f32; 12];
+        for (i, mag) in current_spectrum.iter().enumerate().skip(1) {
+            let freq = i as f32 * bin_size;
+            if !(CHROMA_MIN_HZ..CHROMA_MAX_HZ).contains(&freq) {
+                continue;
+            }
+            let midi = (12.0 * (freq / 440.0).log2() + 69.0).round() as i32;
+            chroma[midi.rem_euclid(12) as usize] += mag;
+        }
+        let chroma_total: f32 = chroma.iter().sum();
+        if chroma_total > 0.0 {
+            chroma.iter_mut().for_each(|c| *c /= chroma_total);
+        }
+
+        // Update previous spectrum
+        self.prev_spectrum = current_spectrum;
+
+        // Normalize by buffer size
+        let norm = len as f32;
+        // Normalize flux (heuristic scaling)
+        let flux_norm = (flux / norm).min(1.0f32);
+
+        AudioFeatures {
+            low_energy: (low / norm).min(1.0f32),
+            mid_energy: (mid / norm).min(1.0f32),
+            high_energy: (high / norm).min(1.0f32),
+            spectral_flux: flux_norm,
+            spectral_centroid: centroid,
+            spectral_rolloff: rolloff,
+            chroma,
+            sample_clock: self.clock,
         }
     }
 }
@@ -0,0 +1,76 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde::Deserialize;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// Claims issued by the middleware's `/auth/login`.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    username: String,
+    role: String,
+}
+
+/// Extractor for show-control endpoints. Requires `Authorization: Bearer <jwt>`
+/// signed (HS256) with the same `JWT_SECRET` as the middleware and an admin role.
+/// Without `JWT_SECRET` the control endpoints are disabled rather than open.
+pub struct Operator {
+    pub username: String,
+}
+
+fn decoding_key() -> Option<&'static DecodingKey> {
+    static KEY: OnceLock<Option<DecodingKey>> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let secret = std::env::var("JWT_SECRET").ok().filter(|s| !s.is_empty());
+        if secret.is_none() {
+            println!("⚠️ [Auth] JWT_SECRET not set. Operator endpoints are disabled.");
+        }
+        secret.map(|s| DecodingKey::from_secret(s.as_bytes()))
+    })
+    .as_ref()
+}
+
+pub struct AuthError(StatusCode, &'static str);
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let body =
+            serde_json::json!({ "success": false, "error": self.1, "code": self.0.as_u16() });
+        (self.0, Json(body)).into_response()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Operator {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let key = decoding_key()
+            .ok_or(AuthError(StatusCode::SERVICE_UNAVAILABLE, "Operator API disabled"))?;
+
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(AuthError(StatusCode::UNAUTHORIZED, "Unauthorized: No token provided"))?;
+
+        let claims = decode::<Claims>(token, key, &Validation::default())
+            .map_err(|e| {
+                warn!(event = "operator_auth_failed", error = %e);
+                AuthError(StatusCode::UNAUTHORIZED, "Unauthorized: Invalid token")
+            })?
+            .claims;
+
+        if claims.role != "admin" {
+            return Err(AuthError(StatusCode::FORBIDDEN, "Forbidden: Operator role required"));
+        }
+        Ok(Operator { username: claims.username })
+    }
+}
@@ -0,0 +1,117 @@
+use crate::state_machine::BeatInfo;
+
+// Onsets within this many beats of the predicted beat pull the phase towards it
+const CAPTURE_WINDOW: f32 = 0.15;
+// Share of the phase error corrected per onset; small, so off-beat hits barely move it
+const PLL_GAIN: f32 = 0.2;
+// A measured tempo this far (relative) from the locked one counts as a new lock
+const RELOCK_RATIO: f32 = 0.03;
+
+/// Integrates `bpm` into `BeatInfo` and keeps it in step with the music: a
+/// first-order phase-locked loop nudges the phase towards 0 whenever an onset
+/// lands near the predicted beat, so rounding and tempo estimate errors do not
+/// accumulate. After a tempo (re-)lock the next onset restarts the phase.
+#[derive(Default)]
+pub struct BeatClock {
+    locked_bpm: Option<f32>,
+    relock: bool,
+}
+
+impl BeatClock {
+    pub fn advance(&self, beat: &mut BeatInfo, dt: f32, bpm: f32) {
+        let advanced = beat.phase + dt * bpm / 60.0;
+        beat.count += advanced.floor() as u64;
+        beat.phase = advanced.fract();
+    }
+
+    /// Takes the tempo of a genre re-evaluation; `None` when no beat was measured.
+    pub fn set_tempo(&mut self, measured_bpm: Option<f32>) {
+        let Some(bpm) = measured_bpm else {
+            self.locked_bpm = None;
+            return;
+        };
+        let relocked =
+            self.locked_bpm.is_none_or(|locked| (bpm - locked).abs() > locked * RELOCK_RATIO);
+        if relocked {
+            self.locked_bpm = Some(bpm);
+            self.relock = true;
+        }
+    }
+
+    pub fn onset(&mut self, beat: &mut BeatInfo) {
+        // Phase error in beats: negative while the onset comes early
+        let error = if beat.phase < 0.5 { beat.phase } else { beat.phase - 1.0 };
+        if self.relock {
+            self.relock = false;
+            if error < 0.0 {
+                beat.count += 1;
+            }
+            beat.phase = 0.0;
+        } else if error.abs() <= CAPTURE_WINDOW {
+            // The correction never crosses 0, so `count` stays put
+            beat.phase -= error * PLL_GAIN;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beat(count: u64, phase: f32) -> BeatInfo {
+        BeatInfo { count, phase }
+    }
+
+    #[test]
+    fn advances_with_tempo() {
+        let clock = BeatClock::default();
+        let mut b = beat(0, 0.75);
+        clock.advance(&mut b, 1.0, 120.0);
+        assert_eq!(b.count, 2);
+        assert!((b.phase - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn onsets_near_the_beat_pull_the_phase() {
+        let mut clock = BeatClock::default();
+
+        // Late onset: phase moves back towards 0
+        let mut b = beat(4, 0.1);
+        clock.onset(&mut b);
+        assert_eq!(b.count, 4);
+        assert!((b.phase - 0.08).abs() < 1e-5);
+
+        // Early onset: phase moves on towards the next beat without crossing it
+        let mut b = beat(4, 0.9);
+        clock.onset(&mut b);
+        assert_eq!(b.count, 4);
+        assert!((b.phase - 0.92).abs() < 1e-5);
+
+        // Off-beat hits are ignored
+        let mut b = beat(4, 0.5);
+        clock.onset(&mut b);
+        assert_eq!(b, beat(4, 0.5));
+    }
+
+    #[test]
+    fn a_tempo_relock_restarts_the_phase_on_the_next_onset() {
+        let mut clock = BeatClock::default();
+        clock.set_tempo(Some(128.0));
+
+        let mut b = beat(7, 0.7);
+        clock.onset(&mut b);
+        assert_eq!(b, beat(8, 0.0));
+
+        // Within the tolerance the lock holds; the next onset only nudges
+        clock.set_tempo(Some(129.0));
+        let mut b = beat(8, 0.4);
+        clock.onset(&mut b);
+        assert_eq!(b, beat(8, 0.4));
+
+        // A new tempo locks again
+        clock.set_tempo(Some(140.0));
+        let mut b = beat(8, 0.3);
+        clock.onset(&mut b);
+        assert_eq!(b, beat(8, 0.0));
+    }
+}
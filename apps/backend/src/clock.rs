@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Time source for the state pipeline. `now()` is monotonic time since an
+/// arbitrary origin, so the Overmind never depends on how often it is called.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+/// Wall-clock time since construction.
+pub struct WallClock {
+    origin: Instant,
+}
+
+impl WallClock {
+    pub fn new() -> Self {
+        Self { origin: Instant::now() }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for WallClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// Externally driven time, e.g. from the audio sample clock, a replayed log or a test.
+#[derive(Default)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, t: Duration) {
+        self.nanos.store(t.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the time from a sample position; ignored when the rate is unknown.
+    pub fn set_samples(&self, samples: u64, sample_rate: u32) {
+        if sample_rate > 0 {
+            self.set(Duration::from_secs_f64(samples as f64 / sample_rate as f64));
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Fires once per `period` of clock time, however irregularly it is polled.
+#[derive(Debug, Clone)]
+pub struct Every {
+    period: Duration,
+    next: Duration,
+}
+
+impl Every {
+    pub fn new(period: Duration) -> Self {
+        Self { period, next: period }
+    }
+
+    pub fn due(&mut self, now: Duration) -> bool {
+        if now < self.next {
+            return false;
+        }
+        // Skip missed periods instead of firing in a burst after a stall
+        while self.next <= now {
+            self.next += self.period;
+        }
+        true
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+/// Primary/secondary colour pair as published in `ai_primary_color` / `ai_secondary_color`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Palette {
+    pub primary: String,
+    pub secondary: String,
+}
+
+impl Palette {
+    /// The first colour that is not `#RRGGBB`, if any.
+    pub fn invalid_color(&self) -> Option<&str> {
+        [&self.primary, &self.secondary]
+            .into_iter()
+            .map(String::as_str)
+            .find(|c| parse_hex(c).is_none())
+    }
+
+    /// Blends towards `to` by `t` (0..1) in OKLab. Unparseable colours switch at the midpoint.
+    pub fn lerp(&self, to: &Palette, t: f32) -> Palette {
+        Palette {
+            primary: lerp_hex(&self.primary, &to.primary, t),
+            secondary: lerp_hex(&self.secondary, &to.secondary, t),
+        }
+    }
+}
+
+pub fn parse_hex(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+pub fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2])
+}
+
+/// Interpolates in OKLab, so a fade keeps an even perceived brightness instead
+/// of dipping through muddy greys the way an sRGB blend does.
+pub fn lerp_hex(from: &str, to: &str, t: f32) -> String {
+    let t = t.clamp(0.0, 1.0);
+    match (parse_hex(from), parse_hex(to)) {
+        (Some(a), Some(b)) => {
+            let (a, b) = (to_oklab(a), to_oklab(b));
+            let mix = |i: usize| a[i] + (b[i] - a[i]) * t as f64;
+            to_hex(from_oklab([mix(0), mix(1), mix(2)]))
+        }
+        _ if t < 0.5 => from.to_string(),
+        _ => to.to_string(),
+    }
+}
+
+// --- OKLab (Björn Ottosson, 2020) ---
+
+fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round() as u8
+}
+
+/// sRGB channels as linear light, 0..1.
+pub fn linear_rgb(rgb: [u8; 3]) -> [f64; 3] {
+    rgb.map(srgb_to_linear)
+}
+
+/// WCAG relative luminance, 0 (black) to 1 (white).
+pub fn relative_luminance(rgb: [u8; 3]) -> f64 {
+    let [r, g, b] = linear_rgb(rgb);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+pub fn to_oklab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+pub fn from_oklab(lab: [f64; 3]) -> [u8; 3] {
+    let [l, a, b] = lab;
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ]
+    .map(linear_to_srgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: [&str; 6] = ["#000000", "#FFFFFF", "#FF0000", "#00FF00", "#1E90FF", "#7F3A10"];
+
+    #[test]
+    fn crossfades_start_and_end_on_the_exact_colours() {
+        for from in COLORS {
+            for to in COLORS {
+                assert_eq!(lerp_hex(from, to, 0.0), from);
+                assert_eq!(lerp_hex(from, to, 1.0), to);
+                // Out-of-range progress is clamped to the endpoints
+                assert_eq!(lerp_hex(from, to, -1.0), from);
+                assert_eq!(lerp_hex(from, to, 2.0), to);
+            }
+        }
+    }
+
+    #[test]
+    fn oklab_round_trips() {
+        for color in COLORS {
+            let rgb = parse_hex(color).unwrap();
+            assert_eq!(from_oklab(to_oklab(rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn greys_stay_grey_through_the_fade() {
+        let mid = parse_hex(&lerp_hex("#000000", "#FFFFFF", 0.5)).unwrap();
+        assert!(mid[0] == mid[1] && mid[1] == mid[2]);
+        assert!(mid[0] > 0 && mid[0] < 255);
+    }
+
+    #[test]
+    fn unparseable_colours_switch_at_the_midpoint() {
+        assert_eq!(lerp_hex("red", "#0000FF", 0.4), "red");
+        assert_eq!(lerp_hex("red", "#0000FF", 0.5), "#0000FF");
+
+        let palette = Palette { primary: "#FF0000".to_string(), secondary: "#12345".to_string() };
+        assert_eq!(palette.invalid_color(), Some("#12345"));
+        assert_eq!(parse_hex("#abcdef"), Some([0xAB, 0xCD, 0xEF]));
+    }
+}
@@ -0,0 +1,396 @@
+use crate::llm_engine::{AiContext, LlmDirector};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const EVALUATE_INTERVAL: Duration = Duration::from_secs(1);
+// Reports older than this many decay constants no longer count at all
+const STALE_DECAYS: f32 = 3.0;
+// Recent interaction can halve a client's boredom at most
+const ACTIVITY_DAMPING: f32 = 0.5;
+// Background tabs still count, but little
+const HIDDEN_WEIGHT: f32 = 0.2;
+// A client that just joined weighs this share of one that has settled in
+const NEW_CLIENT_WEIGHT: f32 = 0.25;
+
+#[derive(Debug, Clone)]
+pub struct CrowdConfig {
+    /// Crowd boredom at or above which chaos may be triggered.
+    pub trigger: f32,
+    /// Crowd boredom at or below which a triggered chaos ends.
+    pub recover: f32,
+    /// Share of the crowd's weight that must itself be above `trigger`.
+    pub min_share: f32,
+    /// How long the crowd must stay bored before chaos is triggered.
+    pub hold: Duration,
+    /// Pause after a recovery before chaos can be triggered again.
+    pub cooldown: Duration,
+    /// Time constant of a report's weight.
+    pub decay: Duration,
+    /// Time until a new client weighs fully.
+    pub dwell: Duration,
+}
+
+impl CrowdConfig {
+    /// `CROWD_TRIGGER` (0.8), `CROWD_RECOVER` (0.5), `CROWD_MIN_SHARE` (0.5),
+    /// `CROWD_HOLD_SECS` (10), `CROWD_COOLDOWN_SECS` (60), `CROWD_DECAY_SECS` (5)
+    /// and `CROWD_DWELL_SECS` (30).
+    pub fn from_env() -> Self {
+        let num = |key: &str, default: f32| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+        // Values too large for a Duration fall back like unparseable ones
+        let secs = |key: &str, default: f32| {
+            Duration::try_from_secs_f32(num(key, default))
+                .unwrap_or_else(|_| Duration::from_secs_f32(default))
+        };
+        let trigger = num("CROWD_TRIGGER", 0.8);
+        Self {
+            trigger,
+            recover: num("CROWD_RECOVER", 0.5).min(trigger),
+            min_share: num("CROWD_MIN_SHARE", 0.5).min(1.0),
+            hold: secs("CROWD_HOLD_SECS", 10.0),
+            cooldown: secs("CROWD_COOLDOWN_SECS", 60.0),
+            decay: secs("CROWD_DECAY_SECS", 5.0).max(Duration::from_millis(100)),
+            dwell: secs("CROWD_DWELL_SECS", 30.0),
+        }
+    }
+}
+
+/// Message a `/ws` client sends upstream, about once per second.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientTelemetry {
+    pub boredom_score: f32,
+    /// Recent interaction (pointer, gamepad, hand tracking), 0..1.
+    #[serde(default)]
+    pub activity: Option<f32>,
+    /// Whether the page is on screen.
+    #[serde(default)]
+    pub visible: Option<bool>,
+}
+
+/// Crowd figures published with every frame as `crowd`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct CrowdStats {
+    pub boredom: f32,     // Weighted crowd boredom, 0..1
+    pub activity: f32,    // Weighted mean of the reported activity
+    pub bored_share: f32, // Share of the weight at or above the trigger level
+    pub clients: u32,     // Clients whose last report still counts
+    pub weight: f32,      // Total weight behind the figures
+    pub chaos: bool,      // A crowd-triggered CHAOS_INJECTION is in effect
+}
+
+struct Report {
+    at: Instant,
+    boredom: f32,
+    activity: f32,
+    visible: bool,
+}
+
+struct Client {
+    connected: Instant,
+    report: Option<Report>,
+}
+
+impl Client {
+    /// Weight of the last report and the boredom it stands for, once damped by activity.
+    fn weigh(&self, config: &CrowdConfig, now: Instant) -> Option<(f32, f32)> {
+        let report = self.report.as_ref()?;
+        let age = (now - report.at).as_secs_f32() / config.decay.as_secs_f32();
+        if age > STALE_DECAYS {
+            return None;
+        }
+        let dwell = match config.dwell.as_secs_f32() {
+            d if d > 0.0 => ((now - self.connected).as_secs_f32() / d).min(1.0),
+            _ => 1.0,
+        };
+        let weight = (-age).exp()
+            * (NEW_CLIENT_WEIGHT + (1.0 - NEW_CLIENT_WEIGHT) * dwell)
+            * if report.visible { 1.0 } else { HIDDEN_WEIGHT };
+        Some((weight, report.boredom * (1.0 - ACTIVITY_DAMPING * report.activity)))
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    clients: HashMap<u64, Client>,
+    stats: CrowdStats,
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Armed { bored_since: Option<Instant> },
+    Chaos,
+    Cooldown { until: Instant },
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Trigger,
+    Recover,
+}
+
+/// Chaos is triggered once the crowd has stayed bored for `hold`, ends when
+/// boredom falls to `recover`, and cannot be triggered again until `cooldown`
+/// has passed.
+struct ChaosTrigger {
+    phase: Phase,
+}
+
+impl ChaosTrigger {
+    fn new() -> Self {
+        Self { phase: Phase::Armed { bored_since: None } }
+    }
+
+    fn in_chaos(&self) -> bool {
+        matches!(self.phase, Phase::Chaos)
+    }
+
+    fn evaluate(
+        &mut self,
+        config: &CrowdConfig,
+        now: Instant,
+        stats: &CrowdStats,
+    ) -> Option<Action> {
+        let bored = stats.boredom >= config.trigger && stats.bored_share >= config.min_share;
+        let (phase, action) = match self.phase {
+            Phase::Armed { .. } if !bored => (Phase::Armed { bored_since: None }, None),
+            Phase::Armed { bored_since } => {
+                let since = bored_since.unwrap_or(now);
+                if now - since >= config.hold {
+                    (Phase::Chaos, Some(Action::Trigger))
+                } else {
+                    (Phase::Armed { bored_since: Some(since) }, None)
+                }
+            }
+            Phase::Chaos if stats.boredom <= config.recover => {
+                (Phase::Cooldown { until: now + config.cooldown }, Some(Action::Recover))
+            }
+            Phase::Cooldown { until } if now >= until => (Phase::Armed { bored_since: None }, None),
+            phase => (phase, None),
+        };
+        self.phase = phase;
+        action
+    }
+}
+
+/// Aggregates the boredom reports of a zone's connected clients. Each report
+/// counts with a weight that decays with its age, grows with the client's time
+/// in the room and drops for hidden tabs, so no single client decides for the
+/// crowd and silent clients fade out instead of lingering.
+pub struct CrowdMonitor {
+    config: CrowdConfig,
+    next_id: AtomicU64,
+    inner: Mutex<Inner>,
+}
+
+impl CrowdMonitor {
+    pub fn new(config: CrowdConfig) -> Arc<Self> {
+        Arc::new(Self { config, next_id: AtomicU64::new(0), inner: Mutex::new(Inner::default()) })
+    }
+
+    /// Registers a `/ws` connection; its dwell time starts now.
+    pub fn connect(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let client = Client { connected: Instant::now(), report: None };
+        self.inner.lock().unwrap().clients.insert(id, client);
+        id
+    }
+
+    pub fn disconnect(&self, id: u64) {
+        self.inner.lock().unwrap().clients.remove(&id);
+    }
+
+    pub fn report(&self, id: u64, telemetry: ClientTelemetry) {
+        if !telemetry.boredom_score.is_finite() {
+            return;
+        }
+        let report = Report {
+            at: Instant::now(),
+            boredom: telemetry.boredom_score.clamp(0.0, 1.0),
+            activity: telemetry.activity.filter(|a| a.is_finite()).unwrap_or(0.0).clamp(0.0, 1.0),
+            visible: telemetry.visible.unwrap_or(true),
+        };
+        if let Some(client) = self.inner.lock().unwrap().clients.get_mut(&id) {
+            client.report = Some(report);
+        }
+    }
+
+    pub fn stats(&self) -> CrowdStats {
+        self.inner.lock().unwrap().stats.clone()
+    }
+
+    /// Published figures plus the per-client breakdown behind them.
+    pub fn snapshot(&self) -> Value {
+        let now = Instant::now();
+        let inner = self.inner.lock().unwrap();
+        let mut clients: Vec<Value> = inner
+            .clients
+            .iter()
+            .map(|(id, client)| {
+                let weighed = client.weigh(&self.config, now);
+                let age_ms = client.report.as_ref().map(|r| (now - r.at).as_millis() as u64);
+                json!({
+                    "id": id,
+                    "dwell_secs": (now - client.connected).as_secs(),
+                    "boredom": client.report.as_ref().map(|r| r.boredom),
+                    "activity": client.report.as_ref().map(|r| r.activity),
+                    "visible": client.report.as_ref().map(|r| r.visible),
+                    "report_age_ms": age_ms,
+                    "weight": weighed.map_or(0.0, |(w, _)| w),
+                })
+            })
+            .collect();
+        clients.sort_by_key(|c| c["id"].as_u64());
+        json!({ "stats": inner.stats, "clients": clients })
+    }
+
+    fn aggregate(&self, now: Instant) -> CrowdStats {
+        let inner = self.inner.lock().unwrap();
+        let (mut weight, mut boredom, mut activity, mut bored) = (0.0, 0.0, 0.0, 0.0);
+        let mut clients = 0;
+        for client in inner.clients.values() {
+            let Some((w, b)) = client.weigh(&self.config, now) else { continue };
+            clients += 1;
+            weight += w;
+            boredom += w * b;
+            activity += w * client.report.as_ref().map_or(0.0, |r| r.activity);
+            if b >= self.config.trigger {
+                bored += w;
+            }
+        }
+        if weight <= f32::EPSILON {
+            return CrowdStats::default();
+        }
+        CrowdStats {
+            boredom: boredom / weight,
+            activity: activity / weight,
+            bored_share: bored / weight,
+            clients,
+            weight,
+            chaos: false,
+        }
+    }
+
+    /// Re-evaluates the crowd every second and drives the director through
+    /// the chaos trigger.
+    pub fn spawn(self: &Arc<Self>, director: Arc<LlmDirector>) {
+        let crowd = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EVALUATE_INTERVAL);
+            let mut trigger = ChaosTrigger::new();
+            let mut previous: Option<AiContext> = None;
+            loop {
+                interval.tick().await;
+                let now = Instant::now();
+                let mut stats = crowd.aggregate(now);
+
+                match trigger.evaluate(&crowd.config, now, &stats) {
+                    Some(Action::Trigger) => {
+                        info!(
+                            event = "crowd_chaos_triggered",
+                            boredom = stats.boredom,
+                            bored_share = stats.bored_share,
+                            clients = stats.clients
+                        );
+                        previous = Some(director.inject_chaos(stats.boredom).await);
+                    }
+                    Some(Action::Recover) => {
+                        info!(event = "crowd_chaos_recovered", boredom = stats.boredom);
+                        if let Some(previous) = previous.take() {
+                            director.end_chaos(previous).await;
+                        }
+                    }
+                    None => {}
+                }
+
+                stats.chaos = trigger.in_chaos();
+                crowd.inner.lock().unwrap().stats = stats;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CrowdConfig {
+        CrowdConfig {
+            trigger: 0.8,
+            recover: 0.5,
+            min_share: 0.5,
+            hold: Duration::from_secs(10),
+            cooldown: Duration::from_secs(60),
+            decay: Duration::from_secs(5),
+            dwell: Duration::from_secs(30),
+        }
+    }
+
+    fn stats(boredom: f32, bored_share: f32) -> CrowdStats {
+        CrowdStats { boredom, bored_share, ..Default::default() }
+    }
+
+    #[test]
+    fn triggers_after_hold_recovers_and_cools_down() {
+        let config = config();
+        let mut trigger = ChaosTrigger::new();
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+        let bored = stats(0.9, 1.0);
+
+        assert_eq!(trigger.evaluate(&config, at(0), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(9), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(10), &bored), Some(Action::Trigger));
+        assert!(trigger.in_chaos());
+
+        // Between recover and trigger the chaos holds
+        assert_eq!(trigger.evaluate(&config, at(11), &stats(0.6, 0.0)), None);
+        assert!(trigger.in_chaos());
+        assert_eq!(trigger.evaluate(&config, at(12), &stats(0.4, 0.0)), Some(Action::Recover));
+        assert!(!trigger.in_chaos());
+
+        // Cooldown until 72 s, then a fresh hold
+        assert_eq!(trigger.evaluate(&config, at(40), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(72), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(73), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(82), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(83), &bored), Some(Action::Trigger));
+    }
+
+    #[test]
+    fn a_dip_restarts_the_hold() {
+        let config = config();
+        let mut trigger = ChaosTrigger::new();
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+        let bored = stats(0.9, 1.0);
+
+        assert_eq!(trigger.evaluate(&config, at(0), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(8), &stats(0.7, 0.0)), None);
+        assert_eq!(trigger.evaluate(&config, at(12), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(21), &bored), None);
+        assert_eq!(trigger.evaluate(&config, at(22), &bored), Some(Action::Trigger));
+    }
+
+    #[test]
+    fn a_bored_minority_does_not_trigger() {
+        let config = config();
+        let mut trigger = ChaosTrigger::new();
+        let t0 = Instant::now();
+        for secs in 0..=60 {
+            let action =
+                trigger.evaluate(&config, t0 + Duration::from_secs(secs), &stats(0.9, 0.3));
+            assert_eq!(action, None);
+        }
+        assert!(!trigger.in_chaos());
+    }
+}
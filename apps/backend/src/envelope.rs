@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+/// One-pole attack/release follower. Rises with the attack time constant,
+/// falls with the release one, independent of how often it is fed.
+#[derive(Debug, Clone, Copy)]
+pub struct EnvelopeFollower {
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    value: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(attack_ms: f32, release_ms: f32) -> Self {
+        Self { attack_ms, release_ms, value: 0.0 }
+    }
+
+    pub fn process(&mut self, input: f32, dt_secs: f32) -> f32 {
+        let tau_ms = if input > self.value { self.attack_ms } else { self.release_ms };
+        let coeff = if tau_ms <= 0.0 { 1.0 } else { 1.0 - (-dt_secs * 1000.0 / tau_ms).exp() };
+        self.value += (input - self.value) * coeff;
+        self.value
+    }
+}
+
+/// Smoothed counterparts of the raw band energies and flux in `GlobalState`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SmoothedFeatures {
+    pub low_energy: f32,
+    pub mid_energy: f32,
+    pub high_energy: f32,
+    pub spectral_flux: f32,
+}
+
+/// The per-feature followers used by the Overmind. Time constants come from
+/// `ENVELOPE_<FEATURE>="attack_ms,release_ms"`, e.g. `ENVELOPE_LOW_ENERGY="5,150"`.
+#[derive(Debug, Clone)]
+pub struct FeatureEnvelopes {
+    low: EnvelopeFollower,
+    mid: EnvelopeFollower,
+    high: EnvelopeFollower,
+    flux: EnvelopeFollower,
+}
+
+impl FeatureEnvelopes {
+    pub fn from_env() -> Self {
+        Self {
+            low: follower_from_env("ENVELOPE_LOW_ENERGY", 5.0, 150.0),
+            mid: follower_from_env("ENVELOPE_MID_ENERGY", 5.0, 120.0),
+            high: follower_from_env("ENVELOPE_HIGH_ENERGY", 2.0, 80.0),
+            flux: follower_from_env("ENVELOPE_SPECTRAL_FLUX", 1.0, 60.0),
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        low: f32,
+        mid: f32,
+        high: f32,
+        flux: f32,
+        dt_secs: f32,
+    ) -> SmoothedFeatures {
+        SmoothedFeatures {
+            low_energy: self.low.process(low, dt_secs),
+            mid_energy: self.mid.process(mid, dt_secs),
+            high_energy: self.high.process(high, dt_secs),
+            spectral_flux: self.flux.process(flux, dt_secs),
+        }
+    }
+}
+
+fn follower_from_env(key: &str, attack_ms: f32, release_ms: f32) -> EnvelopeFollower {
+    let parsed = std::env::var(key).ok().and_then(|v| {
+        let (a, r) = v.split_once(',')?;
+        Some((a.trim().parse().ok()?, r.trim().parse().ok()?))
+    });
+
+    match parsed {
+        Some((a, r)) => EnvelopeFollower::new(a, r),
+        None => EnvelopeFollower::new(attack_ms, release_ms),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rises_and_falls_with_their_own_time_constants() {
+        let mut env = EnvelopeFollower::new(10.0, 100.0);
+        // One time constant covers 1 - 1/e of the step
+        let up = env.process(1.0, 0.010);
+        assert!((up - (1.0 - (-1.0f32).exp())).abs() < 1e-5);
+
+        let mut env = EnvelopeFollower::new(10.0, 100.0);
+        env.process(1.0, 1.0);
+        let down = env.process(0.0, 0.010);
+        assert!(down > 0.9, "release is ten times slower: {}", down);
+    }
+
+    #[test]
+    fn does_not_depend_on_the_update_rate() {
+        let mut coarse = EnvelopeFollower::new(5.0, 150.0);
+        let mut fine = EnvelopeFollower::new(5.0, 150.0);
+        let a = coarse.process(0.8, 0.020);
+        let mut b = 0.0;
+        for _ in 0..20 {
+            b = fine.process(0.8, 0.001);
+        }
+        assert!((a - b).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zero_time_constant_passes_the_input_through() {
+        let mut env = EnvelopeFollower::new(0.0, 0.0);
+        assert!((env.process(0.7, 0.001) - 0.7).abs() < 1e-6);
+        assert!((env.process(0.2, 0.001) - 0.2).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,845 @@
+use crate::audio_engine::{AudioFeatures, FrameAnalyzer};
+use crate::color::Palette;
+use crate::state_machine::Genre;
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Shipped defaults; a hand-tuned stand-in for the old threshold heuristic
+const BUILTIN_MODEL: &str = include_str!("../config/genre_model.json");
+// The original five genres, with the tempos and palettes they used to have in code
+const BUILTIN_GENRES: &str = include_str!("../config/genres.json");
+const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+const ONSET_REFRACTORY: Duration = Duration::from_millis(100);
+
+/// Names accepted in a model's `features` list, in `WindowStats` order.
+pub const FEATURE_NAMES: &[&str] = &[
+    "low_mean",
+    "mid_mean",
+    "high_mean",
+    "flux_mean",
+    "low_std",
+    "mid_std",
+    "high_std",
+    "flux_std",
+    "centroid_mean",
+    "rolloff_mean",
+    "onset_rate",
+    "tempo_bpm",
+];
+
+// --- Window Statistics ---
+
+/// Summary of the features over the classifier window.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WindowStats {
+    pub low_mean: f32,
+    pub mid_mean: f32,
+    pub high_mean: f32,
+    pub flux_mean: f32,
+    pub low_std: f32,
+    pub mid_std: f32,
+    pub high_std: f32,
+    pub flux_std: f32,
+    pub centroid_mean: f32,
+    pub rolloff_mean: f32,
+    pub onset_rate: f32, // Onsets per second
+    pub tempo_bpm: f32,  // 0 when no stable beat was found
+}
+
+impl WindowStats {
+    fn get(&self, name: &str) -> Option<f32> {
+        Some(match name {
+            "low_mean" => self.low_mean,
+            "mid_mean" => self.mid_mean,
+            "high_mean" => self.high_mean,
+            "flux_mean" => self.flux_mean,
+            "low_std" => self.low_std,
+            "mid_std" => self.mid_std,
+            "high_std" => self.high_std,
+            "flux_std" => self.flux_std,
+            "centroid_mean" => self.centroid_mean,
+            "rolloff_mean" => self.rolloff_mean,
+            "onset_rate" => self.onset_rate,
+            "tempo_bpm" => self.tempo_bpm,
+            _ => return None,
+        })
+    }
+}
+
+/// Rolling window of frames plus a simple flux onset detector for tempo.
+struct FeatureWindow {
+    span: Duration,
+    frames: VecDeque<(Duration, [f32; 6])>, // low, mid, high, flux, centroid, rolloff
+    onsets: VecDeque<Duration>,
+    prev_flux: f32,
+}
+
+impl FeatureWindow {
+    fn new(span: Duration) -> Self {
+        Self { span, frames: VecDeque::new(), onsets: VecDeque::new(), prev_flux: 0.0 }
+    }
+
+    /// Returns whether the frame is an onset.
+    fn push(&mut self, now: Duration, f: &AudioFeatures) -> bool {
+        while self.frames.front().is_some_and(|(t, _)| now.saturating_sub(*t) > self.span) {
+            self.frames.pop_front();
+        }
+        while self.onsets.front().is_some_and(|t| now.saturating_sub(*t) > self.span) {
+            self.onsets.pop_front();
+        }
+
+        // Onset: flux rising above 1.5x the window average, at most one per refractory period
+        let mean_flux = self.mean(3);
+        let rising = f.spectral_flux > self.prev_flux;
+        let strong = f.spectral_flux > mean_flux * 1.5 + 0.005;
+        let clear = self.onsets.back().is_none_or(|t| now.saturating_sub(*t) >= ONSET_REFRACTORY);
+        let onset = rising && strong && clear;
+        if onset {
+            self.onsets.push_back(now);
+        }
+        self.prev_flux = f.spectral_flux;
+
+        let values = [
+            f.low_energy,
+            f.mid_energy,
+            f.high_energy,
+            f.spectral_flux,
+            f.spectral_centroid,
+            f.spectral_rolloff,
+        ];
+        self.frames.push_back((now, values));
+        onset
+    }
+
+    fn covered(&self) -> Duration {
+        match (self.frames.front(), self.frames.back()) {
+            (Some((a, _)), Some((b, _))) => b.saturating_sub(*a),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn mean(&self, i: usize) -> f32 {
+        let n = self.frames.len().max(1) as f32;
+        self.frames.iter().map(|(_, v)| v[i]).sum::<f32>() / n
+    }
+
+    fn std(&self, i: usize) -> f32 {
+        let n = self.frames.len().max(1) as f32;
+        let mean = self.mean(i);
+        (self.frames.iter().map(|(_, v)| (v[i] - mean).powi(2)).sum::<f32>() / n).sqrt()
+    }
+
+    /// Median inter-onset interval folded into 70..180 BPM.
+    fn tempo(&self) -> Option<f32> {
+        if self.onsets.len() < 4 {
+            return None;
+        }
+        let mut intervals: Vec<f32> = self
+            .onsets
+            .iter()
+            .zip(self.onsets.iter().skip(1))
+            .map(|(a, b)| b.saturating_sub(*a).as_secs_f32())
+            .collect();
+        intervals.sort_by(|a, b| a.total_cmp(b));
+        let median = intervals[intervals.len() / 2];
+        if median <= 0.0 {
+            return None;
+        }
+
+        let mut bpm = 60.0 / median;
+        while bpm < 70.0 {
+            bpm *= 2.0;
+        }
+        while bpm > 180.0 {
+            bpm /= 2.0;
+        }
+        Some(bpm)
+    }
+
+    fn stats(&self) -> WindowStats {
+        let secs = self.covered().as_secs_f32().max(1e-3);
+        WindowStats {
+            low_mean: self.mean(0),
+            mid_mean: self.mean(1),
+            high_mean: self.mean(2),
+            flux_mean: self.mean(3),
+            low_std: self.std(0),
+            mid_std: self.std(1),
+            high_std: self.std(2),
+            flux_std: self.std(3),
+            centroid_mean: self.mean(4),
+            rolloff_mean: self.mean(5),
+            onset_rate: self.onsets.len() as f32 / secs,
+            tempo_bpm: self.tempo().unwrap_or(0.0),
+        }
+    }
+}
+
+// --- Model ---
+
+/// Optional z-score normalisation applied before the model, per feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Normalize {
+    pub mean: Vec<f32>,
+    pub std: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogisticClass {
+    pub genre: String, // Classifier label, mapped onto the taxonomy
+    pub weights: Vec<f32>,
+    #[serde(default)]
+    pub bias: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Example {
+    pub genre: String,
+    pub x: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModelKind {
+    /// Multinomial logistic regression (softmax over `w·x + b` per class).
+    Logistic { classes: Vec<LogisticClass> },
+    /// k nearest labelled examples, inverse-distance weighted.
+    Knn { k: usize, examples: Vec<Example> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenreModel {
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub normalize: Option<Normalize>,
+    /// Below this top probability the previous genre is kept.
+    #[serde(default)]
+    pub min_confidence: f32,
+    #[serde(flatten)]
+    pub kind: ModelKind,
+}
+
+impl GenreModel {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let model: GenreModel = serde_json::from_str(text)?;
+        model.validate()?;
+        Ok(model)
+    }
+
+    /// `GENRE_MODEL_PATH` if set and valid, otherwise the built-in model.
+    pub fn from_env() -> Self {
+        let builtin = || Self::parse(BUILTIN_MODEL).expect("Invalid built-in genre model");
+        let Ok(path) = std::env::var("GENRE_MODEL_PATH") else { return builtin() };
+
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Self::parse(&text));
+        match loaded {
+            Ok(model) => {
+                println!("🎼 [Genre] Loaded model {:?}", path);
+                model
+            }
+            Err(e) => {
+                println!("⚠️ [Genre] Model {:?} rejected, using built-in: {}", path, e);
+                builtin()
+            }
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let n = self.features.len();
+        if n == 0 {
+            bail!("model has no features");
+        }
+        for name in &self.features {
+            if !FEATURE_NAMES.contains(&name.as_str()) {
+                bail!("unknown feature '{}' (expected one of {:?})", name, FEATURE_NAMES);
+            }
+        }
+        if let Some(norm) = &self.normalize {
+            if norm.mean.len() != n || norm.std.len() != n {
+                bail!("normalize.mean/std must have {} entries", n);
+            }
+        }
+        match &self.kind {
+            ModelKind::Logistic { classes } => {
+                if classes.is_empty() {
+                    bail!("logistic model has no classes");
+                }
+                if let Some(c) = classes.iter().find(|c| c.weights.len() != n) {
+                    bail!("{:?}: expected {} weights, got {}", c.genre, n, c.weights.len());
+                }
+            }
+            ModelKind::Knn { k, examples } => {
+                if *k == 0 || examples.is_empty() {
+                    bail!("knn model needs k > 0 and at least one example");
+                }
+                if examples.iter().any(|e| e.x.len() != n) {
+                    bail!("every knn example needs {} values", n);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn vector(&self, stats: &WindowStats) -> Vec<f32> {
+        self.features
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let x = stats.get(name).unwrap_or(0.0);
+                match &self.normalize {
+                    Some(n) => (x - n.mean[i]) / n.std[i].max(1e-6),
+                    None => x,
+                }
+            })
+            .collect()
+    }
+
+    /// Probability per classifier label; sums to 1.
+    pub fn predict(&self, stats: &WindowStats) -> BTreeMap<String, f32> {
+        let x = self.vector(stats);
+        let mut scores: BTreeMap<String, f32> = BTreeMap::new();
+
+        match &self.kind {
+            ModelKind::Logistic { classes } => {
+                let logits: Vec<f32> = classes
+                    .iter()
+                    .map(|c| c.bias + c.weights.iter().zip(&x).map(|(w, v)| w * v).sum::<f32>())
+                    .collect();
+                let max = logits.iter().cloned().fold(f32::MIN, f32::max);
+                for (c, logit) in classes.iter().zip(logits) {
+                    *scores.entry(c.genre.clone()).or_default() += (logit - max).exp();
+                }
+            }
+            ModelKind::Knn { k, examples } => {
+                let mut nearest: Vec<(f32, &str)> = examples
+                    .iter()
+                    .map(|e| {
+                        let d = e.x.iter().zip(&x).map(|(a, b)| (a - b).powi(2)).sum::<f32>();
+                        (d.sqrt(), e.genre.as_str())
+                    })
+                    .collect();
+                nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (d, label) in nearest.into_iter().take(*k) {
+                    *scores.entry(label.to_string()).or_default() += 1.0 / (d + 1e-3);
+                }
+            }
+        }
+
+        let total: f32 = scores.values().sum();
+        if total > 0.0 {
+            scores.values_mut().for_each(|p| *p /= total);
+        }
+        scores
+    }
+
+    fn labels(&self) -> Vec<&str> {
+        match &self.kind {
+            ModelKind::Logistic { classes } => classes.iter().map(|c| c.genre.as_str()).collect(),
+            ModelKind::Knn { examples, .. } => examples.iter().map(|e| e.genre.as_str()).collect(),
+        }
+    }
+}
+
+// --- Taxonomy ---
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenreDef {
+    /// Published as `genre` on the wire.
+    pub name: String,
+    /// Classifier labels that map to this genre (the name always matches too).
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Typical tempo range in BPM.
+    pub tempo: [f32; 2],
+    /// Tempo assumed when none is measured; defaults to the middle of `tempo`.
+    #[serde(default)]
+    pub bpm: Option<f32>,
+    #[serde(default)]
+    pub palette: Option<Palette>,
+    /// Free-text hint passed to the AI director.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl GenreDef {
+    pub fn typical_bpm(&self) -> f32 {
+        self.bpm.unwrap_or((self.tempo[0] + self.tempo[1]) / 2.0)
+    }
+
+    /// Doubles or halves a measured tempo until it falls in this genre's range,
+    /// correcting the usual octave errors of onset-based tempo estimates.
+    pub fn fold_tempo(&self, bpm: f32) -> f32 {
+        let [lo, hi] = self.tempo;
+        let mut folded = bpm;
+        while folded < lo && folded * 2.0 <= hi {
+            folded *= 2.0;
+        }
+        while folded > hi && folded / 2.0 >= lo {
+            folded /= 2.0;
+        }
+        folded
+    }
+
+    /// One-line summary for the AI director's prompt.
+    pub fn describe(&self) -> String {
+        let mut text = format!("{:.0}-{:.0} BPM", self.tempo[0], self.tempo[1]);
+        if let Some(p) = &self.palette {
+            text.push_str(&format!(", palette {} / {}", p.primary, p.secondary));
+        }
+        if let Some(d) = &self.description {
+            text.push_str(&format!(", {}", d));
+        }
+        text
+    }
+}
+
+/// The configurable set of genres (`GENRES_PATH`, built-in `config/genres.json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenreTaxonomy {
+    /// Genre reported when the classifier's label matches nothing.
+    #[serde(default = "default_fallback")]
+    pub fallback: String,
+    pub genres: Vec<GenreDef>,
+}
+
+fn default_fallback() -> String {
+    Genre::unknown().as_str().to_string()
+}
+
+impl GenreTaxonomy {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let taxonomy: GenreTaxonomy = serde_json::from_str(text)?;
+        taxonomy.validate()?;
+        Ok(taxonomy)
+    }
+
+    /// Loaded once per process; every classifier and the AI director share it.
+    pub fn global() -> &'static GenreTaxonomy {
+        static TAXONOMY: OnceLock<GenreTaxonomy> = OnceLock::new();
+        TAXONOMY.get_or_init(Self::from_env)
+    }
+
+    fn from_env() -> Self {
+        let builtin = || Self::parse(BUILTIN_GENRES).expect("Invalid built-in genre taxonomy");
+        let Ok(path) = std::env::var("GENRES_PATH") else { return builtin() };
+
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Self::parse(&text));
+        match loaded {
+            Ok(taxonomy) => {
+                println!("🎼 [Genre] Loaded {} genres from {:?}", taxonomy.genres.len(), path);
+                taxonomy
+            }
+            Err(e) => {
+                println!("⚠️ [Genre] Taxonomy {:?} rejected, using built-in: {}", path, e);
+                builtin()
+            }
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+        let mut seen: Vec<String> = Vec::new();
+        for def in &self.genres {
+            if def.name.trim().is_empty() {
+                errors.push("genre with empty name".to_string());
+            }
+            for label in std::iter::once(&def.name).chain(&def.labels) {
+                let key = label.to_lowercase();
+                if seen.contains(&key) {
+                    errors.push(format!("{}: name or label '{}' used twice", def.name, label));
+                }
+                seen.push(key);
+            }
+            let [lo, hi] = def.tempo;
+            if !(lo > 0.0 && lo <= hi) {
+                errors.push(format!("{}: tempo range {}..{} is invalid", def.name, lo, hi));
+            }
+            if let Some(color) = def.palette.as_ref().and_then(|p| p.invalid_color()) {
+                errors.push(format!("{}: '{}' is not a #RRGGBB colour", def.name, color));
+            }
+        }
+        if self.find(&self.fallback).is_none() {
+            errors.push(format!("fallback '{}' is not a listed genre", self.fallback));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!(errors.join("; "))
+        }
+    }
+
+    /// The genre whose name or one of whose labels matches (case-insensitive).
+    pub fn find(&self, label: &str) -> Option<&GenreDef> {
+        self.genres.iter().find(|g| {
+            g.name.eq_ignore_ascii_case(label)
+                || g.labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+        })
+    }
+
+    pub fn resolve(&self, label: &str) -> &GenreDef {
+        self.find(label).unwrap_or_else(|| self.fallback_def())
+    }
+
+    pub fn get(&self, genre: &Genre) -> &GenreDef {
+        self.resolve(genre.as_str())
+    }
+
+    fn fallback_def(&self) -> &GenreDef {
+        self.find(&self.fallback).expect("validated fallback genre")
+    }
+}
+
+// --- Classifier ---
+
+#[derive(Debug, Clone)]
+pub struct GenreEstimate {
+    pub genre: Genre,
+    pub confidence: f32,
+    pub probabilities: BTreeMap<String, f32>,
+    pub tempo_bpm: Option<f32>,
+    pub stats: WindowStats, // The window the estimate was made from, reused by the mood estimator
+}
+
+/// Aggregates frames over a window (`GENRE_WINDOW_SECS`, default 10) and
+/// classifies the window with a `GenreModel`.
+pub struct GenreClassifier {
+    window: FeatureWindow,
+    model: GenreModel,
+}
+
+impl GenreClassifier {
+    pub fn new(model: GenreModel, window: Duration) -> Self {
+        let taxonomy = GenreTaxonomy::global();
+        let mut unmapped: Vec<&str> =
+            model.labels().into_iter().filter(|l| taxonomy.find(l).is_none()).collect();
+        unmapped.sort();
+        unmapped.dedup();
+        if !unmapped.is_empty() {
+            println!(
+                "⚠️ [Genre] Model labels {:?} match no configured genre; reported as '{}'",
+                unmapped, taxonomy.fallback
+            );
+        }
+        Self { window: FeatureWindow::new(window), model }
+    }
+
+    pub fn from_env() -> Self {
+        let window = std::env::var("GENRE_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|s| *s > 0.0)
+            .and_then(|s| Duration::try_from_secs_f32(s).ok())
+            .unwrap_or(DEFAULT_WINDOW);
+        Self::new(GenreModel::from_env(), window)
+    }
+
+    /// Adds a frame; returns whether it is an onset (beat tracking).
+    pub fn push(&mut self, now: Duration, features: &AudioFeatures) -> bool {
+        self.window.push(now, features)
+    }
+
+    /// `None` until half the window has been seen, so startup does not guess from a blip.
+    pub fn classify(&self) -> Option<GenreEstimate> {
+        if self.window.covered() < self.window.span / 2 {
+            return None;
+        }
+        let stats = self.window.stats();
+
+        // Several labels may feed one genre; their probabilities add up
+        let taxonomy = GenreTaxonomy::global();
+        let mut probabilities: BTreeMap<String, f32> = BTreeMap::new();
+        for (label, p) in self.model.predict(&stats) {
+            *probabilities.entry(taxonomy.resolve(&label).name.clone()).or_default() += p;
+        }
+        let (name, confidence) =
+            probabilities.iter().max_by(|a, b| a.1.total_cmp(b.1)).map(|(n, p)| (n.clone(), *p))?;
+        let genre = Genre::new(name);
+        let tempo_bpm = (stats.tempo_bpm > 0.0).then_some(stats.tempo_bpm);
+
+        Some(GenreEstimate { genre, confidence, probabilities, tempo_bpm, stats })
+    }
+
+    pub fn min_confidence(&self) -> f32 {
+        self.model.min_confidence
+    }
+}
+
+// --- Offline Evaluation ---
+
+const EVAL_BUFFER_FRAMES: usize = 1024;
+
+/// `backend eval-genre <dir>`: runs every `<dir>/<Genre>/*.wav` clip through the
+/// live feature extraction and classifier and prints accuracy and a confusion matrix.
+pub fn evaluate(dir: &Path) -> anyhow::Result<()> {
+    let model = GenreModel::from_env();
+    let taxonomy = GenreTaxonomy::global();
+    let mut confusion: BTreeMap<(String, String), u32> = BTreeMap::new();
+    let mut labels: Vec<String> = Vec::new();
+    let (mut total, mut correct) = (0u32, 0u32);
+
+    let mut clips: Vec<(Genre, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("reading {:?}", dir))? {
+        let path = entry?.path();
+        let Some(label) = path.file_name().and_then(|n| n.to_str()) else { continue };
+        if !path.is_dir() {
+            continue;
+        }
+        let genre = taxonomy
+            .find(label)
+            .map(|def| Genre::new(def.name.clone()))
+            .ok_or_else(|| anyhow!("directory '{}' is not a configured genre", label))?;
+        for clip in std::fs::read_dir(&path)? {
+            let clip = clip?.path();
+            if clip.extension().is_some_and(|ext| ext == "wav") {
+                clips.push((genre.clone(), clip));
+            }
+        }
+    }
+    clips.sort_by(|a, b| a.1.cmp(&b.1));
+    if clips.is_empty() {
+        bail!("no labelled clips found under {:?}", dir);
+    }
+
+    for (expected, path) in clips {
+        let estimate = match classify_clip(&path, &model) {
+            Ok(Some(e)) => e,
+            Ok(None) => {
+                println!("⚠️ [Genre] {:?} shorter than half the window, skipped", path);
+                continue;
+            }
+            Err(e) => {
+                println!("⚠️ [Genre] {:?}: {}", path, e);
+                continue;
+            }
+        };
+
+        let (want, got) = (expected.as_str().to_string(), estimate.genre.as_str().to_string());
+        println!(
+            "{} {:?}: expected {}, got {} ({:.0}%)",
+            if want == got { "✅" } else { "❌" },
+            path.file_name().unwrap_or_default(),
+            want,
+            got,
+            estimate.confidence * 100.0
+        );
+        for label in [&want, &got] {
+            if !labels.contains(label) {
+                labels.push(label.clone());
+            }
+        }
+        total += 1;
+        correct += (want == got) as u32;
+        *confusion.entry((want, got)).or_default() += 1;
+    }
+
+    println!(
+        "\n📊 [Genre] Accuracy: {}/{} ({:.1}%)",
+        correct,
+        total,
+        100.0 * correct as f32 / total.max(1) as f32
+    );
+    println!("Confusion (rows = expected, columns = predicted):");
+    println!("{:>10} {}", "", labels.iter().map(|l| format!("{:>9}", l)).collect::<String>());
+    for want in &labels {
+        let row: String = labels
+            .iter()
+            .map(|got| format!("{:>9}", confusion.get(&(want.clone(), got.clone())).unwrap_or(&0)))
+            .collect();
+        println!("{:>10} {}", want, row);
+    }
+    Ok(())
+}
+
+/// Classifies the whole clip as one window.
+fn classify_clip(path: &Path, model: &GenreModel) -> anyhow::Result<Option<GenreEstimate>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            let ints: Vec<i32> = reader.samples::<i32>().collect::<Result<_, _>>()?;
+            ints.into_iter().map(|s| s as f32 / scale).collect()
+        }
+    };
+
+    let span = Duration::from_secs_f64(
+        samples.len() as f64 / spec.channels.max(1) as f64 / spec.sample_rate.max(1) as f64,
+    );
+    let mut analyzer = FrameAnalyzer::new(spec.sample_rate, spec.channels);
+    let mut classifier = GenreClassifier::new(model.clone(), span);
+
+    for chunk in samples.chunks(EVAL_BUFFER_FRAMES * spec.channels.max(1) as usize) {
+        let features = analyzer.analyze(chunk);
+        let now = Duration::from_secs_f64(features.sample_clock as f64 / spec.sample_rate as f64);
+        classifier.push(now, &features);
+    }
+    Ok(classifier.classify())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(json: &str) -> anyhow::Result<GenreModel> {
+        GenreModel::parse(json)
+    }
+
+    fn stats(low_mean: f32, flux_mean: f32) -> WindowStats {
+        WindowStats { low_mean, flux_mean, ..WindowStats::default() }
+    }
+
+    #[test]
+    fn logistic_scores_are_a_softmax() {
+        let m = model(
+            r#"{ "features": ["low_mean", "flux_mean"], "kind": "logistic", "classes": [
+                { "genre": "Ambient", "weights": [-4.0, 0.0], "bias": 2.0 },
+                { "genre": "Techno", "weights": [4.0, 0.0], "bias": -2.0 } ] }"#,
+        )
+        .unwrap();
+        let quiet = m.predict(&stats(0.0, 0.0));
+        let loud = m.predict(&stats(1.0, 0.0));
+        assert!((quiet.values().sum::<f32>() - 1.0).abs() < 1e-5);
+        // logits 2 and -2: e^4 / (e^4 + 1)
+        assert!((quiet["Ambient"] - 0.98201).abs() < 1e-4);
+        assert!(loud["Techno"] > 0.98);
+    }
+
+    #[test]
+    fn knn_weighs_the_nearest_examples() {
+        let m = model(
+            r#"{ "features": ["low_mean"], "kind": "knn", "k": 2, "examples": [
+                { "genre": "Ambient", "x": [0.1] },
+                { "genre": "Techno", "x": [0.8] },
+                { "genre": "Techno", "x": [0.9] } ] }"#,
+        )
+        .unwrap();
+        let p = m.predict(&stats(0.85, 0.0));
+        assert_eq!(p.get("Ambient"), None);
+        assert!((p["Techno"] - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rejects_models_that_do_not_fit_their_features() {
+        let unknown = r#"{ "features": ["loudness"], "kind": "knn", "k": 1,
+            "examples": [{ "genre": "Techno", "x": [1.0] }] }"#;
+        assert!(model(unknown).unwrap_err().to_string().contains("unknown feature"));
+
+        let short = r#"{ "features": ["low_mean", "flux_mean"], "kind": "logistic",
+            "classes": [{ "genre": "Techno", "weights": [1.0] }] }"#;
+        assert!(model(short).unwrap_err().to_string().contains("expected 2 weights"));
+
+        let norm = r#"{ "features": ["low_mean"], "normalize": { "mean": [0.0, 0.0], "std": [1.0] },
+            "kind": "knn", "k": 1, "examples": [{ "genre": "Techno", "x": [1.0] }] }"#;
+        assert!(model(norm).is_err());
+        assert!(model(BUILTIN_MODEL).is_ok());
+    }
+
+    #[test]
+    fn normalises_before_scoring() {
+        let m = model(
+            r#"{ "features": ["low_mean"], "normalize": { "mean": [0.5], "std": [0.25] },
+                "kind": "knn", "k": 1, "examples": [
+                { "genre": "Ambient", "x": [-2.0] }, { "genre": "Techno", "x": [2.0] } ] }"#,
+        )
+        .unwrap();
+        assert_eq!(m.vector(&stats(1.0, 0.0)), vec![2.0]);
+        assert!(m.predict(&stats(0.9, 0.0))["Techno"] > 0.99);
+    }
+
+    #[test]
+    fn window_measures_tempo_from_flux_onsets() {
+        let mut window = FeatureWindow::new(Duration::from_secs(10));
+        let mut onsets = 0;
+        // 10 ms frames with a flux spike every 500 ms: 120 BPM
+        for i in 0..600u64 {
+            let spectral_flux = if i % 50 == 0 { 0.5 } else { 0.0 };
+            let f = AudioFeatures { spectral_flux, ..AudioFeatures::default() };
+            if window.push(Duration::from_millis(i * 10), &f) {
+                onsets += 1;
+            }
+        }
+        assert_eq!(onsets, 12);
+        let stats = window.stats();
+        assert!((stats.tempo_bpm - 120.0).abs() < 0.5, "{}", stats.tempo_bpm);
+        assert!((stats.onset_rate - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn classifier_waits_for_half_a_window() {
+        let mut classifier =
+            GenreClassifier::new(model(BUILTIN_MODEL).unwrap(), Duration::from_secs(4));
+        let f = AudioFeatures { low_energy: 0.6, mid_energy: 0.4, ..AudioFeatures::default() };
+        for i in 0..=190u64 {
+            classifier.push(Duration::from_millis(i * 10), &f);
+        }
+        assert!(classifier.classify().is_none());
+        for i in 191..=200u64 {
+            classifier.push(Duration::from_millis(i * 10), &f);
+        }
+        let estimate = classifier.classify().unwrap();
+        assert!((estimate.probabilities.values().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert_eq!(estimate.confidence, estimate.probabilities[estimate.genre.as_str()]);
+        assert_eq!(estimate.tempo_bpm, None);
+    }
+
+    fn taxonomy(genres: &str) -> anyhow::Result<GenreTaxonomy> {
+        GenreTaxonomy::parse(&format!(r#"{{ "fallback": "Unknown", "genres": [{}] }}"#, genres))
+    }
+
+    const UNKNOWN: &str = r#"{ "name": "Unknown", "tempo": [60, 200] }"#;
+
+    #[test]
+    fn labels_resolve_to_their_genre_and_the_rest_to_the_fallback() {
+        let dnb = r#"{ "name": "DnB", "labels": ["Drum and Bass"], "tempo": [160, 180] }"#;
+        let t = taxonomy(&format!("{}, {}", UNKNOWN, dnb)).unwrap();
+        assert_eq!(t.resolve("drum AND bass").name, "DnB");
+        assert_eq!(t.resolve("dnb").name, "DnB");
+        assert_eq!(t.resolve("Polka").name, "Unknown");
+        assert_eq!(t.get(&Genre::new("DnB")).typical_bpm(), 170.0);
+        assert!(GenreTaxonomy::parse(BUILTIN_GENRES).is_ok());
+    }
+
+    #[test]
+    fn validation_lists_every_problem() {
+        let bad = r##"{ "name": "Techno", "labels": ["unknown"], "tempo": [150, 120],
+            "palette": { "primary": "cyan", "secondary": "#000000" } }"##;
+        let err = taxonomy(&format!("{}, {}", UNKNOWN, bad)).unwrap_err().to_string();
+        assert!(err.contains("'unknown' used twice"), "{}", err);
+        assert!(err.contains("tempo range 150..120"), "{}", err);
+        assert!(err.contains("'cyan' is not a #RRGGBB colour"), "{}", err);
+
+        let no_fallback = taxonomy(r#"{ "name": "Techno", "tempo": [120, 150] }"#);
+        assert!(no_fallback.unwrap_err().to_string().contains("fallback 'Unknown'"));
+    }
+
+    #[test]
+    fn tempos_fold_into_the_genre_range() {
+        let t = taxonomy(&format!(
+            "{}, {}",
+            UNKNOWN, r#"{ "name": "Techno", "tempo": [120, 150], "bpm": 128 }"#
+        ))
+        .unwrap();
+        let techno = t.resolve("Techno");
+        assert_eq!(techno.fold_tempo(64.0), 128.0);
+        assert_eq!(techno.fold_tempo(260.0), 130.0);
+        assert_eq!(techno.fold_tempo(135.0), 135.0);
+        // Out of range either way: left as measured
+        assert_eq!(techno.fold_tempo(100.0), 100.0);
+        assert_eq!(techno.typical_bpm(), 128.0);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::state_machine::{GlobalState, PipelineEvent};
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+const DEFAULT_RETENTION: Duration = Duration::from_secs(600);
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(200);
+const DEFAULT_MAX_EVENTS: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub struct HistoryConfig {
+    /// How far back frames are kept; zero disables the history.
+    pub retention: Duration,
+    /// At most one frame is kept per interval.
+    pub interval: Duration,
+    pub max_events: usize,
+}
+
+impl HistoryConfig {
+    /// `HISTORY_SECS` (default 600, 0 = off), `HISTORY_INTERVAL_MS` (default 200)
+    /// and `HISTORY_MAX_EVENTS` (default 2000).
+    pub fn from_env() -> Self {
+        let env = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok());
+        Self {
+            retention: env("HISTORY_SECS").map(Duration::from_secs).unwrap_or(DEFAULT_RETENTION),
+            interval: env("HISTORY_INTERVAL_MS")
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_INTERVAL),
+            max_events: env("HISTORY_MAX_EVENTS").map_or(DEFAULT_MAX_EVENTS, |n| n as usize),
+        }
+    }
+}
+
+struct HistoryFrame {
+    at: Instant,
+    unix_ms: u64,
+    state: Arc<GlobalState>,
+}
+
+/// Every event of every frame, including the ones between kept frames.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEvent {
+    pub unix_ms: u64,
+    #[serde(flatten)]
+    pub event: PipelineEvent,
+}
+
+#[derive(Default)]
+struct Inner {
+    frames: VecDeque<HistoryFrame>,
+    events: VecDeque<HistoryEvent>,
+    vibe_seq: Option<u64>,
+    phase_seq: Option<u64>,
+}
+
+/// Query string of `GET /api/v1/history`. Times are Unix milliseconds.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryQuery {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// Shorthand for `from = now - last_secs`.
+    pub last_secs: Option<u64>,
+    /// Comma-separated dotted paths, e.g. `bpm,mood.valence,smoothed.low_energy`.
+    pub fields: Option<String>,
+    /// Thins the frames further, keeping at most one per step.
+    pub step_ms: Option<u64>,
+}
+
+/// Bounded, downsampled record of the published state stream, fed from its
+/// own broadcast subscription so it sees exactly what clients see.
+pub struct StateHistory {
+    config: HistoryConfig,
+    inner: Mutex<Inner>,
+}
+
+impl StateHistory {
+    pub fn spawn(config: HistoryConfig, mut rx: broadcast::Receiver<GlobalState>) -> Arc<Self> {
+        let history = Arc::new(Self { config, inner: Mutex::new(Inner::default()) });
+        if history.config.retention.is_zero() {
+            println!("⏸️ [History] HISTORY_SECS=0. State history disabled.");
+            return history;
+        }
+
+        let shared = history.clone();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(state) => shared.push(state),
+                    // Missed frames only thin the history further
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        history
+    }
+
+    fn push(&self, state: GlobalState) {
+        self.push_at(state, Instant::now(), unix_ms());
+    }
+
+    fn push_at(&self, state: GlobalState, now: Instant, unix_ms: u64) {
+        let mut inner = self.inner.lock().unwrap();
+
+        for event in &state.events {
+            inner.events.push_back(HistoryEvent { unix_ms, event: event.clone() });
+        }
+        // Transitions are state fields, so they are turned into events here
+        if let Some(t) = &state.last_transition {
+            if inner.vibe_seq.replace(t.seq).is_some_and(|seq| seq != t.seq) {
+                let event = transition_event("vibe_transition", t);
+                inner.events.push_back(HistoryEvent { unix_ms, event });
+            }
+        }
+        if let Some(t) = &state.last_phase_transition {
+            if inner.phase_seq.replace(t.seq).is_some_and(|seq| seq != t.seq) {
+                let event = transition_event("phase_transition", t);
+                inner.events.push_back(HistoryEvent { unix_ms, event });
+            }
+        }
+        while inner.events.len() > self.config.max_events {
+            inner.events.pop_front();
+        }
+
+        let due = inner.frames.back().is_none_or(|f| now - f.at >= self.config.interval);
+        if due {
+            inner.frames.push_back(HistoryFrame { at: now, unix_ms, state: Arc::new(state) });
+        }
+        while inner.frames.front().is_some_and(|f| now - f.at > self.config.retention) {
+            inner.frames.pop_front();
+        }
+    }
+
+    /// Kept frames from the last `span`, oldest first.
+    pub fn recent(&self, span: Duration) -> Vec<Arc<GlobalState>> {
+        let now = Instant::now();
+        let inner = self.inner.lock().unwrap();
+        inner.frames.iter().filter(|f| now - f.at <= span).map(|f| f.state.clone()).collect()
+    }
+
+    pub fn query(&self, query: &HistoryQuery) -> anyhow::Result<Value> {
+        let fields: Vec<&str> = query
+            .fields
+            .as_deref()
+            .map(|f| f.split(',').map(str::trim).filter(|f| !f.is_empty()).collect())
+            .unwrap_or_default();
+        validate_fields(&fields)?;
+
+        let from = match (query.from, query.last_secs) {
+            (Some(_), Some(_)) => bail!("use either from or last_secs"),
+            (Some(from), None) => from,
+            (None, Some(secs)) => unix_ms().saturating_sub(secs * 1000),
+            (None, None) => 0,
+        };
+        let to = query.to.unwrap_or(u64::MAX);
+        let step = query.step_ms.unwrap_or(0);
+
+        // Serialize outside the lock; frames are shared, not copied
+        let (frames, events): (Vec<(u64, Arc<GlobalState>)>, Vec<HistoryEvent>) = {
+            let inner = self.inner.lock().unwrap();
+            let in_range = |t: u64| (from..=to).contains(&t);
+            let mut last: Option<u64> = None;
+            let frames = inner
+                .frames
+                .iter()
+                .filter(|f| in_range(f.unix_ms))
+                .filter(|f| {
+                    let keep = last.is_none_or(|t| f.unix_ms.saturating_sub(t) >= step);
+                    if keep {
+                        last = Some(f.unix_ms);
+                    }
+                    keep
+                })
+                .map(|f| (f.unix_ms, f.state.clone()))
+                .collect();
+            let events = inner.events.iter().filter(|e| in_range(e.unix_ms)).cloned().collect();
+            (frames, events)
+        };
+
+        let frames: Vec<Value> = frames
+            .into_iter()
+            .map(|(unix_ms, state)| {
+                let full = serde_json::to_value(&*state).unwrap_or(Value::Null);
+                let mut frame = Map::new();
+                frame.insert("unix_ms".into(), unix_ms.into());
+                if fields.is_empty() {
+                    frame.insert("state".into(), full);
+                } else {
+                    for path in &fields {
+                        let value = lookup(&full, path).cloned().unwrap_or(Value::Null);
+                        frame.insert(path.to_string(), value);
+                    }
+                }
+                Value::Object(frame)
+            })
+            .collect();
+
+        Ok(json!({
+            "interval_ms": self.config.interval.as_millis() as u64,
+            "retention_secs": self.config.retention.as_secs(),
+            "frames": frames,
+            "events": events,
+        }))
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+fn transition_event<T: Serialize>(name: &str, transition: &T) -> PipelineEvent {
+    let data = serde_json::to_value(transition).unwrap_or(Value::Null);
+    let at_ms = data["at_ms"].as_u64().unwrap_or(0);
+    PipelineEvent { source: "overmind".into(), name: name.into(), data, at_ms }
+}
+
+fn lookup<'a>(view: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(view, |v, key| v.get(key))
+}
+
+/// Rejects paths that no frame can have, like rule conditions do.
+fn validate_fields(fields: &[&str]) -> anyhow::Result<()> {
+    let shape = serde_json::to_value(GlobalState::default())?;
+    let unknown: Vec<&str> = fields
+        .iter()
+        .copied()
+        .filter(|f| !f.starts_with("params.") && !f.starts_with("lfos."))
+        .filter(|f| {
+            // Optional sections (e.g. last_transition) are null until first set
+            let mut v = &shape;
+            for key in f.split('.') {
+                match v.get(key) {
+                    Some(Value::Null) => return false,
+                    Some(next) => v = next,
+                    None => return true,
+                }
+            }
+            false
+        })
+        .collect();
+    if !unknown.is_empty() {
+        bail!("unknown field(s): {}", unknown.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_machine::VibeState;
+    use crate::vibe::VibeTransition;
+
+    const T0: u64 = 1_700_000_000_000;
+
+    fn history(retention_secs: u64, max_events: usize) -> StateHistory {
+        let config = HistoryConfig {
+            retention: Duration::from_secs(retention_secs),
+            interval: DEFAULT_INTERVAL,
+            max_events,
+        };
+        StateHistory { config, inner: Mutex::new(Inner::default()) }
+    }
+
+    /// Pushes a frame every 50 ms over `0..=to_ms`, with `bpm` counting the milliseconds.
+    fn feed(history: &StateHistory, start: Instant, to_ms: u64) {
+        for ms in (0..=to_ms).step_by(50) {
+            let state = GlobalState { bpm: ms as f32, ..GlobalState::default() };
+            history.push_at(state, start + Duration::from_millis(ms), T0 + ms);
+        }
+    }
+
+    fn query(history: &StateHistory, query: HistoryQuery) -> Value {
+        history.query(&query).unwrap()
+    }
+
+    fn times(result: &Value) -> Vec<u64> {
+        let frames = result["frames"].as_array().unwrap();
+        frames.iter().map(|f| f["unix_ms"].as_u64().unwrap() - T0).collect()
+    }
+
+    #[test]
+    fn keeps_one_frame_per_interval() {
+        let history = history(600, 100);
+        feed(&history, Instant::now(), 1000);
+        let all = query(&history, HistoryQuery::default());
+        assert_eq!(times(&all), [0, 200, 400, 600, 800, 1000]);
+        assert_eq!(all["interval_ms"], 200);
+    }
+
+    #[test]
+    fn drops_frames_past_the_retention() {
+        let history = history(1, 100);
+        feed(&history, Instant::now(), 3000);
+        assert_eq!(
+            times(&query(&history, HistoryQuery::default())),
+            [2000, 2200, 2400, 2600, 2800, 3000]
+        );
+    }
+
+    #[test]
+    fn ranges_are_inclusive_and_thinned_by_step() {
+        let history = history(600, 100);
+        feed(&history, Instant::now(), 2000);
+        let range =
+            HistoryQuery { from: Some(T0 + 400), to: Some(T0 + 1200), ..HistoryQuery::default() };
+        assert_eq!(times(&query(&history, range.clone())), [400, 600, 800, 1000, 1200]);
+
+        let stepped = HistoryQuery { step_ms: Some(500), ..range };
+        assert_eq!(times(&query(&history, stepped)), [400, 1000]);
+    }
+
+    #[test]
+    fn projects_the_requested_fields() {
+        let history = history(600, 100);
+        feed(&history, Instant::now(), 200);
+        let fields = HistoryQuery {
+            fields: Some("bpm, mood.valence,params.missing".into()),
+            ..HistoryQuery::default()
+        };
+        let frame = &query(&history, fields)["frames"][1];
+        assert_eq!(frame["bpm"], 200.0);
+        assert_eq!(frame["mood.valence"], 0.0);
+        assert_eq!(frame["params.missing"], Value::Null);
+        assert!(frame.get("state").is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_conflicting_ranges() {
+        let history = history(600, 100);
+        let unknown = HistoryQuery { fields: Some("bpm,vibe".into()), ..HistoryQuery::default() };
+        assert!(history.query(&unknown).unwrap_err().to_string().contains("vibe"));
+        let both = HistoryQuery { from: Some(0), last_secs: Some(10), ..HistoryQuery::default() };
+        assert!(history.query(&both).is_err());
+    }
+
+    #[test]
+    fn events_between_kept_frames_are_kept_up_to_the_cap() {
+        let history = history(600, 3);
+        let start = Instant::now();
+        for ms in 0..5u64 {
+            let event = PipelineEvent {
+                source: "script".into(),
+                name: format!("e{}", ms),
+                data: Value::Null,
+                at_ms: ms,
+            };
+            let state = GlobalState { events: vec![event], ..GlobalState::default() };
+            history.push_at(state, start + Duration::from_millis(ms), T0 + ms);
+        }
+        let result = query(&history, HistoryQuery::default());
+        assert_eq!(times(&result), [0]);
+        let names: Vec<&str> = result["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["e2", "e3", "e4"]);
+    }
+
+    #[test]
+    fn new_transitions_become_events() {
+        let history = history(600, 100);
+        let start = Instant::now();
+        let transition = |seq| VibeTransition {
+            seq,
+            from: VibeState::Chill,
+            to: VibeState::Chaos,
+            reason: "flux".into(),
+            at_ms: seq * 100,
+            unix_ms: T0,
+        };
+        // The first one seen was already published before the history started
+        for (ms, seq) in [(0, 1), (50, 1), (100, 2), (150, 2)] {
+            let state =
+                GlobalState { last_transition: Some(transition(seq)), ..GlobalState::default() };
+            history.push_at(state, start + Duration::from_millis(ms), T0 + ms);
+        }
+        let events = &query(&history, HistoryQuery::default())["events"];
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        assert_eq!(events[0]["name"], "vibe_transition");
+        assert_eq!(events[0]["at_ms"], 200);
+    }
+}
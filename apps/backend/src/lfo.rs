@@ -0,0 +1,271 @@
+use crate::state_machine::{GlobalState, VibePhase};
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tracing::{error, info};
+
+// Shipped defaults: quarter-note sine, four-bar ramp, random step per bar
+const BUILTIN_LFOS: &str = include_str!("../config/lfos.json");
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoShape {
+    /// Peaks at the start of each period.
+    Sine,
+    /// Peaks at the start of each period.
+    Triangle,
+    Ramp,
+    RampDown,
+    /// High for the first `duty` of each period.
+    Square,
+    /// Holds a new random value for each period.
+    RandomStep,
+    /// Attack/decay fired by `on_event` or `on_phase`; `beats` is the decay.
+    Envelope,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LfoDef {
+    pub name: String,
+    pub shape: LfoShape,
+    /// Period in beats (decay length for envelopes).
+    #[serde(default = "one")]
+    pub beats: f32,
+    /// Shifts the waveform later by this many beats.
+    #[serde(default)]
+    pub offset_beats: f32,
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "one")]
+    pub max: f32,
+    #[serde(default)]
+    pub duty: Option<f32>,
+    #[serde(default)]
+    pub attack_beats: f32,
+    /// Envelope trigger: a pipeline event name, e.g. one a script `emit()`s.
+    #[serde(default)]
+    pub on_event: Option<String>,
+    /// Envelope trigger: entering this song phase.
+    #[serde(default)]
+    pub on_phase: Option<VibePhase>,
+    /// Bound to brightness or flashes; the safety limiter slews and flash-counts it.
+    #[serde(default)]
+    pub safety: bool,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl LfoDef {
+    /// Waveform value in 0..1 at `beats` on the beat clock. `fired` is the beat
+    /// position of the last envelope trigger.
+    fn unit(&self, beats: f64, fired: Option<f64>) -> f32 {
+        let pos = (beats - self.offset_beats as f64) / self.beats as f64;
+        let u = pos.rem_euclid(1.0) as f32;
+        match self.shape {
+            LfoShape::Sine => 0.5 + 0.5 * (u * std::f32::consts::TAU).cos(),
+            LfoShape::Triangle => (2.0 * u - 1.0).abs(),
+            LfoShape::Ramp => u,
+            LfoShape::RampDown => 1.0 - u,
+            LfoShape::Square => (u < self.duty.unwrap_or(0.5)) as u8 as f32,
+            LfoShape::RandomStep => random_unit(&self.name, pos.floor() as i64),
+            LfoShape::Envelope => {
+                let Some(t) = fired.map(|at| (beats - at) as f32) else { return 0.0 };
+                if t < self.attack_beats {
+                    t / self.attack_beats
+                } else {
+                    (1.0 - (t - self.attack_beats) / self.beats).max(0.0)
+                }
+            }
+        }
+    }
+}
+
+/// Deterministic per name and period, so every screen and every replay of the
+/// same beat count sees the same sequence.
+fn random_unit(name: &str, period: i64) -> f32 {
+    // FNV-1a over the name, then a splitmix64 finalizer with the period mixed in
+    let seed = name
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    let mut z = seed ^ (period as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LfoSet {
+    pub lfos: Vec<LfoDef>,
+}
+
+impl LfoSet {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let set: LfoSet = serde_json::from_str(text)?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut names = HashSet::new();
+        let mut errors = Vec::new();
+        for lfo in &self.lfos {
+            let name = &lfo.name;
+            if name.trim().is_empty() || name.contains('.') {
+                errors.push(format!("invalid modulator name '{}'", name));
+            }
+            if !names.insert(name.as_str()) {
+                errors.push(format!("{}: declared twice", name));
+            }
+            if !(lfo.beats.is_finite() && lfo.beats > 0.0) {
+                errors.push(format!("{}: beats must be positive", name));
+            }
+            if !lfo.offset_beats.is_finite() || !lfo.min.is_finite() || !lfo.max.is_finite() {
+                errors.push(format!("{}: offset_beats, min and max must be finite", name));
+            }
+            if lfo.duty.is_some_and(|d| !(0.0..=1.0).contains(&d)) {
+                errors.push(format!("{}: duty outside 0..1", name));
+            }
+            if lfo.duty.is_some() && lfo.shape != LfoShape::Square {
+                errors.push(format!("{}: duty only applies to square", name));
+            }
+            let triggered = lfo.on_event.is_some() || lfo.on_phase.is_some();
+            if lfo.shape == LfoShape::Envelope {
+                if !triggered {
+                    errors.push(format!("{}: envelope needs on_event or on_phase", name));
+                }
+                if !(lfo.attack_beats.is_finite() && lfo.attack_beats >= 0.0) {
+                    errors.push(format!("{}: attack_beats must not be negative", name));
+                }
+            } else if triggered || lfo.attack_beats != 0.0 {
+                errors.push(format!("{}: triggers and attack only apply to envelopes", name));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!(errors.join("; "))
+        }
+    }
+}
+
+/// Shared modulator definitions: the zone's file (`LFOS_PATH` by default) if
+/// set, otherwise the built-in `config/lfos.json`. Replacing the set through
+/// the API writes it back to that file; without one the change lasts until restart.
+pub struct LfoBank {
+    path: Option<PathBuf>,
+    current: RwLock<Arc<LfoSet>>,
+}
+
+impl LfoBank {
+    pub fn load(path: Option<PathBuf>) -> Arc<Self> {
+        let builtin = LfoSet::parse(BUILTIN_LFOS).expect("Invalid built-in modulators");
+        let bank = Arc::new(Self {
+            path,
+            current: RwLock::new(Arc::new(builtin)),
+        });
+
+        if let Some(path) = &bank.path {
+            match bank.reload() {
+                Ok(count) => println!("🌊 [LFO] Loaded {} modulator(s) from {:?}", count, path),
+                Err(e) => {
+                    println!("⚠️ [LFO] {:?} rejected, using built-in modulators: {}", path, e)
+                }
+            }
+        }
+        bank
+    }
+
+    pub fn snapshot(&self) -> Arc<LfoSet> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn path(&self) -> Option<String> {
+        self.path.as_ref().map(|p| p.display().to_string())
+    }
+
+    /// Re-reads `LFOS_PATH`. On any error the running set stays in place.
+    pub fn reload(&self) -> anyhow::Result<usize> {
+        let path = self.path.as_ref().ok_or_else(|| anyhow!("LFOS_PATH is not set"))?;
+        let set = LfoSet::parse(&std::fs::read_to_string(path)?)?;
+        let count = set.lfos.len();
+        *self.current.write().unwrap() = Arc::new(set);
+        info!(event = "lfos_loaded", path = ?path, count);
+        Ok(count)
+    }
+
+    /// Validates and installs `set`, saving it to `LFOS_PATH` first if set.
+    pub fn replace(&self, set: LfoSet) -> anyhow::Result<()> {
+        set.validate()?;
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&set)?;
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, text).and_then(|_| std::fs::rename(&tmp, path)).map_err(|e| {
+                error!(event = "lfos_save_failed", path = ?path, error = %e);
+                anyhow!("could not save {:?}: {}", path, e)
+            })?;
+        }
+        *self.current.write().unwrap() = Arc::new(set);
+        Ok(())
+    }
+}
+
+/// Evaluates the bank on the Overmind's beat clock each frame and publishes
+/// the values as `lfos`. Everything is derived from the beat count and phase,
+/// so all clients receive the same values.
+pub struct LfoRunner {
+    bank: Arc<LfoBank>,
+    fired: HashMap<String, f64>, // Beat position of each envelope's last trigger
+    last_phase: Option<VibePhase>,
+}
+
+impl LfoRunner {
+    pub fn new(bank: Arc<LfoBank>) -> Self {
+        Self { bank, fired: HashMap::new(), last_phase: None }
+    }
+
+    /// Runs after the beat clock and song phase, before rules and scripts.
+    pub fn apply(&mut self, state: &mut GlobalState) {
+        let set = self.bank.snapshot();
+        let beats = beat_position(state);
+        let entered = (self.last_phase != Some(state.phase)).then_some(state.phase);
+        self.last_phase = Some(state.phase);
+
+        state.lfos.clear();
+        for lfo in &set.lfos {
+            if entered.is_some() && lfo.on_phase == entered {
+                self.fired.insert(lfo.name.clone(), beats);
+            }
+            let fired = self.fired.get(&lfo.name).copied();
+            let value = lfo.min + (lfo.max - lfo.min) * lfo.unit(beats, fired);
+            state.lfos.insert(lfo.name.clone(), value);
+        }
+    }
+
+    /// Fires envelopes on this frame's events; they start rising next frame.
+    pub fn observe(&mut self, state: &GlobalState) {
+        if state.events.is_empty() {
+            return;
+        }
+        let set = self.bank.snapshot();
+        let beats = beat_position(state);
+        for lfo in &set.lfos {
+            let on = lfo.on_event.as_deref();
+            if state.events.iter().any(|e| on == Some(e.name.as_str())) {
+                self.fired.insert(lfo.name.clone(), beats);
+            }
+        }
+    }
+}
+
+fn beat_position(state: &GlobalState) -> f64 {
+    state.beat.count as f64 + state.beat.phase as f64
+}
@@ -11,6 +11,10 @@ use tokio_retry::Retry;
 use tracing::{error, info, instrument};
 
 use crate::ai_metrics::MetricsCollector;
+use crate::mood::Mood;
+use crate::params::{ParamSchema, ParamValue};
+use crate::state_machine::GlobalState;
+use std::collections::BTreeMap;
 use std::time::Instant;
 
 lazy_static! {
@@ -20,7 +24,11 @@ lazy_static! {
             "theme": { "type": "string" },
             "primary_color": { "type": "string", "pattern": "^#[0-9A-Fa-f]{6}$" },
             "secondary_color": { "type": "string", "pattern": "^#[0-9A-Fa-f]{6}$" },
-            "directive": { "type": "string" }
+            "directive": { "type": "string" },
+            "params": {
+                "type": "object",
+                "additionalProperties": { "type": ["number", "string", "boolean"] }
+            }
         },
         "required": ["theme", "primary_color", "secondary_color", "directive"]
     });
@@ -34,12 +42,51 @@ pub struct AiContext {
     pub primary_color: String,
     pub secondary_color: String,
     pub directive: String,
+    /// Custom parameter values, validated against the parameter schema.
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamValue>,
+    /// Genre the oracle chose these colours for; None for fallbacks and the boot context.
+    #[serde(skip)]
+    pub genre: Option<String>,
+}
+
+impl AiContext {
+    /// The look `state` publishes, as a director's starting context (no genre).
+    pub fn of(state: &GlobalState) -> Self {
+        Self {
+            theme: state.ai_theme.clone(),
+            primary_color: state.ai_primary_color.clone(),
+            secondary_color: state.ai_secondary_color.clone(),
+            directive: state.ai_directive.clone(),
+            params: BTreeMap::new(),
+            genre: None,
+        }
+    }
 }
 
 
+/// Per-zone director settings. Unset fields fall back to the environment
+/// (`OLLAMA_MODEL`) or the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirectorConfig {
+    pub enabled: bool,
+    pub model: Option<String>,
+    /// Free-text guidance added to every prompt, e.g. "chill room: slow, pastel".
+    pub style: Option<String>,
+    /// Pause between consultations.
+    pub interval_secs: u64,
+}
+
+impl Default for DirectorConfig {
+    fn default() -> Self {
+        Self { enabled: true, model: None, style: None, interval_secs: 5 }
+    }
+}
 
 pub struct LlmDirector {
     client: reqwest::Client,
+    pub config: DirectorConfig,
     pub context: Arc<Mutex<AiContext>>,
     cache: Cache<String, AiContext>,
     pub metrics: Arc<MetricsCollector>,
@@ -54,31 +101,54 @@ impl LlmDirector {
 
         Self {
             client: reqwest::Client::new(),
+            config: DirectorConfig::default(),
             context: Arc::new(Mutex::new(initial_context)),
             cache,
             metrics: Arc::new(MetricsCollector::new()),
         }
     }
 
+    pub fn with_config(mut self, config: DirectorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
 
-    #[instrument(skip(self), fields(score = %score))]
-    pub async fn update_boredom(&self, score: f32) {
+    /// Forces CHAOS_INJECTION for a bored crowd and returns the context it replaced.
+    #[instrument(skip(self), fields(boredom = %boredom))]
+    pub async fn inject_chaos(&self, boredom: f32) -> AiContext {
         let mut ctx = self.context.lock().await;
-        // Simple logic: If boredom > 80% and not already in chaos, force a directive
-        if score > 0.8 && ctx.directive != "CHAOS_INJECTION" {
-             println!("🤖 [AI OVERMIND] High Boredom Detected ({:.1}%). Triggering CHAOS.", score * 100.0);
-             ctx.directive = "CHAOS_INJECTION".to_string();
-             ctx.primary_color = "#FF0000".to_string(); // Red Alert
-             ctx.theme = "SYSTEM_FAILURE".to_string();
+        println!("🤖 [AI OVERMIND] Crowd Boredom at {:.1}%. Triggering CHAOS.", boredom * 100.0);
+        let previous = ctx.clone();
+        ctx.directive = "CHAOS_INJECTION".to_string();
+        ctx.primary_color = "#FF0000".to_string(); // Red Alert
+        ctx.theme = "SYSTEM_FAILURE".to_string();
+        previous
+    }
+
+    /// Restores the context from before the chaos, unless the oracle has moved on since.
+    pub async fn end_chaos(&self, previous: AiContext) {
+        let mut ctx = self.context.lock().await;
+        if ctx.directive == "CHAOS_INJECTION" {
+            println!("🤖 [AI OVERMIND] Crowd re-engaged. Restoring '{}'.", previous.theme);
+            *ctx = previous;
         }
     }
 
-    #[instrument(skip(self), fields(genre = %genre, chaos = %chaos, trend = %trend))]
-    pub async fn consult_oracle(&self, genre: &str, chaos: f32, trend: &str) {
+    #[instrument(skip(self, genre_notes), fields(genre = %genre, chaos = %chaos, trend = %trend))]
+    pub async fn consult_oracle(
+        &self,
+        genre: &str,
+        genre_notes: &str,
+        chaos: f32,
+        trend: &str,
+        mood: &Mood,
+    ) {
         let start_time = Instant::now();
         self.metrics.record_request();
 
-        let cache_key = format!("{}_{:.1}_{}", genre, (chaos * 10.0).round() / 10.0, trend);
+        let chaos_key = (chaos * 10.0).round() / 10.0;
+        let cache_key = format!("{}_{:.1}_{}_{}", genre, chaos_key, trend, mood.label());
 
         if let Some(cached_ctx) = self.cache.get(&cache_key).await {
             info!(event = "cache_hit", key = %cache_key);
@@ -113,30 +183,49 @@ impl LlmDirector {
             ctx.theme.clone()
         };
 
+        let param_notes = match ParamSchema::global().describe() {
+            notes if notes.is_empty() => "none".to_string(),
+            notes => notes,
+        };
+
         let prompt = format!(
             "IDENTITY: You are the VIBE_OVERMIND, a Cyber-Oracle controlling a futuristic visualizer.
             CONTEXT:
-            - Genre: '{}'
+            - Genre: '{}' ({})
             - Chaos Level: {:.2}
             - Energy Trend: '{}' (IMPORTANT: React to this!)
+            - Mood: {} (valence: negative..positive, arousal: calm..energetic, -1..1)
             - Previous Theme: '{}' (Do not repeat this if possible)
+            - Room Style: {}
+            - Visual Parameters: {}
 
             DIRECTIVE:
             1. Generate a visually distinct Theme for this moment.
-            2. Choose colors that match the Genre + Trend (e.g., Rising = Brightening, Falling = Darkening).
+            2. Choose colors that match the Genre + Trend + Mood (e.g., Rising = Brightening, Falling = Darkening, low valence = colder hues, high arousal = more saturation).
             3. The 'directive' field must be a short, cool, sci-fi command (e.g., 'INITIATE_DROP_SEQUENCE', 'PURGE_SYSTEMS').
+            4. Optionally set Visual Parameters by name in 'params' (numbers, \"#HEX\" colours, true/false).
 
             Output JSON only:
             {{
                 \"theme\": \"UPPERCASE_THEME_NAME\",
                 \"primary_color\": \"#HEX\",
                 \"secondary_color\": \"#HEX\",
-                \"directive\": \"TECHNICAL_COMMAND\"
+                \"directive\": \"TECHNICAL_COMMAND\",
+                \"params\": {{}}
             }}",
-            genre, chaos, trend, prev_theme
+            genre,
+            genre_notes,
+            chaos,
+            trend,
+            mood.describe(),
+            prev_theme,
+            self.config.style.as_deref().unwrap_or("none"),
+            param_notes
         );
 
-        let model = env::var("OLLAMA_MODEL").expect("OLLAMA_MODEL environment variable must be set");
+        let model = self.config.model.clone().unwrap_or_else(|| {
+            env::var("OLLAMA_MODEL").expect("OLLAMA_MODEL environment variable must be set")
+        });
         let body = serde_json::json!({
             "model": model,
             "prompt": prompt,
@@ -179,9 +268,24 @@ impl LlmDirector {
                                     return;
                                 }
 
-                                if let Ok(new_context) =
+                                if let Ok(mut new_context) =
                                     serde_json::from_value::<AiContext>(validated_json)
                                 {
+                                    // Drop parameter writes the schema rejects, keep the rest
+                                    new_context.params = std::mem::take(&mut new_context.params)
+                                        .into_iter()
+                                        .filter_map(|(name, value)| {
+                                            match ParamSchema::global().check(&name, value) {
+                                                Ok(value) => Some((name, value)),
+                                                Err(e) => {
+                                                    error!(event = "ai_param_rejected", error = %e);
+                                                    None
+                                                }
+                                            }
+                                        })
+                                        .collect();
+
+                                    new_context.genre = Some(genre.to_string());
                                     info!(event = "oracle_success", theme = %new_context.theme);
 
                                     // Record success in circuit breaker
@@ -1,16 +1,41 @@
 mod audio_engine;
+mod auth;
+mod beat;
+mod clock;
+mod color;
+mod crowd;
+mod envelope;
+mod genre;
+mod history;
+mod lfo;
+mod mood;
+mod overrides;
+mod params;
+mod publish;
+mod recorder;
+mod replay;
+mod rules;
+mod safety;
+mod scenes;
+mod scripting;
+mod show;
+mod spectrum;
 mod state_machine;
+mod telemetry;
+mod ticker;
+mod timeline;
+mod transition;
+mod trend;
+mod vibe;
 pub mod websocket;
+mod zones;
 
-use crate::audio_engine::AudioEngine;
-use crate::state_machine::{Overmind, AudioMetadata};
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use crate::telemetry::Telemetry;
+use crate::zones::ZoneSet;
 
 mod ai_metrics;
 mod llm_engine; // Add module
 
-use crate::llm_engine::LlmDirector;
 use tokio::time::{sleep, Duration};
 use tracing::info;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
@@ -79,110 +104,43 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("WARN: Failed to set subscriber: {}", e);
     }
 
+    // Offline tools run instead of the server: `backend eval-genre <clips_dir>`
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("eval-genre") {
+        let dir = args.get(2).map(String::as_str).unwrap_or("clips");
+        if let Err(e) = genre::evaluate(std::path::Path::new(dir)) {
+            println!("🛑 [Genre] Evaluation failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     info!("🚀 [SYSTEM] VIBE Backend Initialization Started.");
 
     // 0. Hardened Ollama Boot (ASYNC BACKGROUND)
     ensure_ollama_ready().await;
 
-    // 1. Audio Engine
-    let (tx_audio, mut rx_audio) = broadcast::channel(16);
-    
-    // Attempt to initialize Audio Engine, NO FALLBACK
-    let (_audio_engine_guard, audio_meta) = match AudioEngine::new(tx_audio.clone()) {
-        Ok((engine, meta)) => {
-            info!("✅ Audio Engine Initialized");
-            (Some(engine), meta)
-        },
+    // System telemetry on its own thread, shared by every zone
+    let telemetry = Telemetry::spawn();
+
+    // One pipeline per zone (ZONES_PATH, or a single "main" zone from the environment)
+    let zone_set = match ZoneSet::from_env() {
+        Ok(set) => set,
         Err(e) => {
-            println!("⚠️ [Audio] Audio Engine Initialization Failed: {}", e);
-            println!("🛑 [Audio] Running in NO_AUDIO mode (0 data).");
-            
-            let dummy_meta = AudioMetadata {
-                device_name: "NO_AUDIO_DEVICE".to_string(),
-                sample_rate: 0,
-                channels: 0,
-            };
-            (None, dummy_meta)
+            println!("🛑 [Zones] Invalid zone configuration: {:#}", e);
+            std::process::exit(1);
         }
     };
+    let mut zones = Vec::new();
+    let mut _audio_engine_guards = Vec::new();
+    for config in zone_set.zones {
+        let (zone, engine) = zones::start(config, telemetry.clone());
+        zones.push(zone);
+        _audio_engine_guards.extend(engine);
+    }
 
-
-    // 2. State Machine (The Overmind)
-    let (tx_state, _): (broadcast::Sender<state_machine::GlobalState>, _) = broadcast::channel(16);
-
-    // Initialize AI Director
-    let llm_director = Arc::new(LlmDirector::new());
-
-    // 2. Start AI Director Loop (Async)
-    let director_clone = llm_director.clone();
-    let mut rx_state_for_director = tx_state.subscribe();
-
-    tokio::spawn(async move {
-        println!("⚡ [LLM] Director Loop Started.");
-        loop {
-            // Wait for a state update to analyze (throttled)
-            if let Ok(state) = rx_state_for_director.recv().await {
-                let genre_str = format!("{:?}", state.genre);
-                let chaos =
-                    if state.state == crate::state_machine::VibeState::Chaos { 1.0 } else { 0.0 };
-                let trend = state.energy_trend.clone();
-
-                // Consult Oracle with REAL data
-                director_clone.consult_oracle(&genre_str, chaos, &trend).await;
-            }
-            sleep(Duration::from_secs(5)).await;
-        }
-    });
-
-    // 3. Start Overmind Loop
-    let tx_state_clone = tx_state.clone();
-    let director_ref = llm_director.clone();
-    let audio_running = _audio_engine_guard.is_some();
-
-    tokio::spawn(async move {
-        let mut overmind = Overmind::new(audio_meta);
-        
-        // If we have no audio engine (guard is None), we just loop sending 0 data
-        if !audio_running {
-             loop {
-                // Send heartbeat of 0 energy
-                let mut new_state = overmind.update(0.0, 0.0, 0.0, 0.0);
-                
-                // Inject AI Context even in silence
-                let ai_ctx = director_ref.context.lock().await;
-                new_state.ai_theme = ai_ctx.theme.clone();
-                new_state.ai_primary_color = ai_ctx.primary_color.clone();
-                new_state.ai_secondary_color = ai_ctx.secondary_color.clone();
-                new_state.ai_directive = ai_ctx.directive.clone();
-                drop(ai_ctx);
-
-                let _ = tx_state_clone.send(new_state);
-                sleep(Duration::from_millis(33)).await; // ~30 FPS heartbeat
-             }
-        } else {
-            while let Ok(features) = rx_audio.recv().await {
-                let mut new_state =
-                    overmind.update(features.low_energy, features.mid_energy, features.high_energy, features.spectral_flux);
-
-                // Inject AI Context
-                let ai_ctx = director_ref.context.lock().await;
-                new_state.ai_theme = ai_ctx.theme.clone();
-                new_state.ai_primary_color = ai_ctx.primary_color.clone();
-                new_state.ai_secondary_color = ai_ctx.secondary_color.clone();
-                new_state.ai_directive = ai_ctx.directive.clone();
-                drop(ai_ctx); // Explicit drop for clarity (though not strictly needed)
-
-                // Broadcast new state to all connected clients
-                let _ = tx_state_clone.send(new_state);
-                
-                // Yield to scheduler to prevent tight-loop starvation in single-core envs
-                tokio::task::yield_now().await; 
-            }
-        }
-    });
-
-    // 4. Start WebSocket Server IMMEDIATELY
-    websocket::start_server(tx_state, llm_director).await;
+    // Start WebSocket Server IMMEDIATELY
+    websocket::start_server(zones).await;
 
     Ok(())
 }
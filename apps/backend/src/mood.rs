@@ -0,0 +1,260 @@
+use crate::audio_engine::AudioFeatures;
+use crate::genre::WindowStats;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_SMOOTHING_SECS: f32 = 6.0;
+// Pitch-class profile memory; long enough to span a few chord changes
+const CHROMA_WINDOW_SECS: f32 = 10.0;
+// Below this correlation with the best key profile no key is reported
+const KEY_MIN_CORRELATION: f32 = 0.5;
+
+const PITCH_CLASSES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+// Krumhansl-Kessler key profiles, tonic first
+const MAJOR_PROFILE: [f32; 12] =
+    [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.37, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] =
+    [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Position on the valence/arousal plane, both -1..1, smoothed over several seconds.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct Mood {
+    pub valence: f32, // Negative .. positive
+    pub arousal: f32, // Calm .. energetic
+    pub mode: f32,    // Minor (-1) .. major (1), 0 when unclear
+    #[serde(default)]
+    pub key: Option<String>, // e.g. "A minor", when the pitch profile is clear enough
+}
+
+impl Mood {
+    /// Quadrant of the circumplex, a cue word for the AI director.
+    pub fn label(&self) -> &'static str {
+        match (self.valence >= 0.0, self.arousal >= 0.0) {
+            (true, true) => "euphoric",
+            (false, true) => "tense",
+            (false, false) => "melancholic",
+            (true, false) => "serene",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let key = self.key.as_deref().unwrap_or("key unclear");
+        format!(
+            "valence {:+.2}, arousal {:+.2} ({}), {}",
+            self.valence,
+            self.arousal,
+            self.label(),
+            key
+        )
+    }
+}
+
+/// Maps the genre window's statistics (tempo, loudness, brightness, onset
+/// density) plus a decaying pitch-class profile (mode) to valence and
+/// arousal. Fast, loud, bright and dense music raises arousal; major mode,
+/// faster tempo and brighter timbre raise valence. `MOOD_SMOOTHING_SECS`
+/// (default 6) is the time constant of the published values.
+pub struct MoodEstimator {
+    smoothing: f32,
+    chroma: [f32; 12],
+    target: Option<Mood>,
+    mood: Mood,
+}
+
+impl MoodEstimator {
+    pub fn from_env() -> Self {
+        let smoothing = std::env::var("MOOD_SMOOTHING_SECS")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|s| s.is_finite() && *s >= 0.0)
+            .unwrap_or(DEFAULT_SMOOTHING_SECS);
+        Self { smoothing, chroma: [0.0; 12], target: None, mood: Mood::default() }
+    }
+
+    /// Accumulates the frame's pitch classes, weighted by its energy.
+    pub fn push(&mut self, dt: f32, features: &AudioFeatures) {
+        let decay = (-dt / CHROMA_WINDOW_SECS).exp();
+        let energy = features.low_energy + features.mid_energy + features.high_energy;
+        for (acc, c) in self.chroma.iter_mut().zip(features.chroma) {
+            *acc = *acc * decay + c * energy;
+        }
+    }
+
+    /// New target from a fresh window; called whenever the genre is re-evaluated.
+    pub fn estimate(&mut self, stats: &WindowStats, bpm: f32) {
+        let unit = |v: f32| v.clamp(0.0, 1.0);
+        let tempo = unit((bpm - 60.0) / 120.0);
+        let loudness = unit((stats.low_mean + stats.mid_mean + stats.high_mean) / 3.0 * 2.0);
+        let brightness = unit(stats.centroid_mean / 0.3);
+        let density = unit(stats.onset_rate / 8.0);
+        let (mode, key) = self.mode();
+
+        // Both weight sets sum to 1 so a fully saturated input reaches the edge of the plane
+        let arousal = 0.35 * tempo + 0.25 * loudness + 0.2 * brightness + 0.2 * density;
+        let valence = 0.5 * (mode + 1.0) / 2.0 + 0.2 * tempo + 0.2 * brightness + 0.1 * loudness;
+        self.target = Some(Mood {
+            valence: (valence * 2.0 - 1.0).clamp(-1.0, 1.0),
+            arousal: (arousal * 2.0 - 1.0).clamp(-1.0, 1.0),
+            mode,
+            key,
+        });
+    }
+
+    /// Steps the published mood towards the target.
+    pub fn update(&mut self, dt: f32) -> Mood {
+        if let Some(target) = &self.target {
+            let t = if self.smoothing > 0.0 { 1.0 - (-dt / self.smoothing).exp() } else { 1.0 };
+            self.mood.valence += (target.valence - self.mood.valence) * t;
+            self.mood.arousal += (target.arousal - self.mood.arousal) * t;
+            self.mood.mode += (target.mode - self.mood.mode) * t;
+            self.mood.key = target.key.clone();
+        }
+        self.mood.clone()
+    }
+
+    /// Correlates the pitch-class profile with every major and minor key.
+    /// Returns the major/minor balance and the best-matching key.
+    fn mode(&self) -> (f32, Option<String>) {
+        if self.chroma.iter().sum::<f32>() <= f32::EPSILON {
+            return (0.0, None);
+        }
+        let best = |profile: &[f32; 12]| {
+            (0..12)
+                .map(|tonic| {
+                    let rotated: Vec<f32> =
+                        (0..12).map(|i| self.chroma[(i + tonic) % 12]).collect();
+                    (tonic, correlation(&rotated, profile))
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0))
+        };
+        let (major_tonic, major) = best(&MAJOR_PROFILE);
+        let (minor_tonic, minor) = best(&MINOR_PROFILE);
+
+        // Relative keys share notes, so the difference is small; scale it up
+        let mode = ((major - minor) * 4.0).clamp(-1.0, 1.0);
+        let key = if major.max(minor) < KEY_MIN_CORRELATION {
+            None
+        } else if major >= minor {
+            Some(format!("{} major", PITCH_CLASSES[major_tonic]))
+        } else {
+            Some(format!("{} minor", PITCH_CLASSES[minor_tonic]))
+        };
+        (mode, key)
+    }
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / n, b.iter().sum::<f32>() / n);
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= 0.0 || var_b <= 0.0 {
+        return 0.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimator(smoothing: f32) -> MoodEstimator {
+        MoodEstimator { smoothing, chroma: [0.0; 12], target: None, mood: Mood::default() }
+    }
+
+    /// Feeds one frame whose pitch classes follow `profile` rotated to `tonic`.
+    fn play_key(mood: &mut MoodEstimator, profile: &[f32; 12], tonic: usize) {
+        let mut chroma = [0.0; 12];
+        for (i, weight) in profile.iter().enumerate() {
+            chroma[(i + tonic) % 12] = *weight;
+        }
+        let features = AudioFeatures { mid_energy: 1.0, chroma, ..Default::default() };
+        mood.push(0.05, &features);
+    }
+
+    fn saturated() -> WindowStats {
+        WindowStats {
+            low_mean: 1.0,
+            mid_mean: 1.0,
+            high_mean: 1.0,
+            centroid_mean: 0.5,
+            onset_rate: 10.0,
+            ..WindowStats::default()
+        }
+    }
+
+    fn target(mood: &MoodEstimator) -> Mood {
+        mood.target.clone().unwrap()
+    }
+
+    #[test]
+    fn silence_is_calm_and_neutral_in_mode() {
+        let mut mood = estimator(0.0);
+        mood.estimate(&WindowStats::default(), 60.0);
+        let silent = target(&mood);
+        assert_eq!(silent.arousal, -1.0);
+        assert!((silent.valence + 0.5).abs() < 1e-6);
+        assert_eq!((silent.mode, silent.key), (0.0, None));
+    }
+
+    #[test]
+    fn fast_loud_bright_major_music_reaches_the_corner() {
+        let mut mood = estimator(0.0);
+        play_key(&mut mood, &MAJOR_PROFILE, 0);
+        mood.estimate(&saturated(), 180.0);
+        let euphoric = target(&mood);
+        assert_eq!(euphoric.arousal, 1.0);
+        assert_eq!(euphoric.valence, 1.0);
+        assert_eq!(euphoric.key.as_deref(), Some("C major"));
+        assert_eq!(mood.update(0.1).label(), "euphoric");
+    }
+
+    #[test]
+    fn minor_mode_lowers_valence_not_arousal() {
+        let mut major = estimator(0.0);
+        play_key(&mut major, &MAJOR_PROFILE, 9);
+        major.estimate(&saturated(), 120.0);
+        let mut minor = estimator(0.0);
+        play_key(&mut minor, &MINOR_PROFILE, 9);
+        minor.estimate(&saturated(), 120.0);
+
+        let (major, minor) = (target(&major), target(&minor));
+        assert_eq!(minor.key.as_deref(), Some("A minor"));
+        assert!(minor.mode < 0.0 && major.mode > 0.0);
+        assert!(minor.valence < major.valence);
+        assert_eq!(minor.arousal, major.arousal);
+    }
+
+    #[test]
+    fn tempo_raises_arousal_and_valence() {
+        let mut mood = estimator(0.0);
+        mood.estimate(&WindowStats::default(), 90.0);
+        let slow = target(&mood);
+        mood.estimate(&WindowStats::default(), 150.0);
+        let fast = target(&mood);
+        assert!(fast.arousal > slow.arousal && fast.valence > slow.valence);
+    }
+
+    #[test]
+    fn published_mood_follows_the_target_with_its_time_constant() {
+        let mut mood = estimator(6.0);
+        assert_eq!(mood.update(1.0), Mood::default());
+
+        mood.estimate(&saturated(), 180.0);
+        let stepped = mood.update(6.0);
+        assert!((stepped.arousal - (1.0 - (-1.0f32).exp())).abs() < 1e-5);
+    }
+
+    #[test]
+    fn quadrants_name_the_mood() {
+        let mood = |valence, arousal| Mood { valence, arousal, ..Mood::default() };
+        assert_eq!(mood(-0.5, 0.5).label(), "tense");
+        assert_eq!(mood(-0.5, -0.5).label(), "melancholic");
+        assert_eq!(mood(0.5, -0.5).label(), "serene");
+        assert!(mood(0.5, -0.5).describe().contains("key unclear"));
+    }
+}
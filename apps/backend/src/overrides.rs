@@ -0,0 +1,352 @@
+use crate::color::Palette;
+use crate::params::{ParamSchema, ParamValue};
+use crate::state_machine::GlobalState;
+use anyhow::{anyhow, bail, ensure};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Fields an operator can pin. `params.<name>` addresses a custom parameter.
+pub const FIELDS: &[&str] = &[
+    "state",
+    "phase",
+    "genre",
+    "bpm",
+    "glitch_factor",
+    "theme",
+    "directive",
+    "palette",
+    "blackout",
+    "params.<name>",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OverrideRequest {
+    pub field: String,
+    /// Omitted: lock the field at whatever value is live when the override takes effect.
+    #[serde(default)]
+    pub value: Option<Value>,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub beats: Option<u64>,
+}
+
+/// An override as published in `GlobalState.overrides` and the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveOverride {
+    pub field: String,
+    pub value: Value,
+    /// Frozen from the live state rather than set explicitly.
+    pub locked: bool,
+    pub remaining_ms: Option<u64>,
+    pub remaining_beats: Option<u64>,
+    pub set_by: String,
+}
+
+struct Entry {
+    field: String,
+    value: Option<Value>,
+    locked: bool,
+    expires_at: Option<Instant>,
+    beats: Option<u64>,
+    until_beat: Option<u64>, // Resolved against the beat clock on first apply
+    last_beat: u64,
+    set_by: String,
+}
+
+impl Entry {
+    fn publish(&self) -> ActiveOverride {
+        let now = Instant::now();
+        ActiveOverride {
+            field: self.field.clone(),
+            value: self.value.clone().unwrap_or(Value::Null),
+            locked: self.locked,
+            remaining_ms: self
+                .expires_at
+                .map(|t| t.saturating_duration_since(now).as_millis() as u64),
+            remaining_beats: match self.until_beat {
+                Some(end) => Some(end.saturating_sub(self.last_beat)),
+                None => self.beats,
+            },
+            set_by: self.set_by.clone(),
+        }
+    }
+}
+
+/// Operator layer applied on top of the Overmind's output, after the AI
+/// context is merged, so pinned values win over audio, rules, scripts and AI.
+/// Timed overrides expire on wall-clock time or on the beat clock.
+#[derive(Default)]
+pub struct OverrideStore {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl OverrideStore {
+    /// Adds or replaces the override for `req.field`.
+    pub fn set(&self, req: OverrideRequest, set_by: &str) -> anyhow::Result<ActiveOverride> {
+        validate(&req)?;
+        let entry = Entry {
+            field: req.field.clone(),
+            locked: req.value.is_none(),
+            value: req.value,
+            expires_at: req.duration_ms.map(|ms| Instant::now() + Duration::from_millis(ms)),
+            beats: req.beats,
+            until_beat: None,
+            last_beat: 0,
+            set_by: set_by.to_string(),
+        };
+        let published = entry.publish();
+        info!(event = "override_set", field = %entry.field, by = %set_by);
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| e.field != entry.field);
+        entries.push(entry);
+        Ok(published)
+    }
+
+    pub fn clear(&self, field: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|e| e.field != field);
+        before != entries.len()
+    }
+
+    pub fn clear_all(&self) -> usize {
+        std::mem::take(&mut *self.entries.lock().unwrap()).len()
+    }
+
+    pub fn list(&self) -> Vec<ActiveOverride> {
+        self.entries.lock().unwrap().iter().map(Entry::publish).collect()
+    }
+
+    /// The tempo `bpm` is held at, once known (a lock freezes on its first apply).
+    pub fn pinned_bpm(&self) -> Option<f32> {
+        let entries = self.entries.lock().unwrap();
+        let value = entries.iter().find(|e| e.field == "bpm")?.value.as_ref()?;
+        value.as_f64().map(|bpm| bpm as f32)
+    }
+
+    /// Drops expired overrides, writes the rest into `state` and publishes them.
+    pub fn apply(&self, state: &mut GlobalState) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.is_empty() {
+            state.overrides.clear();
+            return;
+        }
+
+        let now = Instant::now();
+        let beat = state.beat.count;
+        entries.retain_mut(|e| {
+            e.last_beat = beat;
+            if let (Some(n), None) = (e.beats, e.until_beat) {
+                e.until_beat = Some(beat + n);
+            }
+            let expired = e.expires_at.is_some_and(|t| now >= t)
+                || e.until_beat.is_some_and(|end| beat >= end);
+            if expired {
+                info!(event = "override_expired", field = %e.field);
+            }
+            !expired
+        });
+
+        for e in entries.iter_mut() {
+            // A lock freezes the first live value it sees
+            if e.value.is_none() {
+                e.value = read_field(state, &e.field).ok();
+            }
+            if let Some(value) = &e.value {
+                // Values were validated on set; nothing here can fail for a known field
+                let _ = write_field(state, &e.field, value);
+            }
+        }
+        state.overrides = entries.iter().map(Entry::publish).collect();
+    }
+}
+
+/// Checks `req` against a scratch state so a bad value never reaches the broadcast.
+pub fn validate(req: &OverrideRequest) -> anyhow::Result<()> {
+    let mut scratch = GlobalState::default();
+    match &req.value {
+        Some(value) => write_field(&mut scratch, &req.field, value)?,
+        None => {
+            read_field(&scratch, &req.field)?;
+        }
+    }
+    ensure!(req.duration_ms != Some(0) && req.beats != Some(0), "expiry must be positive");
+    Ok(())
+}
+
+fn read_field(state: &GlobalState, field: &str) -> anyhow::Result<Value> {
+    let value = match field {
+        "state" => serde_json::to_value(state.state)?,
+        "phase" => serde_json::to_value(state.phase)?,
+        "genre" => serde_json::to_value(&state.genre)?,
+        "bpm" => Value::from(state.bpm),
+        "glitch_factor" => Value::from(state.glitch_factor),
+        "theme" => Value::from(state.ai_theme.clone()),
+        "directive" => Value::from(state.ai_directive.clone()),
+        "palette" => serde_json::to_value(Palette {
+            primary: state.ai_primary_color.clone(),
+            secondary: state.ai_secondary_color.clone(),
+        })?,
+        "blackout" => Value::from(state.blackout),
+        _ => match field.strip_prefix("params.") {
+            Some(name) if !name.is_empty() => {
+                let declared = ParamSchema::global().get(name);
+                let value = state.params.get(name).cloned().unwrap_or_else(|| {
+                    declared.map_or(ParamValue::Number(0.0), |d| d.default_value())
+                });
+                serde_json::to_value(value)?
+            }
+            _ => bail!("unknown field '{}' (expected one of {:?})", field, FIELDS),
+        },
+    };
+    Ok(value)
+}
+
+fn write_field(state: &mut GlobalState, field: &str, value: &Value) -> anyhow::Result<()> {
+    match field {
+        "state" => state.state = parse(value)?,
+        "phase" => state.phase = parse(value)?,
+        "genre" => state.genre = parse(value)?,
+        "bpm" => {
+            let bpm: f32 = parse(value)?;
+            ensure!(bpm > 0.0, "bpm must be positive");
+            state.bpm = bpm;
+        }
+        "glitch_factor" => {
+            let g: f32 = parse(value)?;
+            ensure!((0.0..=1.0).contains(&g), "glitch_factor must be within 0..1");
+            state.glitch_factor = g;
+        }
+        "theme" => state.ai_theme = parse(value)?,
+        "directive" => state.ai_directive = parse(value)?,
+        "palette" => {
+            let p: Palette = parse(value)?;
+            if let Some(color) = p.invalid_color() {
+                bail!("'{}' is not a #RRGGBB colour", color);
+            }
+            state.ai_primary_color = p.primary;
+            state.ai_secondary_color = p.secondary;
+        }
+        "blackout" => state.blackout = parse(value)?,
+        _ => {
+            let name =
+                field.strip_prefix("params.").filter(|n| !n.is_empty()).ok_or_else(|| {
+                    anyhow!("unknown field '{}' (expected one of {:?})", field, FIELDS)
+                })?;
+            let value = ParamSchema::global().check(name, parse(value)?)?;
+            state.params.insert(name.to_string(), value);
+        }
+    }
+    Ok(())
+}
+
+fn parse<T: DeserializeOwned>(value: &Value) -> serde_json::Result<T> {
+    serde_json::from_value(value.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(field: &str, value: Option<Value>) -> OverrideRequest {
+        OverrideRequest { field: field.to_string(), value, duration_ms: None, beats: None }
+    }
+
+    fn at_beat(count: u64) -> GlobalState {
+        let mut state = GlobalState { bpm: 128.0, ..GlobalState::default() };
+        state.beat.count = count;
+        state
+    }
+
+    #[test]
+    fn pinned_values_win_and_are_published() {
+        let store = OverrideStore::default();
+        store.set(request("glitch_factor", Some(json!(0.5))), "ops").unwrap();
+        store.set(request("theme", Some(json!("STROBE"))), "ops").unwrap();
+
+        let mut state = GlobalState { glitch_factor: 0.9, ..GlobalState::default() };
+        store.apply(&mut state);
+        assert_eq!(state.glitch_factor, 0.5);
+        assert_eq!(state.ai_theme, "STROBE");
+        assert_eq!(state.overrides.len(), 2);
+        assert_eq!(state.overrides[0].set_by, "ops");
+    }
+
+    #[test]
+    fn a_lock_freezes_the_first_live_value() {
+        let store = OverrideStore::default();
+        store.set(request("bpm", None), "ops").unwrap();
+        assert_eq!(store.pinned_bpm(), None);
+
+        store.apply(&mut at_beat(0));
+        let mut state = GlobalState { bpm: 140.0, ..at_beat(1) };
+        store.apply(&mut state);
+        assert_eq!(state.bpm, 128.0);
+        assert!(state.overrides[0].locked);
+        assert_eq!(store.pinned_bpm(), Some(128.0));
+    }
+
+    #[test]
+    fn setting_a_field_again_replaces_it() {
+        let store = OverrideStore::default();
+        store.set(request("bpm", Some(json!(100.0))), "a").unwrap();
+        store.set(request("bpm", Some(json!(150.0))), "b").unwrap();
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.pinned_bpm(), Some(150.0));
+        assert!(store.clear("bpm"));
+        assert!(!store.clear("bpm"));
+    }
+
+    #[test]
+    fn beat_overrides_count_from_their_first_frame() {
+        let store = OverrideStore::default();
+        let req = OverrideRequest { beats: Some(4), ..request("blackout", Some(json!(true))) };
+        store.set(req, "ops").unwrap();
+
+        let mut state = at_beat(10);
+        store.apply(&mut state);
+        assert_eq!(state.overrides[0].remaining_beats, Some(4));
+        let mut state = at_beat(13);
+        store.apply(&mut state);
+        assert!(state.blackout);
+        assert_eq!(state.overrides[0].remaining_beats, Some(1));
+
+        let mut state = at_beat(14);
+        store.apply(&mut state);
+        assert!(!state.blackout);
+        assert!(state.overrides.is_empty());
+    }
+
+    #[test]
+    fn timed_overrides_expire_on_the_wall_clock() {
+        let store = OverrideStore::default();
+        let req = OverrideRequest { duration_ms: Some(1), ..request("bpm", Some(json!(90.0))) };
+        store.set(req, "ops").unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        let mut state = at_beat(0);
+        store.apply(&mut state);
+        assert_eq!(state.bpm, 128.0);
+        assert!(store.list().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_fields_values_and_expiry() {
+        assert!(validate(&request("bpm", Some(json!(0.0)))).is_err());
+        assert!(validate(&request("glitch_factor", Some(json!(1.5)))).is_err());
+        let palette = json!({"primary": "red", "secondary": "#000000"});
+        assert!(validate(&request("palette", Some(palette))).is_err());
+        assert!(validate(&request("volume", None)).is_err());
+        assert!(validate(&request("params.", None)).is_err());
+        let zero = OverrideRequest { beats: Some(0), ..request("bpm", None) };
+        assert!(validate(&zero).is_err());
+        assert!(validate(&request("bpm", Some(json!(174.0)))).is_ok());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::color::{lerp_hex, parse_hex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
+
+// Shipped defaults; declares the parameters the built-in rules write
+const BUILTIN_PARAMS: &str = include_str!("../config/params.json");
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    Float,
+    Color,
+    Bool,
+    /// Written as `true` to fire; published as a count of firings.
+    Trigger,
+}
+
+/// Value of a custom parameter. Untagged, so floats stay plain numbers on the wire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Number(f32),
+    Color(String),
+}
+
+impl ParamValue {
+    /// Numbers and colours interpolate (colours in OKLab); anything else
+    /// switches at the midpoint.
+    pub fn lerp(&self, to: &ParamValue, t: f32) -> ParamValue {
+        match (self, to) {
+            (ParamValue::Number(a), ParamValue::Number(b)) => ParamValue::Number(a + (b - a) * t),
+            (ParamValue::Color(a), ParamValue::Color(b)) => ParamValue::Color(lerp_hex(a, b, t)),
+            _ if t < 0.5 => self.clone(),
+            _ => to.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParamDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ParamType,
+    /// Floats are clamped into `min..=max`.
+    #[serde(default)]
+    pub min: Option<f32>,
+    #[serde(default)]
+    pub max: Option<f32>,
+    /// Published while nothing writes the parameter (0, `#000000` or false if unset).
+    #[serde(default)]
+    pub default: Option<ParamValue>,
+    /// Can drive brightness or flashes; the safety limiter slews and
+    /// flash-counts it. Floats need `min` and `max`.
+    #[serde(default)]
+    pub safety: bool,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ParamDef {
+    pub fn default_value(&self) -> ParamValue {
+        match (&self.default, self.kind) {
+            (Some(v), _) => v.clone(),
+            (None, ParamType::Float | ParamType::Trigger) => ParamValue::Number(0.0),
+            (None, ParamType::Color) => ParamValue::Color("#000000".into()),
+            (None, ParamType::Bool) => ParamValue::Bool(false),
+        }
+    }
+}
+
+/// Declared parameters (`PARAMS_PATH`, built-in `config/params.json`). Rules,
+/// scripts, overrides, scenes and the AI director write through `check`, so
+/// the frontend can bind uniforms by name and rely on the declared type.
+/// Undeclared names are still accepted as floats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParamSchema {
+    pub params: Vec<ParamDef>,
+}
+
+impl ParamSchema {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let schema: ParamSchema = serde_json::from_str(text)?;
+        let mut names = HashSet::new();
+        let mut errors = Vec::new();
+        for def in &schema.params {
+            if def.name.trim().is_empty() || def.name.contains('.') {
+                errors.push(format!("invalid parameter name '{}'", def.name));
+            }
+            if !names.insert(def.name.as_str()) {
+                errors.push(format!("{}: declared twice", def.name));
+            }
+            if let (Some(min), Some(max)) = (def.min, def.max) {
+                if min > max {
+                    errors.push(format!("{}: min is above max", def.name));
+                }
+            }
+            let bounded = def.min.is_some() && def.max.is_some();
+            if def.safety && def.kind == ParamType::Float && !bounded {
+                errors.push(format!("{}: safety floats need min and max", def.name));
+            }
+            if let Some(default) = &def.default {
+                if let Err(e) = schema.check(&def.name, default.clone()) {
+                    errors.push(format!("{}: default {}", def.name, e));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(schema)
+        } else {
+            anyhow::bail!(errors.join("; "))
+        }
+    }
+
+    /// Loaded once per process, like the genre taxonomy.
+    pub fn global() -> &'static ParamSchema {
+        static SCHEMA: OnceLock<ParamSchema> = OnceLock::new();
+        SCHEMA.get_or_init(Self::from_env)
+    }
+
+    fn from_env() -> Self {
+        let builtin = || Self::parse(BUILTIN_PARAMS).expect("Invalid built-in parameter schema");
+        let Ok(path) = std::env::var("PARAMS_PATH") else { return builtin() };
+
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Self::parse(&text));
+        match loaded {
+            Ok(schema) => {
+                let count = schema.params.len();
+                println!("🎚️ [Params] Loaded {} parameters from {:?}", count, path);
+                schema
+            }
+            Err(e) => {
+                println!("⚠️ [Params] Schema {:?} rejected, using built-in: {}", path, e);
+                builtin()
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ParamDef> {
+        self.params.iter().find(|d| d.name == name)
+    }
+
+    /// Validates a write and normalizes it (clamped floats, upper-case colours).
+    pub fn check(&self, name: &str, value: ParamValue) -> anyhow::Result<ParamValue> {
+        let Some(def) = self.get(name) else {
+            return match value {
+                ParamValue::Number(_) => Ok(value),
+                _ => anyhow::bail!("undeclared parameter '{}' only takes numbers", name),
+            };
+        };
+        match (def.kind, value) {
+            (ParamType::Float, ParamValue::Number(v)) => Ok(ParamValue::Number(
+                v.max(def.min.unwrap_or(f32::MIN)).min(def.max.unwrap_or(f32::MAX)),
+            )),
+            (ParamType::Color, ParamValue::Color(c)) if parse_hex(&c).is_some() => {
+                Ok(ParamValue::Color(c.to_uppercase()))
+            }
+            (ParamType::Bool | ParamType::Trigger, ParamValue::Bool(b)) => Ok(ParamValue::Bool(b)),
+            (kind, value) => {
+                anyhow::bail!("'{}' is a {:?} parameter, got {:?}", name, kind, value)
+            }
+        }
+    }
+
+    /// One line per parameter for the AI director's prompt.
+    pub fn describe(&self) -> String {
+        self.params
+            .iter()
+            .filter(|d| d.kind != ParamType::Trigger)
+            .map(|d| format!("{} ({:?}) {}", d.name, d.kind, d.description).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Last step before publishing: fills in defaults for declared parameters
+/// nobody wrote this frame and turns trigger writes into firing counts. A
+/// trigger fires on the first frame it is written `true`, so a rule that
+/// keeps matching fires it once.
+#[derive(Default)]
+pub struct ParamResolver {
+    counts: BTreeMap<String, u64>,
+    held: HashSet<String>,
+}
+
+impl ParamResolver {
+    pub fn apply(&mut self, params: &mut BTreeMap<String, ParamValue>) {
+        self.resolve(ParamSchema::global(), params);
+    }
+
+    fn resolve(&mut self, schema: &ParamSchema, params: &mut BTreeMap<String, ParamValue>) {
+        let mut held = HashSet::new();
+        for def in &schema.params {
+            if def.kind != ParamType::Trigger {
+                params.entry(def.name.clone()).or_insert_with(|| def.default_value());
+                continue;
+            }
+            let count = self.counts.entry(def.name.clone()).or_default();
+            if params.get(&def.name) == Some(&ParamValue::Bool(true)) {
+                if !self.held.contains(&def.name) {
+                    *count += 1;
+                }
+                held.insert(def.name.clone());
+            }
+            params.insert(def.name.clone(), ParamValue::Number(*count as f32));
+        }
+        self.held = held;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r##"{"params": [
+        {"name": "strobe", "type": "float", "min": 0.0, "max": 1.0, "safety": true},
+        {"name": "tint", "type": "color", "default": "#ff0000"},
+        {"name": "mirror", "type": "bool"},
+        {"name": "burst", "type": "trigger"}
+    ]}"##;
+
+    fn schema() -> ParamSchema {
+        ParamSchema::parse(SCHEMA).unwrap()
+    }
+
+    #[test]
+    fn the_built_in_schema_is_valid() {
+        assert!(ParamSchema::parse(BUILTIN_PARAMS).is_ok());
+    }
+
+    #[test]
+    fn validation_lists_every_error() {
+        let text = r##"{"params": [
+            {"name": "a.b", "type": "float"},
+            {"name": "dup", "type": "bool"},
+            {"name": "dup", "type": "bool"},
+            {"name": "flash", "type": "float", "min": 0.0, "safety": true},
+            {"name": "range", "type": "float", "min": 2.0, "max": 1.0},
+            {"name": "tint", "type": "color", "default": "red"}
+        ]}"##;
+        let error = ParamSchema::parse(text).unwrap_err().to_string();
+        for expected in
+            ["'a.b'", "dup: declared twice", "flash: safety", "range: min", "tint: default"]
+        {
+            assert!(error.contains(expected), "{} missing from {}", expected, error);
+        }
+    }
+
+    #[test]
+    fn writes_are_clamped_and_normalised() {
+        let schema = schema();
+        assert_eq!(
+            schema.check("strobe", ParamValue::Number(3.0)).unwrap(),
+            ParamValue::Number(1.0)
+        );
+        assert_eq!(
+            schema.check("strobe", ParamValue::Number(-1.0)).unwrap(),
+            ParamValue::Number(0.0)
+        );
+        assert_eq!(
+            schema.check("tint", ParamValue::Color("#00ff7f".into())).unwrap(),
+            ParamValue::Color("#00FF7F".into())
+        );
+        assert!(schema.check("tint", ParamValue::Color("green".into())).is_err());
+        assert!(schema.check("mirror", ParamValue::Number(1.0)).is_err());
+        assert!(schema.check("burst", ParamValue::Bool(true)).is_ok());
+    }
+
+    #[test]
+    fn undeclared_parameters_take_only_numbers() {
+        let schema = schema();
+        assert_eq!(schema.check("free", ParamValue::Number(7.5)).unwrap(), ParamValue::Number(7.5));
+        assert!(schema.check("free", ParamValue::Bool(true)).is_err());
+    }
+
+    #[test]
+    fn defaults_fill_unwritten_parameters() {
+        let mut params = BTreeMap::from([("strobe".to_string(), ParamValue::Number(0.5))]);
+        ParamResolver::default().resolve(&schema(), &mut params);
+        assert_eq!(params["strobe"], ParamValue::Number(0.5));
+        assert_eq!(params["tint"], ParamValue::Color("#ff0000".into()));
+        assert_eq!(params["mirror"], ParamValue::Bool(false));
+        assert_eq!(params["burst"], ParamValue::Number(0.0));
+    }
+
+    #[test]
+    fn triggers_count_rising_edges() {
+        let schema = schema();
+        let mut resolver = ParamResolver::default();
+        let mut frame = |fired: bool| {
+            let mut params = BTreeMap::new();
+            if fired {
+                params.insert("burst".to_string(), ParamValue::Bool(true));
+            }
+            resolver.resolve(&schema, &mut params);
+            params["burst"].clone()
+        };
+        assert_eq!(frame(true), ParamValue::Number(1.0));
+        assert_eq!(frame(true), ParamValue::Number(1.0));
+        assert_eq!(frame(false), ParamValue::Number(1.0));
+        assert_eq!(frame(true), ParamValue::Number(2.0));
+    }
+
+    #[test]
+    fn values_interpolate_by_kind() {
+        let (a, b) = (ParamValue::Number(0.0), ParamValue::Number(2.0));
+        assert_eq!(a.lerp(&b, 0.25), ParamValue::Number(0.5));
+        let (on, off) = (ParamValue::Bool(true), ParamValue::Bool(false));
+        assert_eq!(on.lerp(&off, 0.4), on);
+        assert_eq!(on.lerp(&off, 0.5), off);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::crowd::CrowdStats;
+use crate::genre::GenreTaxonomy;
+use crate::lfo::LfoBank;
+use crate::llm_engine::AiContext;
+use crate::params::ParamResolver;
+use crate::safety::{SafetyConfig, SafetyLimiter};
+use crate::show::ShowControl;
+use crate::state_machine::{Genre, GlobalState, VibePhase, VibeState};
+use crate::ticker::FrameStamper;
+use crate::transition::{PaletteTransition, TransitionConfig};
+use crate::trend::TrendDirection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// What the ticker adds to the Overmind's output from outside the audio: the
+/// director's context and the crowd figures. Logged with every published frame
+/// so feature replays can rebuild it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameInputs {
+    pub ai: AiContext,
+    /// Genre `ai` was chosen for; `AiContext` does not serialise it.
+    #[serde(default)]
+    pub ai_genre: Option<String>,
+    pub crowd: CrowdStats,
+}
+
+impl FrameInputs {
+    pub fn new(ai: &AiContext, crowd: CrowdStats) -> Self {
+        Self { ai: ai.clone(), ai_genre: ai.genre.clone(), crowd }
+    }
+
+    /// Best effort for logs written before the inputs were: the published
+    /// target colours stand in for the director's (no genre fallback on top),
+    /// its parameter writes are already merged into `params`.
+    pub fn of_published(state: &GlobalState) -> Self {
+        let ai = AiContext {
+            theme: state.ai_theme.clone(),
+            primary_color: state.ai_target_primary_color.clone(),
+            secondary_color: state.ai_target_secondary_color.clone(),
+            directive: state.ai_directive.clone(),
+            params: Default::default(),
+            genre: Some(state.genre.as_str().to_string()),
+        };
+        Self::new(&ai, state.crowd.clone())
+    }
+}
+
+/// The Overmind's own decisions for a published frame, before post-processing.
+/// Feature replays compare against these, not against the published frame.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Analysis {
+    pub state: VibeState,
+    pub phase: VibePhase,
+    pub genre: Genre,
+    pub energy_trend: TrendDirection,
+    pub glitch_factor: f32,
+}
+
+impl Analysis {
+    pub fn of(state: &GlobalState) -> Self {
+        Self {
+            state: state.state,
+            phase: state.phase,
+            genre: state.genre.clone(),
+            energy_trend: state.energy_trend,
+            glitch_factor: state.glitch_factor,
+        }
+    }
+
+    pub fn diverges(&self, other: &Analysis) -> bool {
+        self.state != other.state
+            || self.phase != other.phase
+            || self.genre != other.genre
+            || self.energy_trend != other.energy_trend
+            || (self.glitch_factor - other.glitch_factor).abs() > 1e-4
+    }
+}
+
+/// Everything between the Overmind and the wire. The live ticker and feature
+/// replays run it once per published frame, so both publish the same frame for
+/// the same analysis and inputs.
+pub struct FrameFinisher {
+    show: Arc<ShowControl>,
+    palette_fade: PaletteTransition,
+    param_resolver: ParamResolver,
+    safety: SafetyLimiter,
+    stamper: FrameStamper,
+}
+
+impl FrameFinisher {
+    pub fn new(show: Arc<ShowControl>, lfos: Arc<LfoBank>, target_hz: Option<f32>) -> Self {
+        Self {
+            show,
+            palette_fade: PaletteTransition::new(TransitionConfig::from_env()),
+            param_resolver: ParamResolver::default(),
+            safety: SafetyLimiter::new(SafetyConfig::from_env()).with_lfos(lfos),
+            stamper: FrameStamper::new(target_hz),
+        }
+    }
+
+    pub fn apply(&mut self, state: &mut GlobalState, inputs: &FrameInputs, audio_ms: Option<f64>) {
+        state.crowd = inputs.crowd.clone();
+
+        // Inject AI Context
+        let ai = &inputs.ai;
+        state.ai_theme = ai.theme.clone();
+        state.ai_primary_color = ai.primary_color.clone();
+        state.ai_secondary_color = ai.secondary_color.clone();
+        state.ai_directive = ai.directive.clone();
+        state.params.extend(ai.params.clone());
+
+        // The locked genre's palette until the oracle has answered for it
+        // (director off, failing or not consulted yet); crowd chaos keeps its red
+        let answered = inputs.ai_genre.as_deref() == Some(state.genre.as_str());
+        let def = GenreTaxonomy::global().get(&state.genre);
+        let fallback = !answered && !state.crowd.chaos;
+        if let Some(p) = def.palette.as_ref().filter(|_| fallback) {
+            state.ai_primary_color = p.primary.clone();
+            state.ai_secondary_color = p.secondary.clone();
+        }
+        self.palette_fade.apply(state);
+
+        // Operator scenes and overrides have the last word
+        self.show.apply(state);
+        self.param_resolver.apply(&mut state.params);
+        self.safety.apply(state);
+        self.stamper.stamp(state, audio_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finisher(test: &str) -> FrameFinisher {
+        let scenes = std::env::temp_dir().join(format!("publish-test-{}-scenes.json", test));
+        FrameFinisher::new(ShowControl::load(scenes, None), LfoBank::load(None), None)
+    }
+
+    fn inputs(genre: Option<&str>) -> FrameInputs {
+        let ai = AiContext {
+            theme: "NEON_RAIN".to_string(),
+            primary_color: "#112233".to_string(),
+            secondary_color: "#445566".to_string(),
+            directive: "HOLD".to_string(),
+            params: Default::default(),
+            genre: genre.map(str::to_string),
+        };
+        FrameInputs::new(&ai, CrowdStats { boredom: 0.25, ..CrowdStats::default() })
+    }
+
+    fn techno() -> GlobalState {
+        GlobalState { genre: Genre::new("Techno"), ..GlobalState::default() }
+    }
+
+    #[test]
+    fn applies_the_director_context_and_crowd() {
+        let mut state = techno();
+        finisher("answered").apply(&mut state, &inputs(Some("Techno")), None);
+        assert_eq!(state.ai_theme, "NEON_RAIN");
+        assert_eq!(state.ai_primary_color, "#112233");
+        assert_eq!(state.ai_target_secondary_color, "#445566");
+        assert_eq!(state.crowd.boredom, 0.25);
+        assert_eq!(state.frame.seq, 0);
+    }
+
+    #[test]
+    fn uses_the_genre_palette_until_the_director_answers() {
+        let mut state = techno();
+        finisher("fallback").apply(&mut state, &inputs(Some("Ambient")), None);
+        assert_eq!(state.ai_theme, "NEON_RAIN");
+        assert_eq!(state.ai_primary_color, "#00FFFF");
+    }
+
+    #[test]
+    fn old_logs_replay_the_published_colours() {
+        let mut recorded = techno();
+        recorded.ai_target_primary_color = "#ABCDEF".to_string();
+        let mut state = techno();
+        finisher("published").apply(&mut state, &FrameInputs::of_published(&recorded), None);
+        assert_eq!(state.ai_primary_color, "#ABCDEF");
+    }
+
+    #[test]
+    fn analysis_compares_the_overmind_output() {
+        let live = Analysis::of(&techno());
+        assert!(!live.diverges(&Analysis::of(&techno())));
+
+        let mut glitched = techno();
+        glitched.glitch_factor += 0.1;
+        assert!(live.diverges(&Analysis::of(&glitched)));
+        let mut other = techno();
+        other.genre = Genre::new("DnB");
+        assert!(live.diverges(&Analysis::of(&other)));
+    }
+}
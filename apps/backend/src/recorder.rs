@@ -0,0 +1,537 @@
+use crate::audio_engine::AudioFeatures;
+use crate::publish::{Analysis, FrameInputs};
+use crate::state_machine::{AudioMetadata, GlobalState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+// Bounded queue between the audio callback / Overmind loop and the disk writer.
+// If the disk stalls we drop chunks rather than block the realtime thread.
+const QUEUE_DEPTH: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    pub dir: PathBuf,
+    pub max_segment_bytes: u64,
+    pub max_segment_secs: u64,
+    pub max_total_bytes: u64,
+}
+
+impl RecorderConfig {
+    pub fn from_env() -> Self {
+        let num = |key: &str, default: u64| {
+            std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        Self {
+            dir: std::env::var("RECORDER_DIR").unwrap_or_else(|_| "recordings".to_string()).into(),
+            max_segment_bytes: num("RECORDER_MAX_SEGMENT_MB", 512) * 1024 * 1024,
+            max_segment_secs: num("RECORDER_MAX_SEGMENT_SECS", 900),
+            max_total_bytes: num("RECORDER_MAX_TOTAL_MB", 8192) * 1024 * 1024,
+        }
+    }
+}
+
+/// One line of the sidecar log. Lines with `features` are analysed capture
+/// buffers, lines with `state` are published frames; logs written before the
+/// fixed-rate ticker carry both on every line. Published frames also carry the
+/// Overmind's own output and the inputs the ticker combined it with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub t_ms: u64,
+    pub unix_ms: u64,
+    /// Offset of this line into the segment's WAV file, in sample frames.
+    pub segment_sample: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<AudioFeatures>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<GlobalState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<Analysis>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<FrameInputs>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct RecorderStatus {
+    pub recording: bool,
+    pub label: Option<String>,
+    pub segment_index: u32,
+    pub current_wav: Option<String>,
+    pub current_log: Option<String>,
+    pub segment_bytes: u64,
+    pub total_bytes: u64,
+    pub dropped_chunks: u64,
+}
+
+enum RecorderMsg {
+    Start { label: Option<String>, opened: oneshot::Sender<anyhow::Result<()>> },
+    Stop,
+    Audio { clock_end: u64, samples: Vec<f32> },
+    Frame { features: Option<AudioFeatures>, published: Option<Box<Published>> },
+}
+
+struct Published {
+    state: GlobalState,
+    analysis: Analysis,
+    inputs: FrameInputs,
+}
+
+pub struct Recorder {
+    tx: SyncSender<RecorderMsg>,
+    active: Arc<AtomicBool>, // Set by the writer once a session's files are open
+    dropped: AtomicU64,
+    format: Arc<Mutex<AudioMetadata>>,
+    status: Arc<Mutex<RecorderStatus>>,
+}
+
+impl Recorder {
+    pub fn new(config: RecorderConfig) -> Arc<Self> {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_DEPTH);
+        let format = Arc::new(Mutex::new(AudioMetadata::default()));
+        let status = Arc::new(Mutex::new(RecorderStatus::default()));
+        let active = Arc::new(AtomicBool::new(false));
+
+        let writer = Writer::new(config, format.clone(), status.clone(), active.clone());
+        std::thread::Builder::new()
+            .name("vibe-recorder".to_string())
+            .spawn(move || writer.run(rx))
+            .expect("Failed to spawn recorder thread");
+
+        Arc::new(Self { tx, active, dropped: AtomicU64::new(0), format, status })
+    }
+
+    pub fn set_audio_format(&self, meta: &AudioMetadata) {
+        *self.format.lock().unwrap() = meta.clone();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Returns once the writer has opened the first segment, or with its error.
+    /// Fails instead of waiting when the writer's queue is full (disk stall).
+    pub async fn start(&self, label: Option<String>) -> anyhow::Result<()> {
+        let (opened, result) = oneshot::channel();
+        self.control(RecorderMsg::Start { label, opened })?;
+        result.await.map_err(|_| anyhow::anyhow!("Recorder writer has stopped"))?
+    }
+
+    pub fn stop(&self) -> anyhow::Result<()> {
+        self.control(RecorderMsg::Stop)?;
+        self.active.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Called from the audio callback. Never blocks.
+    pub fn push_audio(&self, clock_end: u64, samples: &[f32]) {
+        if !self.is_active() {
+            return;
+        }
+        self.offer(RecorderMsg::Audio { clock_end, samples: samples.to_vec() });
+    }
+
+    /// Logs every analysed buffer, so feature replays step the same sequence.
+    pub fn push_features(&self, features: &AudioFeatures) {
+        if !self.is_active() {
+            return;
+        }
+        self.offer(RecorderMsg::Frame { features: Some(features.clone()), published: None });
+    }
+
+    /// Logs a published frame with what it was made from.
+    pub fn push_frame(&self, state: &GlobalState, analysis: &Analysis, inputs: &FrameInputs) {
+        if !self.is_active() {
+            return;
+        }
+        let published =
+            Published { state: state.clone(), analysis: analysis.clone(), inputs: inputs.clone() };
+        self.offer(RecorderMsg::Frame { features: None, published: Some(Box::new(published)) });
+    }
+
+    pub fn status(&self) -> RecorderStatus {
+        let mut status = self.status.lock().unwrap().clone();
+        status.dropped_chunks = self.dropped.load(Ordering::Relaxed);
+        status
+    }
+
+    // Called from request handlers, which must not block the runtime on the disk
+    fn control(&self, msg: RecorderMsg) -> anyhow::Result<()> {
+        match self.tx.try_send(msg) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => anyhow::bail!("Recorder queue is full, try again"),
+            Err(TrySendError::Disconnected(_)) => anyhow::bail!("Recorder writer has stopped"),
+        }
+    }
+
+    fn offer(&self, msg: RecorderMsg) {
+        match self.tx.try_send(msg) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.active.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// --- Disk Writer (runs on its own thread) ---
+
+struct Segment {
+    wav: Option<hound::WavWriter<BufWriter<File>>>,
+    log: BufWriter<File>,
+    wav_path: PathBuf,
+    log_path: PathBuf,
+    started: Instant,
+    // Sample clock of the first audio chunk in this segment (frames).
+    clock_origin: Option<u64>,
+    channels: u16,
+    bytes: u64,
+}
+
+struct Writer {
+    config: RecorderConfig,
+    format: Arc<Mutex<AudioMetadata>>,
+    status: Arc<Mutex<RecorderStatus>>,
+    active: Arc<AtomicBool>,
+    session: Option<String>,
+    segment: Option<Segment>,
+    segment_index: u32,
+    // Finished segments, oldest first, for total-size retention. Starts with
+    // the segments earlier runs left in the directory.
+    finished: VecDeque<(PathBuf, PathBuf, u64)>,
+    // Sample clock of the last analysed buffer, for published-frame lines
+    last_clock: u64,
+}
+
+impl Writer {
+    fn new(
+        config: RecorderConfig,
+        format: Arc<Mutex<AudioMetadata>>,
+        status: Arc<Mutex<RecorderStatus>>,
+        active: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            config,
+            format,
+            status,
+            active,
+            session: None,
+            segment: None,
+            segment_index: 0,
+            finished: VecDeque::new(),
+            last_clock: 0,
+        }
+    }
+
+    fn run(mut self, rx: Receiver<RecorderMsg>) {
+        self.scan_existing();
+        self.enforce_retention();
+        self.publish_status();
+        while let Ok(msg) = rx.recv() {
+            let result = match msg {
+                RecorderMsg::Start { label, opened } => {
+                    let result = self.start(label);
+                    // Set before replying, so a successful start reads as recording
+                    self.active.store(result.is_ok(), Ordering::Relaxed);
+                    let reply = result.as_ref().map(|_| ()).map_err(|e| anyhow::anyhow!("{}", e));
+                    let _ = opened.send(reply);
+                    result
+                }
+                RecorderMsg::Stop => {
+                    self.close_segment();
+                    self.session = None;
+                    self.publish_status();
+                    Ok(())
+                }
+                RecorderMsg::Audio { clock_end, samples } => self.write_audio(clock_end, &samples),
+                RecorderMsg::Frame { features, published } => self.write_frame(features, published),
+            };
+
+            if let Err(e) = result {
+                error!(event = "recorder_write_failed", error = %e);
+                self.active.store(false, Ordering::Relaxed);
+                self.close_segment();
+                self.session = None;
+                self.publish_status();
+            }
+        }
+        self.close_segment();
+    }
+
+    fn start(&mut self, label: Option<String>) -> anyhow::Result<()> {
+        if self.session.is_some() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.config.dir)?;
+
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        // The stamp has 1 s resolution; the counter keeps starts within a second apart
+        let name = (0..)
+            .map(|n| match &label {
+                Some(l) => format!("{}-{}-{}", stamp, n, sanitize(l)),
+                None => format!("{}-{}", stamp, n),
+            })
+            .find(|name| !self.segment_path(name, 0, "jsonl").exists())
+            .unwrap_or_default();
+        println!("🔴 [Recorder] Recording session '{}' to {:?}", name, self.config.dir);
+
+        self.session = Some(name);
+        self.segment_index = 0;
+        self.status.lock().unwrap().label = label;
+        self.open_segment()
+    }
+
+    fn open_segment(&mut self) -> anyhow::Result<()> {
+        let session = self.session.clone().unwrap_or_default();
+        let wav_path = self.segment_path(&session, self.segment_index, "wav");
+        let log_path = self.segment_path(&session, self.segment_index, "jsonl");
+
+        let meta = self.format.lock().unwrap().clone();
+        // NO_AUDIO mode has no format to write; keep the feature log only.
+        let wav = if meta.channels > 0 && meta.sample_rate > 0 {
+            let spec = hound::WavSpec {
+                channels: meta.channels,
+                sample_rate: meta.sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            };
+            Some(hound::WavWriter::create(&wav_path, spec)?)
+        } else {
+            None
+        };
+        let log = BufWriter::new(File::create(&log_path)?);
+
+        info!(event = "recorder_segment_opened", wav = ?wav_path, log = ?log_path);
+        self.segment = Some(Segment {
+            wav,
+            log,
+            wav_path,
+            log_path,
+            started: Instant::now(),
+            clock_origin: None,
+            channels: meta.channels.max(1),
+            bytes: 0,
+        });
+        self.publish_status();
+        Ok(())
+    }
+
+    fn segment_path(&self, session: &str, index: u32, ext: &str) -> PathBuf {
+        self.config.dir.join(format!("session-{}-{:03}.{}", session, index, ext))
+    }
+
+    fn close_segment(&mut self) {
+        let Some(mut seg) = self.segment.take() else { return };
+
+        if let Err(e) = seg.log.flush() {
+            warn!(event = "recorder_log_flush_failed", error = %e);
+        }
+        if let Some(wav) = seg.wav.take() {
+            if let Err(e) = wav.finalize() {
+                warn!(event = "recorder_wav_finalize_failed", error = %e);
+            }
+        }
+
+        self.finished.push_back((seg.wav_path, seg.log_path, seg.bytes));
+        self.enforce_retention();
+    }
+
+    /// Picks up the segments in the directory, oldest first, so the size limit
+    /// covers previous runs as well.
+    fn scan_existing(&mut self) {
+        let Ok(entries) = std::fs::read_dir(&self.config.dir) else { return };
+        let mut segments: BTreeMap<String, (SystemTime, u64)> = BTreeMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(base) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+            let ext = path.extension().and_then(|e| e.to_str());
+            if !base.starts_with("session-") || !matches!(ext, Some("wav" | "jsonl")) {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+            let segment = segments.entry(base.to_string()).or_insert((modified, 0));
+            segment.0 = segment.0.max(modified);
+            segment.1 += meta.len();
+        }
+
+        let mut segments: Vec<_> = segments.into_iter().collect();
+        segments.sort_by_key(|(base, (modified, _))| (*modified, base.clone()));
+        for (base, (_, bytes)) in segments {
+            let wav = self.config.dir.join(format!("{}.wav", base));
+            let log = self.config.dir.join(format!("{}.jsonl", base));
+            self.finished.push_back((wav, log, bytes));
+        }
+        if !self.finished.is_empty() {
+            info!(event = "recorder_segments_found", count = self.finished.len());
+        }
+    }
+
+    /// Deletes the oldest segments while the total, including the one being
+    /// written, is over the limit. The newest file on disk always survives.
+    fn enforce_retention(&mut self) {
+        let live = self.segment.as_ref().map(|s| s.bytes);
+        let mut total: u64 =
+            self.finished.iter().map(|(_, _, b)| b).sum::<u64>() + live.unwrap_or(0);
+        let keep = if live.is_some() { 0 } else { 1 };
+        while total > self.config.max_total_bytes && self.finished.len() > keep {
+            let Some((wav, log, bytes)) = self.finished.pop_front() else { break };
+            println!("🧹 [Recorder] Size limit reached, removing {:?}", wav);
+            let _ = std::fs::remove_file(&wav);
+            let _ = std::fs::remove_file(&log);
+            total -= bytes;
+        }
+    }
+
+    fn rotate_if_needed(&mut self) -> anyhow::Result<()> {
+        let needs_rotation = match &self.segment {
+            Some(seg) => {
+                seg.bytes >= self.config.max_segment_bytes
+                    || seg.started.elapsed().as_secs() >= self.config.max_segment_secs
+            }
+            None => false,
+        };
+
+        if needs_rotation {
+            self.close_segment();
+            self.segment_index += 1;
+            self.open_segment()?;
+        }
+        Ok(())
+    }
+
+    fn write_audio(&mut self, clock_end: u64, samples: &[f32]) -> anyhow::Result<()> {
+        self.rotate_if_needed()?;
+        let Some(seg) = self.segment.as_mut() else { return Ok(()) };
+        let Some(wav) = seg.wav.as_mut() else { return Ok(()) };
+
+        let frames = samples.len() as u64 / seg.channels as u64;
+        seg.clock_origin.get_or_insert(clock_end.saturating_sub(frames));
+
+        for &s in samples {
+            wav.write_sample(s)?;
+        }
+        seg.bytes += samples.len() as u64 * 4;
+        self.enforce_retention();
+        self.publish_status();
+        Ok(())
+    }
+
+    fn write_frame(
+        &mut self,
+        features: Option<AudioFeatures>,
+        published: Option<Box<Published>>,
+    ) -> anyhow::Result<()> {
+        self.rotate_if_needed()?;
+        if let Some(f) = &features {
+            self.last_clock = f.sample_clock;
+        }
+        let clock = self.last_clock;
+        let Some(seg) = self.segment.as_mut() else { return Ok(()) };
+
+        let origin = seg.clock_origin.unwrap_or(clock);
+        let (state, analysis, inputs) = match published.map(|p| *p) {
+            Some(Published { state, analysis, inputs }) => {
+                (Some(state), Some(analysis), Some(inputs))
+            }
+            None => (None, None, None),
+        };
+        let record = FrameRecord {
+            t_ms: seg.started.elapsed().as_millis() as u64,
+            unix_ms: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+                as u64,
+            segment_sample: clock.saturating_sub(origin),
+            features,
+            state,
+            analysis,
+            inputs,
+        };
+
+        let line = serde_json::to_string(&record)?;
+        seg.log.write_all(line.as_bytes())?;
+        seg.log.write_all(b"\n")?;
+        seg.bytes += line.len() as u64 + 1;
+        self.enforce_retention();
+        Ok(())
+    }
+
+    fn publish_status(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.recording = self.session.is_some();
+        status.segment_index = self.segment_index;
+        status.current_wav = self.segment.as_ref().map(|s| s.wav_path.display().to_string());
+        status.current_log = self.segment.as_ref().map(|s| s.log_path.display().to_string());
+        status.segment_bytes = self.segment.as_ref().map(|s| s.bytes).unwrap_or(0);
+        status.total_bytes =
+            self.finished.iter().map(|(_, _, b)| b).sum::<u64>() + status.segment_bytes;
+    }
+}
+
+fn sanitize(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NO_AUDIO format (no channels), so segments hold only the log
+    fn writer(test: &str, max_segment_bytes: u64, max_total_bytes: u64) -> Writer {
+        let dir = std::env::temp_dir().join(format!("recorder-test-{}", test));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config =
+            RecorderConfig { dir, max_segment_bytes, max_segment_secs: 3600, max_total_bytes };
+        Writer::new(config, Arc::default(), Arc::default(), Arc::default())
+    }
+
+    #[test]
+    fn rotates_once_a_segment_is_full() {
+        let mut w = writer("rotate", 100, u64::MAX);
+        w.start(None).unwrap();
+        let first = w.segment.as_ref().unwrap().log_path.clone();
+        for _ in 0..3 {
+            w.write_frame(None, None).unwrap();
+        }
+        assert_eq!(w.segment_index, 1);
+        assert!(first.exists());
+        assert!(w.segment.as_ref().unwrap().log_path.exists());
+    }
+
+    #[test]
+    fn retention_counts_the_live_segment() {
+        let mut w = writer("retention", 100, 150);
+        w.start(None).unwrap();
+        let first = w.segment.as_ref().unwrap().log_path.clone();
+        for _ in 0..4 {
+            w.write_frame(None, None).unwrap();
+        }
+        // One finished segment plus the live one are over the limit together
+        assert!(!first.exists());
+        w.publish_status();
+        assert!(w.status.lock().unwrap().total_bytes <= 150);
+    }
+
+    #[test]
+    fn sessions_started_in_the_same_second_get_their_own_files() {
+        let mut w = writer("names", u64::MAX, u64::MAX);
+        w.start(Some("set".to_string())).unwrap();
+        let first = w.session.take().unwrap();
+        w.close_segment();
+        w.start(Some("set".to_string())).unwrap();
+        assert_ne!(w.session.as_deref(), Some(first.as_str()));
+    }
+}
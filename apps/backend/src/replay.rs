@@ -0,0 +1,230 @@
+use crate::clock::ManualClock;
+use crate::publish::{Analysis, FrameFinisher, FrameInputs};
+use crate::recorder::FrameRecord;
+use crate::show::ShowControl;
+use crate::state_machine::{AudioMetadata, GlobalState, Overmind, OvermindConfig};
+use crate::telemetry::Telemetry;
+use crate::ticker::{audio_ms, FrameStamper};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::time::{sleep, Duration};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayMode {
+    /// Re-emit the recorded `GlobalState` frames verbatim.
+    State,
+    /// Feed the recorded features through the current Overmind and compare its
+    /// output with what the Overmind decided live. The published frames go
+    /// through the same post-processing as live ones, with the recorded AI
+    /// context and crowd figures and the current operator controls. The
+    /// Overmind runs on the recorded clock, so results do not depend on `speed`.
+    Features,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    pub logs: Vec<PathBuf>,
+    pub mode: ReplayMode,
+    /// Playback speed multiplier. 0 replays as fast as possible.
+    pub speed: f32,
+    pub looped: bool,
+}
+
+impl ReplayConfig {
+    /// Returns `None` without a session (the zone's `replay`, `REPLAY_SESSION` by
+    /// default), i.e. live capture is the default. Mode, speed and looping come
+    /// from `REPLAY_MODE`, `REPLAY_SPEED` and `REPLAY_LOOP`.
+    pub fn load(session: Option<PathBuf>) -> Option<Self> {
+        let path = session?;
+        let logs = collect_logs(&path);
+        if logs.is_empty() {
+            println!("⚠️ [Replay] No .jsonl logs found at {:?}. Using live capture.", path);
+            return None;
+        }
+
+        let mode = match std::env::var("REPLAY_MODE").as_deref() {
+            Ok("features") => ReplayMode::Features,
+            _ => ReplayMode::State,
+        };
+        let speed = std::env::var("REPLAY_SPEED")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|s| s.is_finite() && *s >= 0.0)
+            .unwrap_or(1.0);
+        let looped = std::env::var("REPLAY_LOOP").map(|v| v == "1" || v == "true").unwrap_or(false);
+
+        Some(Self { logs, mode, speed, looped })
+    }
+
+    /// Audio metadata as seen by the original session (first published frame of the first log).
+    pub fn audio_meta(&self) -> AudioMetadata {
+        self.logs
+            .first()
+            .and_then(|p| read_log(p).into_iter().find_map(|r| r.state))
+            .map(|state| state.audio_meta)
+            .unwrap_or_default()
+    }
+}
+
+/// A directory replays every segment in it (file names sort chronologically),
+/// a file replays just that segment.
+fn collect_logs(path: &Path) -> Vec<PathBuf> {
+    if path.is_dir() {
+        let mut logs: Vec<PathBuf> = std::fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        logs.sort();
+        logs
+    } else if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        Vec::new()
+    }
+}
+
+fn read_log(path: &Path) -> Vec<FrameRecord> {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            warn!(event = "replay_open_failed", path = ?path, error = %e);
+            return Vec::new();
+        }
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<FrameRecord>(&line).ok())
+        .collect()
+}
+
+/// Drives the state broadcast from recorded logs instead of a live device.
+pub async fn run(
+    config: ReplayConfig,
+    audio_meta: AudioMetadata,
+    overmind_config: OvermindConfig,
+    show: Arc<ShowControl>,
+    telemetry: Arc<Telemetry>,
+    tx_state: broadcast::Sender<GlobalState>,
+) {
+    let sample_rate = audio_meta.sample_rate;
+    println!(
+        "🔁 [Replay] {} segment(s), mode {:?}, speed {}x{}",
+        config.logs.len(),
+        config.mode,
+        config.speed,
+        if config.looped { ", looping" } else { "" }
+    );
+
+    let mut finisher = FrameFinisher::new(show.clone(), overmind_config.lfos.clone(), None);
+    let mut stamper = FrameStamper::new(None);
+    loop {
+        // Fresh Overmind per pass so looped replays reproduce the same sequence
+        let clock = Arc::new(ManualClock::new());
+        let mut overmind =
+            Overmind::with_clock(audio_meta.clone(), &overmind_config, clock.clone());
+        let mut frames = 0u64;
+        let mut elapsed_ms = 0u64;
+        let mut diverged = 0u64;
+        let mut compared = 0u64;
+        let mut sample_clock = 0u64;
+        let mut analysed: Option<GlobalState> = None;
+        let mut pending_events = Vec::new();
+
+        for path in &config.logs {
+            let records = read_log(path);
+            let mut prev_t_ms: Option<u64> = None;
+
+            for record in records {
+                // Pace by the recorded timestamps
+                if let Some(prev) = prev_t_ms {
+                    let delta = record.t_ms.saturating_sub(prev);
+                    elapsed_ms += delta;
+                    let pause = delta as f32 / 1000.0 / config.speed;
+                    // Speed 0, or one too small to give a representable pause, never waits
+                    let pause = Duration::try_from_secs_f32(pause).ok().filter(|p| !p.is_zero());
+                    if let Some(pause) = pause {
+                        sleep(pause).await;
+                    }
+                }
+                prev_t_ms = Some(record.t_ms);
+
+                // Every analysed buffer steps the Overmind, as it did live
+                if let Some(f) = &record.features {
+                    sample_clock = f.sample_clock;
+                    if config.mode == ReplayMode::Features {
+                        if sample_rate > 0 {
+                            clock.set_samples(f.sample_clock, sample_rate);
+                        } else {
+                            // NO_AUDIO recordings have no sample clock
+                            clock.set(Duration::from_millis(elapsed_ms));
+                        }
+                        overmind.pin_bpm(show.overrides.pinned_bpm());
+                        let state = overmind.update(f);
+                        pending_events.extend(state.events.iter().cloned());
+                        analysed = Some(state);
+                    }
+                }
+
+                // Only published frames are sent on
+                let Some(recorded) = record.state else { continue };
+                let audio_ms = audio_ms(sample_clock, sample_rate);
+                let new_state = match config.mode {
+                    ReplayMode::State => {
+                        // Already post-processed; only the stamp and telemetry are this run's
+                        let mut state = recorded;
+                        telemetry.apply(&mut state);
+                        stamper.stamp(&mut state, audio_ms);
+                        state
+                    }
+                    ReplayMode::Features => {
+                        let Some(latest) = &analysed else { continue };
+                        // Logs from before the analysis was recorded cannot be compared
+                        if let Some(live) = &record.analysis {
+                            compared += 1;
+                            if Analysis::of(latest).diverges(live) {
+                                diverged += 1;
+                            }
+                        }
+
+                        // The LLM is not consulted during replay; reuse what it said live
+                        let inputs =
+                            record.inputs.unwrap_or_else(|| FrameInputs::of_published(&recorded));
+                        let mut state = latest.clone();
+                        state.events = std::mem::take(&mut pending_events);
+                        telemetry.apply(&mut state);
+                        finisher.apply(&mut state, &inputs, audio_ms);
+                        state
+                    }
+                };
+
+                frames += 1;
+                let _ = tx_state.send(new_state);
+                tokio::task::yield_now().await;
+            }
+        }
+
+        match config.mode {
+            ReplayMode::State => println!("🔁 [Replay] Pass complete: {} frames", frames),
+            ReplayMode::Features => println!(
+                "🔁 [Replay] Pass complete: {} frames, {} of {} compared diverged from recording",
+                frames, diverged, compared
+            ),
+        }
+
+        if !config.looped {
+            break;
+        }
+    }
+
+    // Keep clients connected on the last frame instead of tearing down the server
+    println!("⏹️ [Replay] Replay finished. Holding last state.");
+}
@@ -0,0 +1,511 @@
+use crate::params::{ParamSchema, ParamValue};
+use crate::state_machine::{GlobalState, VibeState};
+use crate::vibe::VibeConfig;
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+// Shipped defaults; reproduces the original compiled-in thresholds and glitch levels
+const BUILTIN_RULES: &str = include_str!("../config/rules.json");
+const RELOAD_POLL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub vibe: VibeConfig,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Top-level fields the conditions read; the only ones put in the view.
+    #[serde(skip)]
+    roots: Vec<String>,
+}
+
+/// `when` matches → `set` is applied. Rules run in file order after the
+/// Overmind's own analysis, so later rules see (and may override) earlier ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    pub set: RuleOutputs,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Condition {
+    All { all: Vec<Condition> },
+    Any { any: Vec<Condition> },
+    Not { not: Box<Condition> },
+    Compare { feature: String, op: Op, value: Value },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum Op {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RuleOutputs {
+    pub state: Option<VibeState>,
+    pub glitch_factor: Option<f32>,
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl RuleSet {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut set: RuleSet = serde_json::from_str(text)?;
+        set.validate()?;
+
+        let mut roots = Vec::new();
+        for rule in &set.rules {
+            rule.when.roots(&mut roots);
+        }
+        roots.sort();
+        roots.dedup();
+        set.roots = roots;
+        Ok(set)
+    }
+
+    /// Checks every condition against the `GlobalState` fields rules can read
+    /// so a typo is rejected at load time instead of silently never matching.
+    fn validate(&self) -> anyhow::Result<()> {
+        let defaults = GlobalState::default();
+        let mut errors = Vec::new();
+        let mut names = HashSet::new();
+
+        if let Err(e) = self.vibe.validate() {
+            errors.push(format!("vibe: {}", e));
+        }
+
+        for rule in &self.rules {
+            if rule.name.trim().is_empty() {
+                errors.push("rule with empty name".to_string());
+            }
+            if !names.insert(rule.name.as_str()) {
+                errors.push(format!("{}: duplicate rule name", rule.name));
+            }
+            let out = &rule.set;
+            if out.state.is_none() && out.glitch_factor.is_none() && out.params.is_empty() {
+                errors.push(format!("{}: rule sets nothing", rule.name));
+            }
+            if let Some(g) = out.glitch_factor {
+                if !(0.0..=1.0).contains(&g) {
+                    errors.push(format!("{}: glitch_factor {} outside 0..1", rule.name, g));
+                }
+            }
+            for (name, value) in &out.params {
+                if let Err(e) = ParamSchema::global().check(name, value.clone()) {
+                    errors.push(format!("{}: {}", rule.name, e));
+                }
+            }
+            validate_condition(&rule.name, &rule.when, &defaults, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!(errors.join("; "))
+        }
+    }
+
+    /// Applies every matching rule to `state`. Rule-owned outputs start from
+    /// neutral each frame so a rule that stops matching releases its values.
+    pub fn apply(&self, state: &mut GlobalState) {
+        state.glitch_factor = 0.0;
+        state.params.clear();
+
+        if self.rules.is_empty() {
+            return;
+        }
+
+        // JSON view of the fields the conditions read, patched as rules fire
+        let schema = ParamSchema::global();
+        let mut view = Value::Object(
+            self.roots
+                .iter()
+                .filter_map(|root| Some((root.clone(), feature(state, root)?)))
+                .collect(),
+        );
+
+        for rule in &self.rules {
+            if !rule.when.eval(&view) {
+                continue;
+            }
+            let out = &rule.set;
+            if let Some(s) = out.state {
+                state.state = s;
+                view["state"] = serde_json::to_value(s).unwrap_or(Value::Null);
+            }
+            if let Some(g) = out.glitch_factor {
+                state.glitch_factor = g;
+                view["glitch_factor"] = Value::from(g);
+            }
+            for (name, value) in &out.params {
+                // Clamped or normalized the same way it was validated at load
+                let value = schema.check(name, value.clone()).unwrap_or_else(|_| value.clone());
+                view["params"][name] = serde_json::to_value(&value).unwrap_or(Value::Null);
+                state.params.insert(name.clone(), value);
+            }
+        }
+    }
+}
+
+impl Condition {
+    /// Collects the top-level fields this condition reads.
+    fn roots(&self, roots: &mut Vec<String>) {
+        match self {
+            Condition::All { all: list } | Condition::Any { any: list } => {
+                list.iter().for_each(|c| c.roots(roots))
+            }
+            Condition::Not { not } => not.roots(roots),
+            Condition::Compare { feature, .. } => {
+                roots.push(feature.split('.').next().unwrap_or_default().to_string())
+            }
+        }
+    }
+
+    fn eval(&self, view: &Value) -> bool {
+        match self {
+            Condition::All { all } => all.iter().all(|c| c.eval(view)),
+            Condition::Any { any } => any.iter().any(|c| c.eval(view)),
+            Condition::Not { not } => !not.eval(view),
+            Condition::Compare { feature, op, value } => match lookup(view, feature) {
+                Some(actual) => compare(actual, *op, value),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Top-level `GlobalState` field as rules see it. Only what the Overmind has
+/// computed by the time the rules run (AI, operator and output stages come later).
+fn feature(state: &GlobalState, root: &str) -> Option<Value> {
+    let value = match root {
+        "state" => serde_json::to_value(state.state),
+        "phase" => serde_json::to_value(state.phase),
+        "genre" => serde_json::to_value(&state.genre),
+        "genre_confidence" => Ok(Value::from(state.genre_confidence)),
+        "genre_probabilities" => serde_json::to_value(&state.genre_probabilities),
+        "mood" => serde_json::to_value(&state.mood),
+        "bpm" => Ok(Value::from(state.bpm)),
+        "beat" => serde_json::to_value(state.beat),
+        "glitch_factor" => Ok(Value::from(state.glitch_factor)),
+        "params" => serde_json::to_value(&state.params),
+        "lfos" => serde_json::to_value(&state.lfos),
+        "low_energy" => Ok(Value::from(state.low_energy)),
+        "mid_energy" => Ok(Value::from(state.mid_energy)),
+        "high_energy" => Ok(Value::from(state.high_energy)),
+        "spectral_flux" => Ok(Value::from(state.spectral_flux)),
+        "energy_trend" => serde_json::to_value(state.energy_trend),
+        "energy_trends" => serde_json::to_value(state.energy_trends),
+        "last_transition" => serde_json::to_value(&state.last_transition),
+        "last_phase_transition" => serde_json::to_value(&state.last_phase_transition),
+        "smoothed" => serde_json::to_value(&state.smoothed),
+        "audio_meta" => serde_json::to_value(&state.audio_meta),
+        _ => return None,
+    };
+    value.ok()
+}
+
+fn lookup<'a>(view: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(view, |v, key| v.get(key))
+}
+
+fn crosses_null(view: &Value, path: &str) -> bool {
+    let mut v = view;
+    for key in path.split('.') {
+        match v.get(key) {
+            Some(Value::Null) => return true,
+            Some(next) => v = next,
+            None => return false,
+        }
+    }
+    false
+}
+
+fn compare(actual: &Value, op: Op, expected: &Value) -> bool {
+    if let (Some(a), Some(b)) = (actual.as_f64(), expected.as_f64()) {
+        return match op {
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+        };
+    }
+    match op {
+        Op::Eq => actual == expected,
+        Op::Ne => actual != expected,
+        _ => false,
+    }
+}
+
+fn validate_condition(
+    rule: &str,
+    cond: &Condition,
+    defaults: &GlobalState,
+    errors: &mut Vec<String>,
+) {
+    match cond {
+        Condition::All { all: list } | Condition::Any { any: list } => {
+            if list.is_empty() {
+                errors.push(format!("{}: empty all/any", rule));
+            }
+            for c in list {
+                validate_condition(rule, c, defaults, errors);
+            }
+        }
+        Condition::Not { not } => validate_condition(rule, not, defaults, errors),
+        Condition::Compare { feature: path, op, value } => {
+            // Parameters and modulators are named at runtime, so they cannot be checked here
+            if path.starts_with("params.") || path.starts_with("lfos.") {
+                return;
+            }
+            let root = path.split('.').next().unwrap_or_default();
+            let Some(field) = feature(defaults, root) else {
+                errors.push(format!("{}: unknown feature '{}'", rule, path));
+                return;
+            };
+            let shape = serde_json::json!({ root: field });
+            match lookup(&shape, path) {
+                // Optional sections (e.g. last_transition) are null until first set
+                None if crosses_null(&shape, path) => {}
+                None => errors.push(format!("{}: unknown feature '{}'", rule, path)),
+                Some(v) if v.is_object() || v.is_array() => {
+                    errors.push(format!("{}: '{}' is not a scalar feature", rule, path))
+                }
+                Some(v) => {
+                    let ordered = !matches!(op, Op::Eq | Op::Ne);
+                    if ordered && !(v.is_number() && value.is_number()) {
+                        errors.push(format!("{}: {:?} needs numeric '{}'", rule, op, path));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// --- Hot Reload ---
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleStatus {
+    pub path: Option<String>,
+    pub generation: u64,
+    pub last_error: Option<String>,
+    pub rules: RuleSet,
+}
+
+/// Shared, hot-swappable rule set. `generation` bumps on every successful load
+/// so the Overmind can cheaply notice new vibe thresholds.
+pub struct RuleEngine {
+    path: Option<PathBuf>,
+    current: RwLock<Arc<RuleSet>>,
+    generation: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl RuleEngine {
+    /// Uses `path` (the zone's rule file, `RULES_PATH` by default) if set,
+    /// otherwise the built-in defaults. A rule file that fails validation at
+    /// startup is reported and the defaults are used.
+    pub fn load(path: Option<PathBuf>) -> Arc<Self> {
+        let builtin = RuleSet::parse(BUILTIN_RULES).expect("Invalid built-in rules");
+        let engine = Arc::new(Self {
+            path,
+            current: RwLock::new(Arc::new(builtin)),
+            generation: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        });
+
+        if let Some(path) = &engine.path {
+            match engine.reload() {
+                Ok(()) => println!("📜 [Rules] Loaded {:?}", path),
+                Err(e) => {
+                    println!("⚠️ [Rules] {:?} rejected, using built-in rules: {}", path, e)
+                }
+            }
+        }
+        engine
+    }
+
+    pub fn snapshot(&self) -> (u64, Arc<RuleSet>) {
+        let rules = self.current.read().unwrap().clone();
+        (self.generation.load(Ordering::Acquire), rules)
+    }
+
+    pub fn status(&self) -> RuleStatus {
+        let (generation, rules) = self.snapshot();
+        RuleStatus {
+            path: self.path.as_ref().map(|p| p.display().to_string()),
+            generation,
+            last_error: self.last_error.lock().unwrap().clone(),
+            rules: (*rules).clone(),
+        }
+    }
+
+    /// Re-reads the rule file. On any error the running rules stay in place.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let path = self.path.as_ref().ok_or_else(|| anyhow!("RULES_PATH is not set"))?;
+        let result = std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| RuleSet::parse(&text));
+
+        match result {
+            Ok(set) => {
+                *self.current.write().unwrap() = Arc::new(set);
+                self.generation.fetch_add(1, Ordering::AcqRel);
+                *self.last_error.lock().unwrap() = None;
+                info!(event = "rules_loaded", path = ?path);
+                Ok(())
+            }
+            Err(e) => {
+                error!(event = "rules_rejected", path = ?path, error = %e);
+                *self.last_error.lock().unwrap() = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Polls the rule file's mtime and reloads on change.
+    pub fn spawn_hot_reload(self: &Arc<Self>) {
+        let Some(path) = self.path.clone() else { return };
+        let engine = self.clone();
+
+        tokio::spawn(async move {
+            let mtime = |p: &PathBuf| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+            let mut last_seen: Option<SystemTime> = mtime(&path);
+
+            loop {
+                tokio::time::sleep(RELOAD_POLL).await;
+                let current = mtime(&path);
+                if current.is_some() && current != last_seen {
+                    last_seen = current;
+                    if engine.reload().is_ok() {
+                        println!("🔄 [Rules] Hot-reloaded {:?}", path);
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"{ "rules": [
+        {
+            "name": "loud",
+            "when": { "feature": "low_energy", "op": ">", "value": 0.5 },
+            "set": { "glitch_factor": 0.7, "params": { "kick_flash": 2.0 } }
+        },
+        {
+            "name": "escalate",
+            "when": { "all": [
+                { "feature": "glitch_factor", "op": ">", "value": 0.5 },
+                { "feature": "state", "op": "==", "value": "Chill" }
+            ] },
+            "set": { "state": "Chaos" }
+        }
+    ] }"#;
+
+    #[test]
+    fn later_rules_see_earlier_outputs() {
+        let rules = RuleSet::parse(RULES).unwrap();
+        let mut state = GlobalState { low_energy: 0.8, ..Default::default() };
+        rules.apply(&mut state);
+
+        assert_eq!(state.glitch_factor, 0.7);
+        assert_eq!(state.state, VibeState::Chaos);
+        // Clamped to the declared range, as at load time
+        assert_eq!(state.params.get("kick_flash"), Some(&ParamValue::Number(1.0)));
+    }
+
+    #[test]
+    fn outputs_are_released_when_rules_stop_matching() {
+        let rules = RuleSet::parse(RULES).unwrap();
+        let mut state = GlobalState { low_energy: 0.8, ..Default::default() };
+        rules.apply(&mut state);
+
+        state.low_energy = 0.1;
+        state.state = VibeState::Build;
+        rules.apply(&mut state);
+        assert_eq!(state.glitch_factor, 0.0);
+        assert!(state.params.is_empty());
+        // `state` is the Overmind's own output; rules only override it
+        assert_eq!(state.state, VibeState::Build);
+    }
+
+    #[test]
+    fn conditions_combine() {
+        let cond: Condition = serde_json::from_str(
+            r#"{ "any": [
+                { "feature": "bpm", "op": ">=", "value": 170 },
+                { "not": { "feature": "genre", "op": "!=", "value": "Techno" } }
+            ] }"#,
+        )
+        .unwrap();
+        assert!(cond.eval(&serde_json::json!({ "bpm": 174.0, "genre": "House" })));
+        assert!(cond.eval(&serde_json::json!({ "bpm": 128.0, "genre": "Techno" })));
+        assert!(!cond.eval(&serde_json::json!({ "bpm": 128.0, "genre": "House" })));
+
+        // Comparisons on missing features never match
+        let cond: Condition =
+            serde_json::from_str(r#"{ "feature": "bpm", "op": "<", "value": 170 }"#).unwrap();
+        assert!(!cond.eval(&serde_json::json!({ "genre": "House" })));
+    }
+
+    #[test]
+    fn invalid_rule_files_are_rejected() {
+        let rule = |when: &str, set: &str| {
+            format!(r#"{{ "rules": [ {{ "name": "r", "when": {}, "set": {} }} ] }}"#, when, set)
+        };
+        let typo = r#"{ "feature": "smoothed.lowenergy", "op": ">", "value": 0.5 }"#;
+        let glitch = r#"{ "glitch_factor": 0.5 }"#;
+        assert!(RuleSet::parse(&rule(typo, glitch)).is_err());
+
+        let object = r#"{ "feature": "smoothed", "op": "==", "value": 1 }"#;
+        assert!(RuleSet::parse(&rule(object, glitch)).is_err());
+
+        let ordered_text = r#"{ "feature": "genre", "op": ">", "value": 1 }"#;
+        assert!(RuleSet::parse(&rule(ordered_text, glitch)).is_err());
+
+        // Set after the rules run, so never visible to them
+        let late = r#"{ "feature": "ai_theme", "op": "==", "value": "BOOT_SEQUENCE" }"#;
+        assert!(RuleSet::parse(&rule(late, glitch)).is_err());
+
+        // Modulators are named at runtime
+        let lfo = r#"{ "feature": "lfos.anything", "op": ">", "value": 0.5 }"#;
+        assert!(RuleSet::parse(&rule(lfo, glitch)).is_ok());
+
+        assert!(RuleSet::parse(&rule(lfo, "{}")).is_err());
+        assert!(RuleSet::parse(&rule(lfo, r#"{ "glitch_factor": 1.5 }"#)).is_err());
+    }
+
+    #[test]
+    fn builtin_rules_parse() {
+        let rules = RuleSet::parse(BUILTIN_RULES).unwrap();
+        assert_eq!(rules.roots, vec!["phase", "smoothed", "state"]);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::color::{lerp_hex, linear_rgb, parse_hex, relative_luminance, to_hex};
+use crate::lfo::LfoBank;
+use crate::params::{ParamSchema, ParamType, ParamValue};
+use crate::state_machine::GlobalState;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// WCAG 2.3.1 general flash: a luminance swing of 10% where the darker side is below 0.8
+const LUMINANCE_FLASH: f32 = 0.1;
+const LUMINANCE_BRIGHT: f32 = 0.8;
+// WCAG 2.3.1 red flash: (R - G - B) * 320 changes by more than 20 on a saturated red
+const RED_FLASH: f32 = 20.0;
+const RED_SATURATION: f64 = 0.8;
+// A glitch swing this large is treated like a general flash
+const GLITCH_FLASH: f32 = 0.2;
+const MAX_FRAME_GAP: f32 = 0.1;
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+const DEFAULT_MAX_FLASHES: f32 = 3.0;
+const DEFAULT_LUMINANCE_RATE: f32 = 2.0;
+const DEFAULT_GLITCH_SLEW: f32 = 4.0;
+
+#[derive(Debug, Clone)]
+pub struct SafetyConfig {
+    pub enabled: bool,
+    /// Flashes (pairs of opposing transitions) allowed in any one second.
+    pub max_flashes_per_sec: f32,
+    /// Largest relative luminance change per second of either palette colour.
+    pub max_luminance_per_sec: f32,
+    /// Largest `glitch_factor` change per second.
+    pub glitch_slew_per_sec: f32,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_flashes_per_sec: DEFAULT_MAX_FLASHES,
+            max_luminance_per_sec: DEFAULT_LUMINANCE_RATE,
+            glitch_slew_per_sec: DEFAULT_GLITCH_SLEW,
+        }
+    }
+}
+
+impl SafetyConfig {
+    /// On unless `SAFETY_LIMITER=off`. `SAFETY_MAX_FLASHES`, `SAFETY_MAX_LUMINANCE_RATE`
+    /// and `SAFETY_GLITCH_SLEW` tighten the defaults; looser values are accepted
+    /// but announced, so a relaxed venue setup is never silent.
+    pub fn from_env() -> Self {
+        let env = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<f32>().ok());
+        let defaults = Self::default();
+        let config = Self {
+            enabled: !std::env::var("SAFETY_LIMITER").is_ok_and(|v| v.eq_ignore_ascii_case("off")),
+            max_flashes_per_sec: env("SAFETY_MAX_FLASHES")
+                .filter(|v| *v >= 0.0)
+                .unwrap_or(defaults.max_flashes_per_sec),
+            max_luminance_per_sec: env("SAFETY_MAX_LUMINANCE_RATE")
+                .filter(|v| *v > 0.0)
+                .unwrap_or(defaults.max_luminance_per_sec),
+            glitch_slew_per_sec: env("SAFETY_GLITCH_SLEW")
+                .filter(|v| *v > 0.0)
+                .unwrap_or(defaults.glitch_slew_per_sec),
+        };
+
+        if !config.enabled {
+            println!("⚠️ [Safety] SAFETY_LIMITER=off. Flash limiting is DISABLED.");
+        } else if config.max_flashes_per_sec > defaults.max_flashes_per_sec
+            || config.max_luminance_per_sec > defaults.max_luminance_per_sec
+            || config.glitch_slew_per_sec > defaults.glitch_slew_per_sec
+        {
+            println!("⚠️ [Safety] Limits relaxed beyond the defaults: {:?}", config);
+        }
+        config
+    }
+}
+
+/// Counts transitions of one signal the way WCAG counts flashes: a transition
+/// is a swing of at least `threshold` against the previous extreme.
+struct FlashGuard {
+    threshold: f32,
+    bright: Option<f32>, // Swings entirely above this level do not count
+    extreme: Option<f32>,
+    direction: f32,
+    transitions: VecDeque<Instant>,
+}
+
+impl FlashGuard {
+    fn new(threshold: f32, bright: Option<f32>) -> Self {
+        Self { threshold, bright, extreme: None, direction: 0.0, transitions: VecDeque::new() }
+    }
+
+    fn transition(&self, value: f32) -> bool {
+        let Some(extreme) = self.extreme else { return false };
+        let delta = value - extreme;
+        delta * self.direction <= 0.0
+            && delta.abs() >= self.threshold
+            && self.bright.is_none_or(|b| extreme.min(value) < b)
+    }
+
+    fn allows(&mut self, now: Instant, value: f32, max_transitions: usize) -> bool {
+        while self.transitions.front().is_some_and(|t| now - *t > Duration::from_secs(1)) {
+            self.transitions.pop_front();
+        }
+        !self.transition(value) || self.transitions.len() < max_transitions
+    }
+
+    fn record(&mut self, now: Instant, value: f32) {
+        let Some(extreme) = self.extreme else {
+            self.extreme = Some(value);
+            return;
+        };
+        let delta = value - extreme;
+        if self.transition(value) {
+            self.transitions.push_back(now);
+            self.direction = delta.signum();
+            self.extreme = Some(value);
+        } else if delta * self.direction > 0.0 {
+            self.extreme = Some(value); // Still moving the same way
+        }
+    }
+}
+
+/// A level the frontend may turn into brightness: slew-limited and
+/// flash-counted on `0..1` of its range, like `glitch_factor`.
+struct LevelChannel {
+    shown: Option<f32>,
+    guard: FlashGuard,
+}
+
+impl LevelChannel {
+    fn new(shown: Option<f32>) -> Self {
+        Self { shown, guard: FlashGuard::new(GLITCH_FLASH, None) }
+    }
+
+    fn limit(&mut self, now: Instant, target: f32, step: f32, max_transitions: usize) -> f32 {
+        let Some(shown) = self.shown else {
+            self.guard.record(now, target);
+            self.shown = Some(target);
+            return target;
+        };
+        let mut next = shown + (target - shown).clamp(-step, step);
+        if self.guard.allows(now, next, max_transitions) {
+            self.guard.record(now, next);
+        } else {
+            next = shown;
+        }
+        self.shown = Some(next);
+        next
+    }
+}
+
+struct ColorChannel {
+    shown: Option<[u8; 3]>,
+    luminance: FlashGuard,
+    red: FlashGuard,
+}
+
+impl ColorChannel {
+    fn new() -> Self {
+        Self {
+            shown: None,
+            luminance: FlashGuard::new(LUMINANCE_FLASH, Some(LUMINANCE_BRIGHT)),
+            red: FlashGuard::new(RED_FLASH, None),
+        }
+    }
+
+    /// Limits `field` in place; returns whether it was changed.
+    fn limit(
+        &mut self,
+        now: Instant,
+        field: &mut String,
+        max_luminance_step: f64,
+        max_transitions: usize,
+    ) -> bool {
+        let Some(target) = parse_hex(field) else { return false };
+        let Some(shown) = self.shown else {
+            self.luminance.record(now, relative_luminance(target) as f32);
+            self.red.record(now, red_level(target));
+            self.shown = Some(target);
+            return false;
+        };
+
+        let mut next = target;
+        let change = (relative_luminance(target) - relative_luminance(shown)).abs();
+        if change > max_luminance_step {
+            let t = (max_luminance_step / change) as f32;
+            next = parse_hex(&lerp_hex(&to_hex(shown), field, t)).unwrap_or(shown);
+        }
+        let luminance = relative_luminance(next) as f32;
+        let red = red_level(next);
+        if self.luminance.allows(now, luminance, max_transitions)
+            && self.red.allows(now, red, max_transitions)
+        {
+            self.luminance.record(now, luminance);
+            self.red.record(now, red);
+        } else {
+            next = shown;
+        }
+
+        self.shown = Some(next);
+        if next != target {
+            *field = to_hex(next);
+            return true;
+        }
+        false
+    }
+}
+
+/// An on/off signal where every toggle is a full swing (blackout, bool params).
+struct ToggleChannel {
+    shown: Option<bool>,
+    guard: FlashGuard,
+}
+
+impl ToggleChannel {
+    fn new() -> Self {
+        Self { shown: None, guard: FlashGuard::new(0.5, None) }
+    }
+
+    /// Limits `value` in place; returns whether it was held back.
+    fn limit(&mut self, now: Instant, value: &mut bool, max_transitions: usize) -> bool {
+        let level = |on: bool| if on { 1.0 } else { 0.0 };
+        let mut held = false;
+        match self.shown {
+            Some(shown) if shown != *value => {
+                if self.guard.allows(now, level(*value), max_transitions) {
+                    self.guard.record(now, level(*value));
+                } else {
+                    *value = shown;
+                    held = true;
+                }
+            }
+            None => self.guard.record(now, level(*value)),
+            _ => {}
+        }
+        self.shown = Some(*value);
+        held
+    }
+}
+
+/// Trigger counts: every firing is a flash (on, then off again).
+#[derive(Default)]
+struct PulseChannel {
+    shown: Option<f32>,
+    fired: VecDeque<Instant>,
+}
+
+impl PulseChannel {
+    fn limit(&mut self, now: Instant, count: &mut f32, max_flashes: usize) -> bool {
+        while self.fired.front().is_some_and(|t| now - *t > Duration::from_secs(1)) {
+            self.fired.pop_front();
+        }
+        let shown = *self.shown.get_or_insert(*count);
+        if *count <= shown {
+            self.shown = Some(*count);
+            return false;
+        }
+        if self.fired.len() < max_flashes {
+            self.fired.push_back(now);
+            self.shown = Some(shown + 1.0);
+        }
+        let held = self.shown != Some(*count);
+        *count = self.shown.unwrap_or(*count);
+        held
+    }
+}
+
+/// Last stage before a frame is broadcast: limits flash rate, luminance
+/// change and red flicker of everything a client renders, whichever stage
+/// (audio, rules, scripts, AI, scenes, operator) caused it. Parameters and
+/// modulators declared with `safety` are limited like `glitch_factor` (levels),
+/// the palette (colours) or `blackout` (bools); triggers fire at the flash rate.
+pub struct SafetyLimiter {
+    config: SafetyConfig,
+    lfos: Option<Arc<LfoBank>>,
+    last_frame: Option<Instant>,
+    glitch: LevelChannel,
+    colors: [ColorChannel; 2],
+    blackout: ToggleChannel,
+    levels: HashMap<String, LevelChannel>,
+    param_colors: HashMap<String, ColorChannel>,
+    toggles: HashMap<String, ToggleChannel>,
+    pulses: HashMap<String, PulseChannel>,
+    interventions: HashMap<String, (Instant, u64)>, // Last log, frames limited since
+}
+
+impl SafetyLimiter {
+    pub fn new(config: SafetyConfig) -> Self {
+        Self {
+            config,
+            lfos: None,
+            last_frame: None,
+            glitch: LevelChannel::new(Some(0.0)),
+            colors: [ColorChannel::new(), ColorChannel::new()],
+            blackout: ToggleChannel::new(),
+            levels: HashMap::new(),
+            param_colors: HashMap::new(),
+            toggles: HashMap::new(),
+            pulses: HashMap::new(),
+            interventions: HashMap::new(),
+        }
+    }
+
+    /// Also limits the bank's modulators that are declared with `safety`.
+    pub fn with_lfos(mut self, lfos: Arc<LfoBank>) -> Self {
+        self.lfos = Some(lfos);
+        self
+    }
+
+    pub fn apply(&mut self, state: &mut GlobalState) {
+        if !self.config.enabled {
+            return;
+        }
+        let now = Instant::now();
+        let dt = self.last_frame.map_or(0.0, |t| (now - t).as_secs_f32()).min(MAX_FRAME_GAP);
+        self.last_frame = Some(now);
+        let max_transitions = (self.config.max_flashes_per_sec * 2.0).floor() as usize;
+        let step = self.config.glitch_slew_per_sec * dt;
+        let mut limited = Vec::new();
+
+        // Glitch: slew-limited, then flash-counted
+        let glitch = self.glitch.limit(now, state.glitch_factor, step, max_transitions);
+        if (glitch - state.glitch_factor).abs() > f32::EPSILON {
+            limited.push("glitch_factor".to_string());
+        }
+        state.glitch_factor = glitch;
+
+        let max_luminance_step = (self.config.max_luminance_per_sec * dt) as f64;
+        let fields = [&mut state.ai_primary_color, &mut state.ai_secondary_color];
+        for ((channel, field), name) in
+            self.colors.iter_mut().zip(fields).zip(["ai_primary_color", "ai_secondary_color"])
+        {
+            if channel.limit(now, field, max_luminance_step, max_transitions) {
+                limited.push(name.to_string());
+            }
+        }
+
+        // Blackout toggles are full-screen flashes
+        if self.blackout.limit(now, &mut state.blackout, max_transitions) {
+            limited.push("blackout".to_string());
+        }
+
+        // Flash-capable parameters, e.g. the built-in kick_flash
+        let max_flashes = self.config.max_flashes_per_sec.floor() as usize;
+        for def in ParamSchema::global().params.iter().filter(|d| d.safety) {
+            let Some(value) = state.params.get_mut(&def.name) else { continue };
+            let held = match (def.kind, value) {
+                (ParamType::Float, ParamValue::Number(v)) => {
+                    let (min, max) = (def.min.unwrap_or(0.0), def.max.unwrap_or(1.0));
+                    let channel = self.levels.entry(format!("params.{}", def.name));
+                    let channel = channel.or_insert_with(|| LevelChannel::new(None));
+                    limit_in_range(channel, now, v, min, max, step, max_transitions)
+                }
+                (ParamType::Color, ParamValue::Color(c)) => {
+                    let channel = self.param_colors.entry(def.name.clone());
+                    let channel = channel.or_insert_with(ColorChannel::new);
+                    channel.limit(now, c, max_luminance_step, max_transitions)
+                }
+                (ParamType::Bool, ParamValue::Bool(b)) => {
+                    let channel = self.toggles.entry(def.name.clone());
+                    channel.or_insert_with(ToggleChannel::new).limit(now, b, max_transitions)
+                }
+                (ParamType::Trigger, ParamValue::Number(count)) => {
+                    let channel = self.pulses.entry(def.name.clone()).or_default();
+                    channel.limit(now, count, max_flashes)
+                }
+                _ => false,
+            };
+            if held {
+                limited.push(format!("params.{}", def.name));
+            }
+        }
+
+        // Modulators bound to brightness, e.g. a square LFO on a strobe
+        if let Some(bank) = &self.lfos {
+            for lfo in bank.snapshot().lfos.iter().filter(|l| l.safety) {
+                let Some(v) = state.lfos.get_mut(&lfo.name) else { continue };
+                let channel = self.levels.entry(format!("lfos.{}", lfo.name));
+                let channel = channel.or_insert_with(|| LevelChannel::new(None));
+                if limit_in_range(channel, now, v, lfo.min, lfo.max, step, max_transitions) {
+                    limited.push(format!("lfos.{}", lfo.name));
+                }
+            }
+        }
+
+        state.safety_limited = !limited.is_empty();
+        for channel in limited {
+            let never = now.checked_sub(LOG_INTERVAL).unwrap_or(now);
+            let (logged, frames) = self.interventions.entry(channel.clone()).or_insert((never, 0));
+            *frames += 1;
+            if now - *logged >= LOG_INTERVAL {
+                warn!(event = "safety_limited", channel = %channel, frames = *frames);
+                *logged = now;
+                *frames = 0;
+            }
+        }
+    }
+}
+
+/// Limits `value` on `0..1` of `min..max`; returns whether it was changed.
+fn limit_in_range(
+    channel: &mut LevelChannel,
+    now: Instant,
+    value: &mut f32,
+    min: f32,
+    max: f32,
+    step: f32,
+    max_transitions: usize,
+) -> bool {
+    let span = (max - min).abs().max(f32::EPSILON);
+    let target = (*value - min) / span;
+    let shown = min + channel.limit(now, target, step, max_transitions) * span;
+    let changed = (shown - *value).abs() > span * 1e-4;
+    *value = shown;
+    changed
+}
+
+/// WCAG red flash level: `(R - G - B) * 320` for saturated reds, otherwise 0.
+fn red_level(rgb: [u8; 3]) -> f32 {
+    let [r, g, b] = linear_rgb(rgb);
+    if r + g + b <= 0.0 || r / (r + g + b) < RED_SATURATION {
+        return 0.0;
+    }
+    ((r - g - b) * 320.0).max(0.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glitch_jumps_are_slewed() {
+        let mut safety = SafetyLimiter::new(SafetyConfig::default());
+        let mut state = GlobalState::default();
+        safety.apply(&mut state);
+
+        // At most one capped frame gap (0.1 s at 4.0 per second) has passed
+        state.glitch_factor = 1.0;
+        safety.apply(&mut state);
+        assert!(state.glitch_factor <= 0.4 + f32::EPSILON);
+        assert!(state.safety_limited);
+    }
+
+    #[test]
+    fn blackout_toggles_are_capped_per_second() {
+        let mut safety = SafetyLimiter::new(SafetyConfig::default());
+        let mut state = GlobalState::default();
+        safety.apply(&mut state);
+
+        let mut shown = state.blackout;
+        let (mut changes, mut held) = (0, 0);
+        for i in 1..=20 {
+            state.blackout = i % 2 == 1;
+            safety.apply(&mut state);
+            if state.blackout != shown {
+                changes += 1;
+            }
+            if state.safety_limited {
+                held += 1;
+            }
+            shown = state.blackout;
+        }
+        // 3 flashes are 6 transitions
+        assert!(changes <= 6, "{} toggles got through", changes);
+        assert!(held > 0);
+    }
+
+    #[test]
+    fn disabled_limiter_passes_frames_through() {
+        let config = SafetyConfig { enabled: false, ..Default::default() };
+        let mut safety = SafetyLimiter::new(config);
+        let mut state = GlobalState::default();
+        safety.apply(&mut state);
+        state.glitch_factor = 1.0;
+        state.blackout = true;
+        safety.apply(&mut state);
+        assert_eq!(state.glitch_factor, 1.0);
+        assert!(state.blackout);
+        assert!(!state.safety_limited);
+    }
+
+    #[test]
+    fn safety_params_are_slewed() {
+        // The built-in kick_flash is declared with `safety`
+        let mut safety = SafetyLimiter::new(SafetyConfig::default());
+        let mut state = GlobalState::default();
+        state.params.insert("kick_flash".to_string(), ParamValue::Number(0.0));
+        safety.apply(&mut state);
+
+        state.params.insert("kick_flash".to_string(), ParamValue::Number(1.0));
+        safety.apply(&mut state);
+        match state.params.get("kick_flash") {
+            Some(ParamValue::Number(v)) => assert!(*v <= 0.4 + f32::EPSILON),
+            other => panic!("unexpected kick_flash {:?}", other),
+        }
+        assert!(state.safety_limited);
+    }
+
+    #[test]
+    fn trigger_firings_are_held_back_to_the_flash_rate() {
+        let mut pulse = PulseChannel::default();
+        let now = Instant::now();
+        let mut count = 0.0;
+        assert!(!pulse.limit(now, &mut count, 3));
+
+        let published: Vec<f32> = (1..=5)
+            .map(|n| {
+                let mut count = n as f32;
+                pulse.limit(now, &mut count, 3);
+                count
+            })
+            .collect();
+        assert_eq!(published, vec![1.0, 2.0, 3.0, 3.0, 3.0]);
+
+        // Held firings are published once the window has passed
+        let mut count = 5.0;
+        assert!(pulse.limit(now + Duration::from_millis(1500), &mut count, 3));
+        assert_eq!(count, 4.0);
+    }
+}
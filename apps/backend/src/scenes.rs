@@ -0,0 +1,472 @@
+use crate::color::Palette;
+use crate::params::{ParamSchema, ParamType, ParamValue};
+use crate::state_machine::{GlobalState, VibePhase, VibeState};
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// A saved look. Unset fields follow the live state, so a scene can pin just
+/// the palette and leave glitch to the rules.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default)]
+    pub palette: Option<Palette>,
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub directive: Option<String>,
+    #[serde(default)]
+    pub glitch_factor: Option<f32>,
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamValue>,
+    /// Fade-in time when the scene becomes active.
+    #[serde(default)]
+    pub crossfade_ms: u64,
+}
+
+/// Activates `scene` while every given condition matches. First match wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneBinding {
+    pub scene: String,
+    #[serde(default)]
+    pub vibe: Option<VibeState>,
+    #[serde(default)]
+    pub phase: Option<VibePhase>,
+    #[serde(default)]
+    pub genre: Option<String>,
+}
+
+impl SceneBinding {
+    fn matches(&self, state: &GlobalState) -> bool {
+        self.vibe.is_none_or(|v| v == state.state)
+            && self.phase.is_none_or(|p| p == state.phase)
+            && self.genre.as_ref().is_none_or(|g| g.eq_ignore_ascii_case(state.genre.as_str()))
+    }
+}
+
+/// Everything persisted to `SCENES_PATH`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneBank {
+    #[serde(default)]
+    pub scenes: BTreeMap<String, Scene>,
+    #[serde(default)]
+    pub bindings: Vec<SceneBinding>,
+}
+
+impl SceneBank {
+    fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+        for (name, scene) in &self.scenes {
+            if name.trim().is_empty() {
+                errors.push("scene with empty name".to_string());
+            }
+            if let Some(color) = scene.palette.as_ref().and_then(|p| p.invalid_color()) {
+                errors.push(format!("{}: '{}' is not a #RRGGBB colour", name, color));
+            }
+            if scene.glitch_factor.is_some_and(|g| !(0.0..=1.0).contains(&g)) {
+                errors.push(format!("{}: glitch_factor outside 0..1", name));
+            }
+            for (param, value) in &scene.params {
+                if let Err(e) = ParamSchema::global().check(param, value.clone()) {
+                    errors.push(format!("{}: {}", name, e));
+                }
+            }
+        }
+        for b in &self.bindings {
+            if !self.scenes.contains_key(&b.scene) {
+                errors.push(format!("binding refers to unknown scene '{}'", b.scene));
+            }
+            if b.vibe.is_none() && b.phase.is_none() && b.genre.is_none() {
+                errors.push(format!("binding for '{}' has no condition", b.scene));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!(errors.join("; "))
+        }
+    }
+}
+
+/// Published as `GlobalState.scene` while a scene is showing or fading.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActiveScene {
+    /// `None` while fading back to the live look.
+    pub name: Option<String>,
+    /// "manual" (recalled) or "binding".
+    pub source: String,
+    /// Crossfade progress, 1.0 once complete.
+    pub fade: f32,
+}
+
+/// The scene-controllable slice of `GlobalState`.
+#[derive(Debug, Clone)]
+struct Look {
+    palette: Palette,
+    theme: String,
+    directive: String,
+    glitch_factor: f32,
+    params: BTreeMap<String, ParamValue>,
+}
+
+impl Look {
+    fn of(state: &GlobalState) -> Self {
+        Self {
+            palette: Palette {
+                primary: state.ai_primary_color.clone(),
+                secondary: state.ai_secondary_color.clone(),
+            },
+            theme: state.ai_theme.clone(),
+            directive: state.ai_directive.clone(),
+            glitch_factor: state.glitch_factor,
+            params: state.params.clone(),
+        }
+    }
+
+    fn with(mut self, scene: &Scene) -> Self {
+        if let Some(p) = &scene.palette {
+            self.palette = p.clone();
+        }
+        if let Some(t) = &scene.theme {
+            self.theme = t.clone();
+        }
+        if let Some(d) = &scene.directive {
+            self.directive = d.clone();
+        }
+        if let Some(g) = scene.glitch_factor {
+            self.glitch_factor = g;
+        }
+        self.params.extend(scene.params.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self
+    }
+
+    /// Colours and numbers interpolate; text switches at the midpoint. Numeric
+    /// params missing on one side fade from/to zero.
+    fn blend(&self, to: &Look, t: f32) -> Look {
+        let pick = |a: &String, b: &String| if t < 0.5 { a.clone() } else { b.clone() };
+        let mut params = BTreeMap::new();
+        let zero = ParamValue::Number(0.0);
+        for name in self.params.keys().chain(to.params.keys()) {
+            let value = match (self.params.get(name), to.params.get(name)) {
+                (Some(a), Some(b)) => Some(a.lerp(b, t)),
+                (Some(a @ ParamValue::Number(_)), None) => Some(a.lerp(&zero, t)),
+                (None, Some(b @ ParamValue::Number(_))) => Some(zero.lerp(b, t)),
+                (Some(a), None) => (t < 0.5).then(|| a.clone()),
+                (None, Some(b)) => (t >= 0.5).then(|| b.clone()),
+                (None, None) => None,
+            };
+            if let Some(value) = value {
+                params.insert(name.clone(), value);
+            }
+        }
+        Look {
+            palette: self.palette.lerp(&to.palette, t),
+            theme: pick(&self.theme, &to.theme),
+            directive: pick(&self.directive, &to.directive),
+            glitch_factor: self.glitch_factor + (to.glitch_factor - self.glitch_factor) * t,
+            params,
+        }
+    }
+
+    fn write(self, state: &mut GlobalState) {
+        state.ai_primary_color = self.palette.primary;
+        state.ai_secondary_color = self.palette.secondary;
+        state.ai_theme = self.theme;
+        state.ai_directive = self.directive;
+        state.glitch_factor = self.glitch_factor;
+        state.params = self.params;
+    }
+}
+
+struct Fade {
+    from: Look,
+    started: Instant,
+    duration: Duration,
+}
+
+struct Inner {
+    bank: SceneBank,
+    manual: Option<String>,
+    manual_crossfade: Option<u64>,
+    current: Option<String>,
+    source: &'static str,
+    fade: Option<Fade>,
+    shown: Option<Look>, // Last look written, the starting point of the next fade
+}
+
+/// Named presets with crossfaded recall and vibe/phase/genre bindings,
+/// persisted as JSON to the zone's scene file (`SCENES_PATH`, default `scenes.json`).
+pub struct SceneEngine {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+    saving: Mutex<()>, // Serialises edits so the file write can happen outside `inner`
+}
+
+impl SceneEngine {
+    pub fn load(path: PathBuf) -> Self {
+        let bank = match std::fs::read_to_string(&path) {
+            Err(_) => SceneBank::default(),
+            Ok(text) => match serde_json::from_str::<SceneBank>(&text)
+                .map_err(anyhow::Error::from)
+                .and_then(|bank| bank.validate().map(|_| bank))
+            {
+                Ok(bank) => {
+                    println!("🎬 [Scenes] Loaded {} scene(s) from {:?}", bank.scenes.len(), path);
+                    bank
+                }
+                Err(e) => {
+                    // Keep the broken file for inspection instead of overwriting it on next save
+                    let aside = path.with_extension("json.invalid");
+                    let _ = std::fs::rename(&path, &aside);
+                    println!("⚠️ [Scenes] {:?} rejected ({}), moved to {:?}", path, e, aside);
+                    SceneBank::default()
+                }
+            },
+        };
+
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                bank,
+                manual: None,
+                manual_crossfade: None,
+                current: None,
+                source: "binding",
+                fade: None,
+                shown: None,
+            }),
+            saving: Mutex::new(()),
+        }
+    }
+
+    pub fn bank(&self) -> SceneBank {
+        self.inner.lock().unwrap().bank.clone()
+    }
+
+    /// Stores `scene` under `name`, replacing any previous version.
+    pub fn save(&self, name: &str, scene: Scene) -> anyhow::Result<()> {
+        self.mutate(|bank| {
+            bank.scenes.insert(name.to_string(), scene);
+            Ok(())
+        })
+    }
+
+    /// Saves what is on screen right now as `name`.
+    pub fn capture(&self, name: &str, crossfade_ms: u64) -> anyhow::Result<Scene> {
+        let look = self.inner.lock().unwrap().shown.clone();
+        let look = look.ok_or_else(|| anyhow!("no frame has been published yet"))?;
+        let scene = Scene {
+            palette: Some(look.palette),
+            theme: Some(look.theme),
+            directive: Some(look.directive),
+            glitch_factor: Some(look.glitch_factor),
+            // Trigger writes are one-frame events, not part of a look
+            params: look
+                .params
+                .into_iter()
+                .filter(|(name, _)| {
+                    ParamSchema::global().get(name).is_none_or(|d| d.kind != ParamType::Trigger)
+                })
+                .collect(),
+            crossfade_ms,
+        };
+        self.save(name, scene.clone())?;
+        Ok(scene)
+    }
+
+    /// Deletes the scene and any bindings to it. Returns false if it did not exist.
+    pub fn delete(&self, name: &str) -> anyhow::Result<bool> {
+        let mut found = false;
+        self.mutate(|bank| {
+            found = bank.scenes.remove(name).is_some();
+            bank.bindings.retain(|b| b.scene != name);
+            Ok(())
+        })?;
+        Ok(found)
+    }
+
+    pub fn set_bindings(&self, bindings: Vec<SceneBinding>) -> anyhow::Result<()> {
+        self.mutate(|bank| {
+            bank.bindings = bindings;
+            Ok(())
+        })
+    }
+
+    /// Shows `name` until released, fading over `crossfade_ms` or the scene's own time.
+    pub fn recall(&self, name: &str, crossfade_ms: Option<u64>) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.bank.scenes.contains_key(name) {
+            bail!("unknown scene '{}'", name);
+        }
+        inner.manual = Some(name.to_string());
+        inner.manual_crossfade = crossfade_ms;
+        info!(event = "scene_recalled", scene = %name);
+        Ok(())
+    }
+
+    /// Ends a manual recall; bindings (or the live look) take over again.
+    pub fn release(&self, crossfade_ms: Option<u64>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.manual = None;
+        inner.manual_crossfade = crossfade_ms;
+    }
+
+    pub fn active(&self) -> Option<String> {
+        self.inner.lock().unwrap().current.clone()
+    }
+
+    /// Validates, persists and only then swaps in the edited bank. The disk write
+    /// runs without `inner` held, so `apply` on the pipeline never waits for it.
+    fn mutate(
+        &self,
+        edit: impl FnOnce(&mut SceneBank) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let _saving = self.saving.lock().unwrap();
+        let mut bank = self.bank();
+        edit(&mut bank)?;
+        bank.validate()?;
+
+        let text = serde_json::to_string_pretty(&bank)?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, text).and_then(|_| std::fs::rename(&tmp, &self.path)).map_err(
+            |e| {
+                error!(event = "scenes_save_failed", path = ?self.path, error = %e);
+                anyhow!("could not save {:?}: {}", self.path, e)
+            },
+        )?;
+
+        self.inner.lock().unwrap().bank = bank;
+        Ok(())
+    }
+
+    pub fn apply(&self, state: &mut GlobalState) {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        // Only for the change it was requested with, never a later one
+        let requested_ms = inner.manual_crossfade.take();
+
+        let (target, source) = match &inner.manual {
+            Some(name) => (Some(name.clone()), "manual"),
+            None => {
+                let bound = inner.bank.bindings.iter().find(|b| b.matches(state));
+                (bound.map(|b| b.scene.clone()), "binding")
+            }
+        };
+        // A scene deleted while showing fades back to live
+        let target = target.filter(|name| inner.bank.scenes.contains_key(name));
+
+        if target != inner.current {
+            let incoming = target.as_ref().and_then(|n| inner.bank.scenes.get(n));
+            let outgoing = inner.current.as_ref().and_then(|n| inner.bank.scenes.get(n));
+            let ms = requested_ms
+                .unwrap_or_else(|| incoming.or(outgoing).map(|s| s.crossfade_ms).unwrap_or(0));
+            inner.fade = inner.shown.clone().map(|from| Fade {
+                from,
+                started: Instant::now(),
+                duration: Duration::from_millis(ms),
+            });
+            info!(event = "scene_changed", from = ?inner.current, to = ?target);
+            inner.current = target;
+            inner.source = source;
+        }
+
+        let live = Look::of(state);
+        let goal = match inner.current.as_ref().and_then(|n| inner.bank.scenes.get(n)) {
+            Some(scene) => live.with(scene),
+            None => live,
+        };
+
+        let mut progress = 1.0;
+        let look = match &inner.fade {
+            Some(f) if f.started.elapsed() < f.duration => {
+                progress = f.started.elapsed().as_secs_f32() / f.duration.as_secs_f32();
+                f.from.blend(&goal, progress)
+            }
+            _ => {
+                inner.fade = None;
+                goal
+            }
+        };
+
+        inner.shown = Some(look.clone());
+        look.write(state);
+        state.scene = (inner.current.is_some() || inner.fade.is_some()).then(|| ActiveScene {
+            name: inner.current.clone(),
+            source: inner.source.to_string(),
+            fade: progress,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An engine with scene "slow" (10 s fade), bound to the Chaos vibe.
+    fn engine(test: &str) -> SceneEngine {
+        let path = std::env::temp_dir().join(format!("scenes-test-{}.json", test));
+        let _ = std::fs::remove_file(&path);
+        let engine = SceneEngine::load(path);
+        let slow =
+            Scene { theme: Some("SLOW".to_string()), crossfade_ms: 10_000, ..Scene::default() };
+        engine.save("slow", slow).unwrap();
+        let binding = SceneBinding {
+            scene: "slow".to_string(),
+            vibe: Some(VibeState::Chaos),
+            phase: None,
+            genre: None,
+        };
+        engine.set_bindings(vec![binding]).unwrap();
+        engine
+    }
+
+    fn frame(engine: &SceneEngine, vibe: VibeState) -> GlobalState {
+        let mut state = GlobalState { state: vibe, ..GlobalState::default() };
+        engine.apply(&mut state);
+        state
+    }
+
+    #[test]
+    fn recall_fades_over_the_requested_time() {
+        let engine = engine("requested");
+        frame(&engine, VibeState::Chill);
+        engine.recall("slow", Some(0)).unwrap();
+        let state = frame(&engine, VibeState::Chill);
+        assert_eq!(state.ai_theme, "SLOW");
+        assert_eq!(state.scene.as_ref().map(|s| s.source.as_str()), Some("manual"));
+
+        engine.release(None);
+        let state = frame(&engine, VibeState::Chill);
+        assert!(state.scene.is_some_and(|s| s.name.is_none() && s.fade < 1.0));
+    }
+
+    #[test]
+    fn an_unused_crossfade_does_not_carry_over() {
+        let engine = engine("stale");
+        frame(&engine, VibeState::Chill);
+        assert!(frame(&engine, VibeState::Chaos).scene.is_some_and(|s| s.fade < 1.0));
+
+        // Already showing: nothing changes, so the 0 ms must not linger
+        engine.recall("slow", Some(0)).unwrap();
+        frame(&engine, VibeState::Chaos);
+        assert_eq!(engine.inner.lock().unwrap().manual_crossfade, None);
+
+        engine.release(None);
+        let state = frame(&engine, VibeState::Chill);
+        assert!(state.scene.is_some_and(|s| s.name.is_none() && s.fade < 1.0));
+    }
+
+    #[test]
+    fn unknown_scenes_are_refused() {
+        let engine = engine("unknown");
+        assert!(engine.recall("missing", None).is_err());
+        assert_eq!(frame(&engine, VibeState::Chill).scene, None);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::audio_engine::AudioFeatures;
+use crate::params::{ParamSchema, ParamValue};
+use crate::state_machine::{GlobalState, PipelineEvent};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_FAILURES: u32 = 5;
+
+enum ScriptCommand {
+    SetParam { name: String, value: ParamValue, hold_ms: Option<i64> },
+    Fire { name: String },
+    SetGlitch { value: f32, hold_ms: i64 },
+    Emit { name: String, data: serde_json::Value },
+}
+
+/// What the watcher thread found in the script directory.
+struct Scan {
+    paths: Vec<PathBuf>,                             // Every script file, sorted
+    changed: Vec<(PathBuf, Result<String, String>)>, // New or saved, with source
+}
+
+struct Script {
+    name: String,
+    path: PathBuf,
+    ast: AST,
+    scope: Scope<'static>,
+    failures: u32,
+}
+
+impl Script {
+    fn disabled(&self) -> bool {
+        self.failures >= MAX_FAILURES
+    }
+}
+
+/// Runs the `*.rhai` files in the zone's script directory (`SCRIPTS_DIR` by
+/// default) once per Overmind update, after the rule engine. Each run sees
+/// `features`, `state`, `time_ms` and a persistent `mem` map, and talks back
+/// through `set_param`, `fire`, `set_glitch` and `emit`.
+/// Scripts are bounded by an operation count, a wall-clock budget and size
+/// limits; one that keeps failing is disabled until its file changes.
+pub struct ScriptHost {
+    scans: Option<Receiver<Scan>>, // From the watcher thread; None without a directory
+    engine: Engine,
+    scripts: Vec<Script>,
+    commands: Arc<Mutex<Vec<ScriptCommand>>>,
+    deadline: Arc<Mutex<Option<Instant>>>,
+    budget: Duration,
+    // Script-owned outputs, re-applied every frame until they expire (clock time)
+    params: BTreeMap<String, (ParamValue, Option<Duration>)>,
+    glitch_hold: Option<(f32, Duration)>,
+}
+
+impl ScriptHost {
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let num = |key: &str, default: u64| {
+            std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+        let budget = Duration::from_micros(num("SCRIPT_BUDGET_US", 2000));
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let deadline: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+
+        let mut engine = Engine::new();
+        // No `import`: scripts must not load files from disk
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine
+            .set_max_operations(num("SCRIPT_MAX_OPERATIONS", 50_000))
+            .set_max_call_levels(16)
+            .set_max_expr_depths(32, 32)
+            .set_max_string_size(4096)
+            .set_max_array_size(1024)
+            .set_max_map_size(256);
+
+        let progress_deadline = deadline.clone();
+        engine.on_progress(move |ops| {
+            // Checking the time on every operation would dominate cheap scripts
+            if ops % 256 != 0 {
+                return None;
+            }
+            let over = progress_deadline.lock().unwrap().is_some_and(|d| Instant::now() > d);
+            if over {
+                Some(Dynamic::from("time budget exceeded"))
+            } else {
+                None
+            }
+        });
+        engine.on_print(|text| info!(event = "script_print", text = %text));
+        engine.on_debug(|text, _, _| info!(event = "script_debug", text = %text));
+
+        // Floats, colour strings and bools, checked against the parameter schema
+        let cmds = commands.clone();
+        engine.register_fn(
+            "set_param",
+            move |name: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                let value = param_value(name, value)?;
+                let cmd = ScriptCommand::SetParam { name: name.to_string(), value, hold_ms: None };
+                cmds.lock().unwrap().push(cmd);
+                Ok(())
+            },
+        );
+        let cmds = commands.clone();
+        engine.register_fn(
+            "set_param",
+            move |name: &str, value: Dynamic, hold_ms: i64| -> Result<(), Box<EvalAltResult>> {
+                let value = param_value(name, value)?;
+                let hold_ms = Some(hold_ms);
+                let cmd = ScriptCommand::SetParam { name: name.to_string(), value, hold_ms };
+                cmds.lock().unwrap().push(cmd);
+                Ok(())
+            },
+        );
+        let cmds = commands.clone();
+        engine.register_fn("fire", move |name: &str| -> Result<(), Box<EvalAltResult>> {
+            param_value(name, Dynamic::TRUE)?;
+            cmds.lock().unwrap().push(ScriptCommand::Fire { name: name.to_string() });
+            Ok(())
+        });
+        let cmds = commands.clone();
+        engine.register_fn("set_glitch", move |value: f64, hold_ms: i64| {
+            let cmd = ScriptCommand::SetGlitch { value: value as f32, hold_ms };
+            cmds.lock().unwrap().push(cmd);
+        });
+        let cmds = commands.clone();
+        engine.register_fn("emit", move |name: &str| {
+            let cmd = ScriptCommand::Emit { name: name.to_string(), data: serde_json::Value::Null };
+            cmds.lock().unwrap().push(cmd);
+        });
+        let cmds = commands.clone();
+        engine.register_fn("emit", move |name: &str, data: Dynamic| {
+            let data = rhai::serde::from_dynamic(&data).unwrap_or(serde_json::Value::Null);
+            cmds.lock().unwrap().push(ScriptCommand::Emit { name: name.to_string(), data });
+        });
+
+        // Directory polling and file reads stay off the pipeline thread
+        let scans = dir.map(|dir| {
+            println!("📝 [Scripts] Loading behaviours from {:?}", dir);
+            let (tx, rx) = mpsc::channel();
+            std::thread::Builder::new()
+                .name("vibe-scripts".to_string())
+                .spawn(move || watch(dir, tx))
+                .expect("Failed to spawn script watcher thread");
+            rx
+        });
+
+        Self {
+            scans,
+            engine,
+            scripts: Vec::new(),
+            commands,
+            deadline,
+            budget,
+            params: BTreeMap::new(),
+            glitch_hold: None,
+        }
+    }
+
+    pub fn run(&mut self, now: Duration, features: &AudioFeatures, state: &mut GlobalState) {
+        let Some(scans) = &self.scans else { return };
+        let pending: Vec<Scan> = scans.try_iter().collect();
+        for scan in pending {
+            self.load_scan(scan);
+        }
+
+        if self.scripts.iter().any(|s| !s.disabled()) {
+            let features_dyn = rhai::serde::to_dynamic(features).unwrap_or_default();
+            let state_dyn = rhai::serde::to_dynamic(&*state).unwrap_or_default();
+            let time_ms = now.as_millis() as i64;
+
+            for i in 0..self.scripts.len() {
+                if self.scripts[i].disabled() {
+                    continue;
+                }
+                self.run_script(i, &features_dyn, &state_dyn, time_ms);
+                let commands = std::mem::take(&mut *self.commands.lock().unwrap());
+                let source = format!("script:{}", self.scripts[i].name);
+                self.apply_commands(commands, &source, now, state);
+            }
+        }
+
+        // Held outputs persist across frames until their expiry
+        self.params.retain(|_, (_, until)| until.is_none_or(|u| now < u));
+        for (name, (value, _)) in &self.params {
+            state.params.insert(name.clone(), value.clone());
+        }
+        if let Some((value, until)) = self.glitch_hold {
+            if now < until {
+                state.glitch_factor = value;
+            } else {
+                self.glitch_hold = None;
+            }
+        }
+    }
+
+    fn run_script(&mut self, i: usize, features: &Dynamic, state: &Dynamic, time_ms: i64) {
+        let script = &mut self.scripts[i];
+        script.scope.set_value("features", features.clone());
+        script.scope.set_value("state", state.clone());
+        script.scope.set_value("time_ms", time_ms);
+        // Drop per-run `let`s afterwards; `mem` was pushed first and survives
+        let len = script.scope.len();
+
+        *self.deadline.lock().unwrap() = Some(Instant::now() + self.budget);
+        let result = self.engine.run_ast_with_scope(&mut script.scope, &script.ast);
+        *self.deadline.lock().unwrap() = None;
+        script.scope.rewind(len);
+
+        match result {
+            Ok(()) => script.failures = 0,
+            Err(e) => {
+                script.failures += 1;
+                warn!(event = "script_failed", script = %script.name, error = %e);
+                if script.disabled() {
+                    println!(
+                        "🛑 [Scripts] '{}' disabled after {} consecutive failures (last: {})",
+                        script.name, MAX_FAILURES, e
+                    );
+                }
+            }
+        }
+    }
+
+    fn apply_commands(
+        &mut self,
+        commands: Vec<ScriptCommand>,
+        source: &str,
+        now: Duration,
+        state: &mut GlobalState,
+    ) {
+        let until = |ms: i64| now + Duration::from_millis(ms.max(0) as u64);
+        for cmd in commands {
+            match cmd {
+                ScriptCommand::SetParam { name, value, hold_ms } => {
+                    self.params.insert(name, (value, hold_ms.map(until)));
+                }
+                // Triggers last one frame; the resolver turns them into counts
+                ScriptCommand::Fire { name } => {
+                    state.params.insert(name, ParamValue::Bool(true));
+                }
+                ScriptCommand::SetGlitch { value, hold_ms } => {
+                    self.glitch_hold = Some((value.clamp(0.0, 1.0), until(hold_ms)));
+                }
+                ScriptCommand::Emit { name, data } => state.events.push(PipelineEvent {
+                    source: source.to_string(),
+                    name,
+                    data,
+                    at_ms: now.as_millis() as u64,
+                }),
+            }
+        }
+    }
+
+    /// Picks up new, changed and removed script files.
+    fn load_scan(&mut self, scan: Scan) {
+        self.scripts.retain(|s| scan.paths.contains(&s.path));
+
+        for (path, source) in scan.changed {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            // A broken file is parked (disabled) until it is saved again
+            let (ast, failures) =
+                match source.and_then(|s| self.engine.compile(s).map_err(|e| e.to_string())) {
+                    Ok(ast) => (ast, 0),
+                    Err(e) => {
+                        println!("⚠️ [Scripts] '{}' failed to compile: {}", name, e);
+                        (AST::empty(), MAX_FAILURES)
+                    }
+                };
+
+            let mut scope = Scope::new();
+            scope.push("mem", Map::new());
+            let existing = self.scripts.iter().position(|s| s.path == path);
+            let script = Script { name: name.clone(), path, ast, scope, failures };
+            match existing {
+                Some(i) => self.scripts[i] = script,
+                None => self.scripts.push(script),
+            }
+            if failures == 0 {
+                println!("📝 [Scripts] Loaded '{}'", name);
+            }
+        }
+        // Files run in name order
+        self.scripts.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+/// Polls `dir` and sends the files that appeared, changed or went away,
+/// until the host is dropped.
+fn watch(dir: PathBuf, tx: Sender<Scan>) {
+    let mut known: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+    let mut first = true;
+    loop {
+        let paths = script_paths(&dir);
+        let mut changed = Vec::new();
+        for path in &paths {
+            let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            if known.get(path) == Some(&modified) {
+                continue;
+            }
+            known.insert(path.clone(), modified);
+            let source = std::fs::read_to_string(path).map_err(|e| e.to_string());
+            changed.push((path.clone(), source));
+        }
+        let removed = known.len() > paths.len();
+        known.retain(|p, _| paths.contains(p));
+
+        if first || removed || !changed.is_empty() {
+            first = false;
+            if tx.send(Scan { paths, changed }).is_err() {
+                return;
+            }
+        }
+        std::thread::sleep(RESCAN_INTERVAL);
+    }
+}
+
+fn script_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "rhai"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+fn param_value(name: &str, value: Dynamic) -> Result<ParamValue, Box<EvalAltResult>> {
+    let value = if let Ok(v) = value.as_float() {
+        ParamValue::Number(v as f32)
+    } else if let Ok(i) = value.as_int() {
+        ParamValue::Number(i as f32)
+    } else if let Ok(b) = value.as_bool() {
+        ParamValue::Bool(b)
+    } else if let Ok(s) = value.into_string() {
+        ParamValue::Color(s)
+    } else {
+        return Err(format!("set_param('{}'): unsupported value type", name).into());
+    };
+    ParamSchema::global().check(name, value).map_err(|e| e.to_string().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A host without a watcher, fed one scan with the given sources
+    fn host(sources: &[(&str, &str)]) -> ScriptHost {
+        let mut host = ScriptHost::load(None);
+        let path = |name: &str| PathBuf::from(format!("{}.rhai", name));
+        let (tx, rx) = mpsc::channel();
+        tx.send(Scan {
+            paths: sources.iter().map(|(name, _)| path(name)).collect(),
+            changed: sources.iter().map(|(name, src)| (path(name), Ok(src.to_string()))).collect(),
+        })
+        .unwrap();
+        host.scans = Some(rx);
+        host
+    }
+
+    fn run(host: &mut ScriptHost, ms: u64) -> GlobalState {
+        let mut state = GlobalState::default();
+        host.run(Duration::from_millis(ms), &AudioFeatures::default(), &mut state);
+        state
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped_and_disabled() {
+        let mut host = host(&[("spin", "loop { }")]);
+        for i in 1..=MAX_FAILURES {
+            run(&mut host, i as u64);
+            assert_eq!(host.scripts[0].failures, i);
+        }
+        assert!(host.scripts[0].disabled());
+    }
+
+    #[test]
+    fn imports_are_refused() {
+        let mut host = host(&[("imports", r#"import "secrets" as s; emit("loaded");"#)]);
+        let state = run(&mut host, 0);
+        assert_eq!(host.scripts[0].failures, 1);
+        assert!(state.events.is_empty());
+    }
+
+    #[test]
+    fn scripts_that_do_not_compile_are_parked() {
+        let mut host = host(&[("broken", "let = ;"), ("fine", r#"emit("ok");"#)]);
+        let state = run(&mut host, 0);
+        assert!(host.scripts[0].disabled());
+        assert_eq!(state.events.len(), 1);
+        assert_eq!(state.events[0].source, "script:fine");
+    }
+
+    #[test]
+    fn mem_survives_between_runs_and_holds_expire() {
+        let src = r#"
+            mem.n = if "n" in mem { mem.n + 1 } else { 1 };
+            if mem.n == 1 { set_glitch(0.8, 100); }
+            emit("count", #{ n: mem.n });
+        "#;
+        let mut host = host(&[("counter", src)]);
+        let first = run(&mut host, 0);
+        assert_eq!(first.glitch_factor, 0.8);
+
+        let second = run(&mut host, 50);
+        assert_eq!(second.events[0].data["n"], 2);
+        assert_eq!(second.glitch_factor, 0.8);
+
+        let third = run(&mut host, 100);
+        assert_eq!(third.glitch_factor, GlobalState::default().glitch_factor);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::overrides::OverrideStore;
+use crate::scenes::SceneEngine;
+use crate::state_machine::GlobalState;
+use crate::timeline::Timeline;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Operator layers applied to every published frame after the AI context:
+/// the timeline fires its cues, then the active scene and the overrides are
+/// applied, so a pinned field beats a scene.
+pub struct ShowControl {
+    pub timeline: Timeline,
+    pub scenes: SceneEngine,
+    pub overrides: OverrideStore,
+}
+
+impl ShowControl {
+    pub fn load(scenes: PathBuf, cues: Option<PathBuf>) -> Arc<Self> {
+        Arc::new(Self {
+            timeline: Timeline::load(cues),
+            scenes: SceneEngine::load(scenes),
+            overrides: OverrideStore::default(),
+        })
+    }
+
+    pub fn apply(&self, state: &mut GlobalState) {
+        self.timeline.apply(state, &self.scenes, &self.overrides);
+        self.scenes.apply(state);
+        self.overrides.apply(state);
+    }
+}
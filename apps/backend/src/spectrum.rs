@@ -0,0 +1,194 @@
+use serde::Deserialize;
+
+// Binary frame layout (little-endian), 24-byte header followed by f32 payloads:
+//   0  "VSPC" magic
+//   4  u8  version (1)
+//   5  u8  flags (bit 0: log-frequency bins)
+//   6  u16 reserved
+//   8  u64 sample_clock
+//  16  u32 sample_rate
+//  20  u16 waveform point count
+//  22  u16 spectrum bin count
+//  24  f32[points] waveform, then f32[bins] magnitudes
+const MAGIC: &[u8; 4] = b"VSPC";
+const VERSION: u8 = 1;
+const FLAG_LOG: u8 = 0b0000_0001;
+const MAX_POINTS: usize = 4096;
+const MAX_BINS: usize = 4096;
+const LOG_MIN_HZ: f32 = 20.0;
+
+/// Full-resolution analysis output of one capture buffer.
+#[derive(Debug, Clone)]
+pub struct SpectrumFrame {
+    pub sample_clock: u64,
+    pub sample_rate: u32,
+    /// Mono downmix of the buffer.
+    pub waveform: Vec<f32>,
+    /// FFT magnitudes of the downmix up to Nyquist, scaled to peak amplitude
+    /// (2|X|/N), so bin `i` sits at `i * sample_rate / N`.
+    pub magnitudes: Vec<f32>,
+}
+
+impl SpectrumFrame {
+    pub fn new(sample_clock: u64, sample_rate: u32, mono: &[f32], magnitudes: &[f32]) -> Self {
+        let scale = 2.0 / mono.len().max(1) as f32;
+        let magnitudes = magnitudes.iter().map(|m| m * scale).collect();
+        Self { sample_clock, sample_rate, waveform: mono.to_vec(), magnitudes }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BinScale {
+    #[default]
+    Linear,
+    Log,
+}
+
+/// Per-connection stream settings, taken from the `/ws/spectrum` query string.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SpectrumQuery {
+    pub points: usize,
+    pub bins: usize,
+    pub fps: f32,
+    pub scale: BinScale,
+}
+
+impl Default for SpectrumQuery {
+    fn default() -> Self {
+        Self { points: 512, bins: 256, fps: 30.0, scale: BinScale::Linear }
+    }
+}
+
+impl SpectrumQuery {
+    pub fn clamped(self) -> Self {
+        Self {
+            points: self.points.min(MAX_POINTS),
+            bins: self.bins.min(MAX_BINS),
+            // `clamp` passes NaN through, and `1 / fps` becomes the send interval
+            fps: if self.fps.is_finite() {
+                self.fps.clamp(1.0, 120.0)
+            } else {
+                Self::default().fps
+            },
+            scale: self.scale,
+        }
+    }
+
+    pub fn encode(&self, frame: &SpectrumFrame) -> Vec<u8> {
+        let mut waveform = decimate(&frame.waveform, self.points);
+        let mut bins = match self.scale {
+            BinScale::Linear => pool_linear(&frame.magnitudes, self.bins),
+            BinScale::Log => pool_log(&frame.magnitudes, self.bins, frame.sample_rate),
+        };
+        // `0` passes a buffer through unpooled; the header counts are u16
+        waveform.truncate(u16::MAX as usize);
+        bins.truncate(u16::MAX as usize);
+
+        let mut out = Vec::with_capacity(24 + 4 * (waveform.len() + bins.len()));
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(if self.scale == BinScale::Log { FLAG_LOG } else { 0 });
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&frame.sample_clock.to_le_bytes());
+        out.extend_from_slice(&frame.sample_rate.to_le_bytes());
+        out.extend_from_slice(&(waveform.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(bins.len() as u16).to_le_bytes());
+        for v in waveform.iter().chain(bins.iter()) {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+}
+
+/// Picks evenly spaced samples; keeps the trace shape for scopes.
+fn decimate(input: &[f32], points: usize) -> Vec<f32> {
+    if points == 0 || input.len() <= points {
+        return input.to_vec();
+    }
+    let step = input.len() as f32 / points as f32;
+    (0..points).map(|i| input[(i as f32 * step) as usize]).collect()
+}
+
+/// Max-pools magnitudes into `bins` equal-width bands.
+fn pool_linear(mags: &[f32], bins: usize) -> Vec<f32> {
+    if bins == 0 || mags.len() <= bins {
+        return mags.to_vec();
+    }
+    let step = mags.len() as f32 / bins as f32;
+    (0..bins)
+        .map(|i| {
+            let start = (i as f32 * step) as usize;
+            let end = (((i + 1) as f32 * step) as usize).max(start + 1).min(mags.len());
+            mags[start..end].iter().cloned().fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+/// Max-pools magnitudes into `bins` geometrically spaced bands from 20 Hz to Nyquist.
+fn pool_log(mags: &[f32], bins: usize, sample_rate: u32) -> Vec<f32> {
+    if bins == 0 || mags.is_empty() || sample_rate == 0 {
+        return mags.to_vec();
+    }
+    let nyquist = sample_rate as f32 / 2.0;
+    let hz_per_bin = nyquist / mags.len() as f32;
+    let ratio = (nyquist / LOG_MIN_HZ).powf(1.0 / bins as f32);
+
+    (0..bins)
+        .map(|i| {
+            let lo = LOG_MIN_HZ * ratio.powi(i as i32);
+            let hi = lo * ratio;
+            let start = ((lo / hz_per_bin) as usize).min(mags.len() - 1);
+            let end = ((hi / hz_per_bin) as usize).max(start + 1).min(mags.len());
+            mags[start..end].iter().cloned().fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(fps: f32) -> SpectrumQuery {
+        SpectrumQuery { fps, ..SpectrumQuery::default() }.clamped()
+    }
+
+    #[test]
+    fn non_finite_fps_falls_back_to_the_default() {
+        assert_eq!(query(f32::NAN).fps, 30.0);
+        assert_eq!(query(f32::INFINITY).fps, 30.0);
+        assert_eq!(query(f32::NEG_INFINITY).fps, 30.0);
+        assert_eq!(query(0.0).fps, 1.0);
+        assert_eq!(query(1000.0).fps, 120.0);
+    }
+
+    #[test]
+    fn encodes_the_documented_layout() {
+        let frame = SpectrumFrame {
+            sample_clock: 0x0102_0304_0506_0708,
+            sample_rate: 48_000,
+            waveform: (0..8).map(|i| i as f32).collect(),
+            magnitudes: vec![1.0, 4.0, 2.0, 3.0],
+        };
+        let q = SpectrumQuery { points: 4, bins: 2, fps: 30.0, scale: BinScale::Linear };
+        let out = q.encode(&frame);
+
+        assert_eq!(&out[0..4], b"VSPC");
+        assert_eq!(out[4], VERSION);
+        assert_eq!(out[5], 0);
+        assert_eq!(u64::from_le_bytes(out[8..16].try_into().unwrap()), frame.sample_clock);
+        assert_eq!(u32::from_le_bytes(out[16..20].try_into().unwrap()), 48_000);
+        assert_eq!(u16::from_le_bytes([out[20], out[21]]), 4);
+        assert_eq!(u16::from_le_bytes([out[22], out[23]]), 2);
+        assert_eq!(out.len(), 24 + 4 * (4 + 2));
+
+        let floats: Vec<f32> =
+            out[24..].chunks(4).map(|c| f32::from_le_bytes(c.try_into().unwrap())).collect();
+        // Every other sample, then the max of each half of the spectrum
+        assert_eq!(floats, vec![0.0, 2.0, 4.0, 6.0, 4.0, 3.0]);
+
+        let log = SpectrumQuery { scale: BinScale::Log, ..q }.encode(&frame);
+        assert_eq!(log[5], FLAG_LOG);
+    }
+}
@@ -1,4 +1,24 @@
+use crate::audio_engine::AudioFeatures;
+use crate::beat::BeatClock;
+use crate::crowd::CrowdStats;
+use crate::envelope::{FeatureEnvelopes, SmoothedFeatures};
+use crate::genre::{GenreClassifier, GenreTaxonomy};
+use crate::lfo::{LfoBank, LfoRunner};
+use crate::mood::{Mood, MoodEstimator};
+use crate::overrides::ActiveOverride;
+use crate::params::ParamValue;
+use crate::rules::RuleEngine;
+use crate::scenes::ActiveScene;
+use crate::scripting::ScriptHost;
+use crate::telemetry::SystemStats;
+use crate::ticker::FrameStamp;
+use crate::timeline::CueStatus;
+use crate::trend::{EnergyTrends, TrendAnalyzer, TrendConfig, TrendDirection};
+use crate::vibe::{
+    PhaseConfig, PhaseTracker, PhaseTransition, VibeConfig, VibeMachine, VibeTransition,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum VibeState {
@@ -7,43 +27,124 @@ pub enum VibeState {
     Chaos,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
-pub enum Genre {
-    Unknown,
-    Ambient,
-    Techno,
-    DnB,
-    Dubstep,
+/// Musical phase of the set. Finer than `VibeState`, which stays on the wire
+/// unchanged for clients that only know Chill/Build/Chaos.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum VibePhase {
+    #[default]
+    Idle,
+    Intro,
+    Build,
+    Drop,
+    Breakdown,
+    Outro,
+}
+
+/// Genre name from the configured taxonomy (see `GenreTaxonomy`). Serialized
+/// as a plain string, so the built-in names match the former enum on the wire.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Genre(String);
+
+impl Genre {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    pub fn unknown() -> Self {
+        Self::new("Unknown")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for Genre {
+    fn default() -> Self {
+        Self::unknown()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalState {
     pub state: VibeState,
+    #[serde(default)]
+    pub phase: VibePhase,
     pub genre: Genre, 
+    #[serde(default)]
+    pub genre_confidence: f32,
+    #[serde(default)]
+    pub genre_probabilities: BTreeMap<String, f32>,
+    #[serde(default)]
+    pub mood: Mood, // Smoothed valence/arousal estimate
     pub bpm: f32,
+    #[serde(default)]
+    pub beat: BeatInfo,
     pub glitch_factor: f32,
+    #[serde(default)]
+    pub params: BTreeMap<String, ParamValue>, // Custom channels, typed by the parameter schema
+    #[serde(default)]
+    pub lfos: BTreeMap<String, f32>, // Beat-synced modulators by name (see lfo.rs)
+    #[serde(default)]
+    pub events: Vec<PipelineEvent>, // Emitted during this update only
+    #[serde(default)]
+    pub blackout: bool, // Operator-only; clients render black while set
+    #[serde(default)]
+    pub safety_limited: bool, // The safety limiter changed this frame
+    #[serde(default)]
+    pub overrides: Vec<ActiveOverride>, // Operator overrides currently pinning fields
+    #[serde(default)]
+    pub scene: Option<ActiveScene>, // Scene showing or fading, if any
+    #[serde(default)]
+    pub cue: Option<CueStatus>, // Timeline position while a cue list is loaded
     pub low_energy: f32,
     pub mid_energy: f32,
     pub high_energy: f32,
     pub spectral_flux: f32, // NEW: Onset detection / Kick
-    pub energy_trend: String, // NEW: "RISING", "FALLING", "STABLE"
+    pub energy_trend: TrendDirection, // "RISING", "FALLING", "STABLE" (= energy_trends.medium)
+    #[serde(default)]
+    pub energy_trends: EnergyTrends, // Regression slope per horizon
+    #[serde(default)]
+    pub last_transition: Option<VibeTransition>,
+    #[serde(default)]
+    pub last_phase_transition: Option<PhaseTransition>,
+    #[serde(default)]
+    pub smoothed: SmoothedFeatures, // Attack/release envelopes of the raw values above
     // AI Director Context
     pub ai_theme: String,
     pub ai_primary_color: String,
-    pub ai_secondary_color: String,
+    pub ai_secondary_color: String, // Interpolated towards the targets below
+    #[serde(default)]
+    pub ai_target_primary_color: String,
+    #[serde(default)]
+    pub ai_target_secondary_color: String,
     pub ai_directive: String,
+    #[serde(default)]
+    pub crowd: CrowdStats, // Aggregated client boredom behind CHAOS_INJECTION (see crowd.rs)
     // System Telemetry
     pub system_stats: SystemStats,
     pub audio_meta: AudioMetadata,
+    #[serde(default)]
+    pub frame: FrameStamp, // Sequence number, timestamps and output rate (see ticker.rs)
 }
 
+/// Beat position integrated from `bpm` on the Overmind clock and phase-locked
+/// to onsets (see beat.rs). `count` is the number of whole beats since start,
+/// `phase` the position within the current one.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub struct BeatInfo {
+    pub count: u64,
+    pub phase: f32,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct SystemStats {
-    pub cpu_usage: f32,
-    pub memory_used: u64,
-    pub memory_total: u64,
-    pub uptime: u64,
+/// One-shot event raised inside the pipeline, e.g. by a script's `emit()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineEvent {
+    pub source: String,
+    pub name: String,
+    pub data: serde_json::Value,
+    pub at_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -58,133 +159,322 @@ impl Default for GlobalState {
     fn default() -> Self {
         Self {
             state: VibeState::Chill,
-            genre: Genre::Unknown,
+            phase: VibePhase::Idle,
+            genre: Genre::unknown(),
+            genre_confidence: 0.0,
+            genre_probabilities: BTreeMap::new(),
+            mood: Mood::default(),
             bpm: 128.0,
+            beat: BeatInfo::default(),
             glitch_factor: 0.0,
+            params: BTreeMap::new(),
+            lfos: BTreeMap::new(),
+            events: Vec::new(),
+            blackout: false,
+            safety_limited: false,
+            overrides: Vec::new(),
+            scene: None,
+            cue: None,
             low_energy: 0.0,
             mid_energy: 0.0,
             high_energy: 0.0,
             spectral_flux: 0.0,
-            energy_trend: "STABLE".to_string(), // Default
+            energy_trend: TrendDirection::Stable,
+            energy_trends: EnergyTrends::default(),
+            last_transition: None,
+            last_phase_transition: None,
+            smoothed: SmoothedFeatures::default(),
             ai_theme: "BOOT_SEQUENCE".to_string(),
             ai_primary_color: "#FFFFFF".to_string(),
             ai_secondary_color: "#000000".to_string(),
+            ai_target_primary_color: "#FFFFFF".to_string(),
+            ai_target_secondary_color: "#000000".to_string(),
             ai_directive: "INITIALIZING".to_string(),
+            crowd: CrowdStats::default(),
             system_stats: SystemStats::default(),
             audio_meta: AudioMetadata {
                 device_name: "Scanning...".to_string(),
                 sample_rate: 0,
                 channels: 0,
             },
+            frame: FrameStamp::default(),
         }
     }
 }
 
-use sysinfo::System;
-use std::collections::VecDeque;
+use crate::clock::{Clock, Every, WallClock};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Scheduling is in clock time, so behaviour does not depend on the callback rate
+const TREND_INTERVAL: Duration = Duration::from_secs(1);
+const GENRE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What a zone configures about its Overmind; shared with the API where it can be edited.
+#[derive(Clone)]
+pub struct OvermindConfig {
+    pub rules: Arc<RuleEngine>,
+    pub lfos: Arc<LfoBank>,
+    pub scripts_dir: Option<PathBuf>,
+}
 
 pub struct Overmind {
     state: GlobalState,
-    frame_count: u64,
-    sys_monitor: System,
-    energy_history: VecDeque<f32>, // Store last N frames of total energy
+    clock: Arc<dyn Clock>,
+    trends: TrendAnalyzer,
+    envelopes: FeatureEnvelopes,
+    vibe: VibeMachine,
+    phase: PhaseTracker,
+    genre: GenreClassifier,
+    mood: MoodEstimator,
+    beat: BeatClock,
+    pinned_bpm: Option<f32>,
+    rules: Arc<RuleEngine>,
+    rules_generation: Option<u64>,
+    lfos: LfoRunner,
+    scripts: ScriptHost,
+    last_update: Option<Duration>,
+    trend_timer: Every,
+    genre_timer: Every,
 }
 
 impl Overmind {
-    pub fn new(metadata: AudioMetadata) -> Self {
-        let mut sys = System::new_all();
-        sys.refresh_all();
+    pub fn new(metadata: AudioMetadata, config: &OvermindConfig) -> Self {
+        Self::with_clock(metadata, config, Arc::new(WallClock::new()))
+    }
 
-        let mut state = GlobalState::default();
-        state.audio_meta = metadata;
+    pub fn with_clock(
+        metadata: AudioMetadata,
+        config: &OvermindConfig,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let state = GlobalState { audio_meta: metadata, ..Default::default() };
 
-        Self { 
-            state, 
-            frame_count: 0, 
-            sys_monitor: sys,
-            energy_history: VecDeque::with_capacity(150), // ~5 seconds @ 30fps
+        Self {
+            state,
+            clock,
+            trends: TrendAnalyzer::new(TrendConfig::from_env()),
+            envelopes: FeatureEnvelopes::from_env(),
+            vibe: VibeMachine::new(VibeConfig::default()),
+            phase: PhaseTracker::new(PhaseConfig::default()),
+            genre: GenreClassifier::from_env(),
+            mood: MoodEstimator::from_env(),
+            beat: BeatClock::default(),
+            pinned_bpm: None,
+            rules: config.rules.clone(),
+            rules_generation: None,
+            lfos: LfoRunner::new(config.lfos.clone()),
+            scripts: ScriptHost::load(config.scripts_dir.clone()),
+            last_update: None,
+            trend_timer: Every::new(TREND_INTERVAL),
+            genre_timer: Every::new(GENRE_INTERVAL),
         }
     }
 
-    pub fn update(&mut self, low: f32, mid: f32, high: f32, flux: f32) -> GlobalState {
-        self.frame_count += 1;
+    /// Tempo an operator override holds `bpm` at. The beat clock runs on it so
+    /// beat counts (and beat-timed overrides) follow the published tempo.
+    pub fn pin_bpm(&mut self, bpm: Option<f32>) {
+        self.pinned_bpm = bpm;
+    }
+
+    pub fn update(&mut self, features: &AudioFeatures) -> GlobalState {
+        let now = self.clock.now();
+        let (low, mid, high, flux) = (
+            features.low_energy,
+            features.mid_energy,
+            features.high_energy,
+            features.spectral_flux,
+        );
+        self.state.events.clear();
+
+        // Pick up hot-reloaded thresholds without resetting the current vibe
+        let (generation, rules) = self.rules.snapshot();
+        if self.rules_generation != Some(generation) {
+            self.vibe.set_config(rules.vibe.clone());
+            self.rules_generation = Some(generation);
+        }
 
         self.state.low_energy = low;
         self.state.mid_energy = mid;
         self.state.high_energy = high;
         self.state.spectral_flux = flux;
 
-        // --- Trend Analysis ---
-        let total_energy = (low + mid + high) / 3.0;
-        if self.energy_history.len() >= 150 {
-            self.energy_history.pop_front();
+        // --- Envelope Smoothing ---
+        let dt = self.last_update.map(|t| now.saturating_sub(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+        self.state.smoothed = self.envelopes.process(low, mid, high, flux, dt);
+
+        // --- Beat Clock ---
+        // Onsets near the predicted beat pull the phase back in step
+        let bpm = self.pinned_bpm.unwrap_or(self.state.bpm);
+        self.beat.advance(&mut self.state.beat, dt, bpm);
+        if self.genre.push(now, features) {
+            self.beat.onset(&mut self.state.beat);
         }
-        self.energy_history.push_back(total_energy);
-
-        // Every 30 frames (1s), calculate trend
-        if self.frame_count % 30 == 0 && self.energy_history.len() > 30 {
-            let recent_avg: f32 = self.energy_history.iter().skip(self.energy_history.len() - 30).sum::<f32>() / 30.0;
-            let old_avg: f32 = self.energy_history.iter().take(30).sum::<f32>() / 30.0;
-            
-            let delta = recent_avg - old_avg;
-            if delta > 0.1 {
-                self.state.energy_trend = "RISING".to_string();
-            } else if delta < -0.1 {
-                self.state.energy_trend = "FALLING".to_string();
-            } else {
-                self.state.energy_trend = "STABLE".to_string();
-            }
+
+        // --- Trend Analysis ---
+        // Least-squares slope of the mean energy over short/medium/long horizons
+        self.trends.push(now, (low + mid + high) / 3.0);
+        if self.trend_timer.due(now) {
+            self.state.energy_trends = self.trends.trends(now);
+            self.state.energy_trend = self.state.energy_trends.medium.direction;
         }
 
         // --- Vibe Logic ---
-        // Enhanced onset detection using spectral flux
-        if flux > 0.6 || low > 0.8 {
-            self.state.state = VibeState::Chaos;
-            self.state.glitch_factor = 1.0;
-        } else if flux > 0.3 || mid > 0.5 {
-            self.state.state = VibeState::Build;
-            self.state.glitch_factor = 0.3;
-        } else {
-            self.state.state = VibeState::Chill;
-            self.state.glitch_factor = 0.0;
-        }
+        // Hysteresis + dwell times live in the VibeMachine; a single kick no longer strobes
+        self.state.state = self.vibe.update(now, low, mid, flux);
+        self.state.last_transition = self.vibe.last_transition().cloned();
+
+        // --- Song Phase ---
+        let s = &self.state.smoothed;
+        let energy = (s.low_energy + s.mid_energy + s.high_energy) / 3.0;
+        self.state.phase =
+            self.phase.update(now, self.state.state, self.state.energy_trend, energy);
+        self.state.last_phase_transition = self.phase.last_transition().cloned();
 
         // --- Genre & Rhythm Analysis ---
-        // Classification based on real energy profile signatures (Heuristic V1)
-        if self.frame_count % 300 == 0 {
-            // Every ~10 seconds (was 5, slowing down for better samples)
-            if low < 0.15 && mid < 0.15 {
-                self.state.genre = Genre::Ambient;
-                self.state.bpm = 90.0;
-            } else if low > 0.65 && high > 0.65 {
-                self.state.genre = Genre::DnB;
-                self.state.bpm = 174.0;
-            } else if low > 0.45 && mid > 0.35 {
-                self.state.genre = Genre::Techno;
-                self.state.bpm = 128.0;
-            } else if low > 0.3 && mid > 0.2 {
-                self.state.genre = Genre::Dubstep;
-                self.state.bpm = 140.0;
-            } else {
-                self.state.genre = Genre::Unknown;
-                self.state.bpm = 120.0;
+        // Windowed feature statistics through the loadable model (see genre.rs)
+        self.mood.push(dt, features);
+        if self.genre_timer.due(now) {
+            if let Some(estimate) = self.genre.classify() {
+                if estimate.confidence >= self.genre.min_confidence() {
+                    self.state.genre = estimate.genre;
+                }
+                self.state.genre_confidence = estimate.confidence;
+                self.state.genre_probabilities = estimate.probabilities;
+
+                // Measured tempo, octave-corrected into the genre's range (e.g. 87 -> 174 for DnB)
+                let def = GenreTaxonomy::global().get(&self.state.genre);
+                let measured = estimate.tempo_bpm.map(|bpm| def.fold_tempo(bpm));
+                self.state.bpm = measured.unwrap_or_else(|| def.typical_bpm());
+                self.beat.set_tempo(measured);
+                self.mood.estimate(&estimate.stats, self.state.bpm);
             }
 
             #[cfg(debug_assertions)]
-            println!("[STATE] Classification: {:?} | BPM: {} | Trend: {}", self.state.genre, self.state.bpm, self.state.energy_trend);
+            println!("[STATE] Classification: {} ({:.0}%) | BPM: {:.1} | Trend: {}",
+                self.state.genre.as_str(), self.state.genre_confidence * 100.0, self.state.bpm,
+                self.state.energy_trend.as_str());
         }
 
-        // Monitor System Stats (Every ~1s)
-        if self.frame_count % 30 == 0 {
-            self.sys_monitor.refresh_cpu();
-            self.sys_monitor.refresh_memory();
+        // --- Mood ---
+        // Valence/arousal from the genre window and pitch profile (see mood.rs)
+        self.state.mood = self.mood.update(dt);
 
-            self.state.system_stats.cpu_usage = self.sys_monitor.global_cpu_info().cpu_usage();
-            self.state.system_stats.memory_used = self.sys_monitor.used_memory();
-            self.state.system_stats.memory_total = self.sys_monitor.total_memory();
-            self.state.system_stats.uptime = System::uptime();
-        }
+        // --- Modulators ---
+        // Beat-synced LFOs and envelopes, visible to the rules and scripts below
+        self.lfos.apply(&mut self.state);
+
+        // --- Rules ---
+        // Declarative outputs (glitch_factor, params, optional state) from the rule file
+        rules.apply(&mut self.state);
+
+        // --- Scripts ---
+        // User behaviours run last and may override rule outputs
+        self.scripts.run(now, features, &mut self.state);
+        self.lfos.observe(&self.state);
 
         self.state.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn overmind() -> (Overmind, Arc<ManualClock>) {
+        let config = OvermindConfig {
+            rules: RuleEngine::load(None),
+            lfos: LfoBank::load(None),
+            scripts_dir: None,
+        };
+        let clock = Arc::new(ManualClock::new());
+        (Overmind::with_clock(AudioMetadata::default(), &config, clock.clone()), clock)
+    }
+
+    fn features(low: f32, flux: f32) -> AudioFeatures {
+        AudioFeatures { low_energy: low, spectral_flux: flux, ..Default::default() }
+    }
+
+    /// Steps the Overmind every `step_ms` from `from_ms` to `to_ms` inclusive.
+    fn run(
+        overmind: &mut Overmind,
+        clock: &ManualClock,
+        (from_ms, to_ms, step_ms): (u64, u64, u64),
+        features: &AudioFeatures,
+    ) -> GlobalState {
+        let mut state = GlobalState::default();
+        for ms in (from_ms..=to_ms).step_by(step_ms as usize) {
+            clock.set(Duration::from_millis(ms));
+            state = overmind.update(features);
+        }
+        state
+    }
+
+    #[test]
+    fn envelope_release_follows_the_clock_not_the_call_rate() {
+        // Built-in low band: 5 ms attack, 150 ms release
+        let release = |step_ms: u64| {
+            let (mut overmind, clock) = overmind();
+            run(&mut overmind, &clock, (0, 100, 100), &features(1.0, 0.0));
+            run(&mut overmind, &clock, (100 + step_ms, 250, step_ms), &features(0.0, 0.0))
+                .smoothed
+                .low_energy
+        };
+        let expected = (-1.0f32).exp();
+        assert!((release(150) - expected).abs() < 1e-3);
+        assert!((release(10) - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn vibe_holds_between_exit_and_entry_thresholds() {
+        // Built-in thresholds: chaos enters above flux 0.6 and exits below 0.45
+        let (mut overmind, clock) = overmind();
+
+        // Chill must last its 500 ms dwell before escalating
+        let state = run(&mut overmind, &clock, (0, 480, 20), &features(0.0, 0.7));
+        assert_eq!(state.state, VibeState::Chill);
+        let state = run(&mut overmind, &clock, (500, 600, 20), &features(0.0, 0.7));
+        assert_eq!(state.state, VibeState::Chaos);
+
+        let state = run(&mut overmind, &clock, (620, 5000, 20), &features(0.0, 0.5));
+        assert_eq!(state.state, VibeState::Chaos);
+
+        // Releasing needs 500 ms below the exit thresholds
+        let state = run(&mut overmind, &clock, (5020, 5500, 20), &features(0.0, 0.0));
+        assert_eq!(state.state, VibeState::Chaos);
+        let state = run(&mut overmind, &clock, (5520, 5600, 20), &features(0.0, 0.0));
+        assert_eq!(state.state, VibeState::Chill);
+        assert_eq!(state.last_transition.map(|t| t.from), Some(VibeState::Chaos));
+    }
+
+    #[test]
+    fn genre_is_reevaluated_every_two_seconds() {
+        // Default 10 s window: nothing is classified before 5 s are covered
+        let (mut overmind, clock) = overmind();
+        let state = run(&mut overmind, &clock, (0, 5950, 50), &features(0.6, 0.3));
+        assert!(state.genre_probabilities.is_empty());
+
+        let classified = run(&mut overmind, &clock, (6000, 6000, 50), &features(0.6, 0.3));
+        assert!(!classified.genre_probabilities.is_empty());
+
+        // Held between evaluations, whatever the audio does
+        let state = run(&mut overmind, &clock, (6050, 7950, 50), &features(0.1, 0.0));
+        assert_eq!(state.genre_probabilities, classified.genre_probabilities);
+        assert_eq!(state.bpm, classified.bpm);
+    }
+
+    #[test]
+    fn pinned_bpm_drives_the_beat_clock() {
+        let (mut overmind, clock) = overmind();
+        overmind.pin_bpm(Some(60.0));
+        let state = run(&mut overmind, &clock, (0, 10_000, 100), &features(0.0, 0.0));
+        assert_eq!(state.beat.count, 10);
+
+        overmind.pin_bpm(Some(240.0));
+        let state = run(&mut overmind, &clock, (10_100, 12_000, 100), &features(0.0, 0.0));
+        assert_eq!(state.beat.count, 18);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::audio_engine::CALLBACK_BUSY_NANOS;
+use crate::state_machine::GlobalState;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use sysinfo::{Components, System};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SystemStats {
+    pub cpu_usage: f32,
+    pub memory_used: u64,
+    pub memory_total: u64,
+    pub uptime: u64,
+    #[serde(default)]
+    pub cpu_per_core: Vec<f32>,
+    #[serde(default)]
+    pub load_average: [f64; 3], // 1, 5 and 15 minutes; zero where unsupported
+    #[serde(default)]
+    pub process_rss: u64, // Bytes
+    #[serde(default)]
+    pub process_cpu: f32, // Percent of one core, may exceed 100
+    #[serde(default)]
+    pub thread_count: Option<usize>,
+    #[serde(default)]
+    pub audio_thread_cpu: Option<f32>, // Percent of one core spent in the capture callback
+    #[serde(default)]
+    pub temperature_c: Option<f32>, // Hottest sensor, when the platform exposes any
+}
+
+/// System telemetry sampled on its own thread every `TELEMETRY_INTERVAL_MS`
+/// (default 1000). The pipeline only copies the latest sample into each frame.
+pub struct Telemetry {
+    latest: RwLock<SystemStats>,
+}
+
+impl Telemetry {
+    pub fn spawn() -> Arc<Self> {
+        let interval = sample_interval(std::env::var("TELEMETRY_INTERVAL_MS").ok().as_deref());
+
+        let telemetry = Arc::new(Self { latest: RwLock::new(SystemStats::default()) });
+        let shared = telemetry.clone();
+        let spawned = std::thread::Builder::new()
+            .name("telemetry".into())
+            .spawn(move || Sampler::new().run(interval, &shared));
+        if let Err(e) = spawned {
+            println!("⚠️ [Telemetry] Could not start sampler thread: {}", e);
+        }
+        telemetry
+    }
+
+    pub fn apply(&self, state: &mut GlobalState) {
+        state.system_stats = self.latest.read().unwrap().clone();
+    }
+}
+
+fn sample_interval(ms: Option<&str>) -> Duration {
+    ms.and_then(|v| v.parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_INTERVAL)
+        .max(MIN_INTERVAL)
+}
+
+/// Share of one core the capture callback was busy for, in percent.
+fn busy_percent(busy_nanos: u64, elapsed: Duration) -> f32 {
+    (busy_nanos as f64 / elapsed.as_nanos().max(1) as f64 * 100.0) as f32
+}
+
+struct Sampler {
+    sys: System,
+    components: Components,
+    pid: Option<sysinfo::Pid>,
+    last_busy: u64,
+    last_sample: Instant,
+}
+
+impl Sampler {
+    fn new() -> Self {
+        let mut sys = System::new();
+        sys.refresh_cpu(); // CPU usage is a delta, so take the baseline now
+        Self {
+            sys,
+            components: Components::new_with_refreshed_list(),
+            pid: sysinfo::get_current_pid().ok(),
+            last_busy: CALLBACK_BUSY_NANOS.load(Ordering::Relaxed),
+            last_sample: Instant::now(),
+        }
+    }
+
+    fn run(mut self, interval: Duration, telemetry: &Telemetry) {
+        loop {
+            std::thread::sleep(interval);
+            let stats = self.sample();
+            *telemetry.latest.write().unwrap() = stats;
+        }
+    }
+
+    fn sample(&mut self) -> SystemStats {
+        self.sys.refresh_cpu();
+        self.sys.refresh_memory();
+        self.components.refresh();
+
+        if let Some(pid) = self.pid {
+            self.sys.refresh_process(pid);
+        }
+        let process = self.pid.and_then(|pid| self.sys.process(pid));
+        let load = System::load_average();
+
+        let now = Instant::now();
+        let busy = CALLBACK_BUSY_NANOS.load(Ordering::Relaxed);
+        // Stays None in replay and NO_AUDIO mode, where the callback never runs
+        let audio_thread_cpu = (busy > 0)
+            .then(|| busy_percent(busy.saturating_sub(self.last_busy), now - self.last_sample));
+        self.last_busy = busy;
+        self.last_sample = now;
+
+        SystemStats {
+            cpu_usage: self.sys.global_cpu_info().cpu_usage(),
+            memory_used: self.sys.used_memory(),
+            memory_total: self.sys.total_memory(),
+            uptime: System::uptime(),
+            cpu_per_core: self.sys.cpus().iter().map(|c| c.cpu_usage()).collect(),
+            load_average: [load.one, load.five, load.fifteen],
+            process_rss: process.map_or(0, |p| p.memory()),
+            process_cpu: process.map_or(0.0, |p| p.cpu_usage()),
+            thread_count: std::fs::read_dir("/proc/self/task").ok().map(|d| d.count()),
+            audio_thread_cpu,
+            temperature_c: self
+                .components
+                .iter()
+                .map(|c| c.temperature())
+                .filter(|t| t.is_finite() && *t > 0.0)
+                .reduce(f32::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_defaults_and_has_a_floor() {
+        assert_eq!(sample_interval(None), DEFAULT_INTERVAL);
+        assert_eq!(sample_interval(Some("soon")), DEFAULT_INTERVAL);
+        assert_eq!(sample_interval(Some("500")), Duration::from_millis(500));
+        assert_eq!(sample_interval(Some("10")), MIN_INTERVAL);
+    }
+
+    #[test]
+    fn busy_time_is_a_share_of_the_elapsed_time() {
+        let second = Duration::from_secs(1);
+        assert_eq!(busy_percent(250_000_000, second), 25.0);
+        assert_eq!(busy_percent(0, second), 0.0);
+        assert!(busy_percent(1, Duration::ZERO).is_finite());
+    }
+
+    #[test]
+    fn samples_the_host() {
+        let stats = Sampler::new().sample();
+        assert!(stats.memory_total > 0 && stats.memory_used <= stats.memory_total);
+        assert!(!stats.cpu_per_core.is_empty());
+        assert!(stats.load_average.iter().all(|l| l.is_finite() && *l >= 0.0));
+        assert!(stats.temperature_c.is_none_or(|t| t.is_finite() && t > 0.0));
+    }
+
+    #[test]
+    fn frames_carry_the_latest_sample() {
+        let telemetry = Telemetry { latest: RwLock::new(SystemStats::default()) };
+        telemetry.latest.write().unwrap().uptime = 42;
+        let mut state = GlobalState::default();
+        telemetry.apply(&mut state);
+        assert_eq!(state.system_stats.uptime, 42);
+    }
+
+    #[test]
+    fn older_records_without_the_extended_fields_still_load() {
+        let json = r#"{"cpu_usage": 12.5, "memory_used": 1, "memory_total": 2, "uptime": 3}"#;
+        let stats: SystemStats = serde_json::from_str(json).unwrap();
+        assert_eq!(stats.cpu_usage, 12.5);
+        assert_eq!(stats.audio_thread_cpu, None);
+        assert_eq!(stats.load_average, [0.0; 3]);
+    }
+}
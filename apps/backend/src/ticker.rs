@@ -0,0 +1,188 @@
+use crate::state_machine::GlobalState;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+const DEFAULT_RATE_HZ: f32 = 60.0;
+const MAX_RATE_HZ: f32 = 240.0;
+// The measured rate is the frame count over windows of this length
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+// Below this share of the target the stream is reported as falling behind
+const LOW_RATE_RATIO: f32 = 0.9;
+
+#[derive(Debug, Clone)]
+pub struct TickerConfig {
+    pub rate_hz: f32,
+}
+
+impl TickerConfig {
+    /// `STATE_RATE_HZ` (default 60, clamped to 1..240).
+    pub fn from_env() -> Self {
+        Self { rate_hz: parse_rate(std::env::var("STATE_RATE_HZ").ok().as_deref()) }
+    }
+
+    /// Ticks at the configured rate. Ticks missed during a stall are skipped,
+    /// not burst out to catch up.
+    pub fn interval(&self) -> Interval {
+        let mut interval = tokio::time::interval(Duration::from_secs_f32(1.0 / self.rate_hz));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        interval
+    }
+}
+
+fn parse_rate(hz: Option<&str>) -> f32 {
+    hz.and_then(|v| v.parse::<f32>().ok())
+        .filter(|hz| hz.is_finite() && *hz > 0.0)
+        .map_or(DEFAULT_RATE_HZ, |hz| hz.clamp(1.0, MAX_RATE_HZ))
+}
+
+/// Timing of a published frame. `seq` grows by one per frame of the zone's
+/// stream, so a gap on the client means frames were dropped on the way.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct FrameStamp {
+    pub seq: u64,
+    pub mono_ms: f64, // Monotonic time since the pipeline started
+    #[serde(default)]
+    pub audio_ms: Option<f64>, // Capture clock at the end of the analysed buffer
+    #[serde(default)]
+    pub target_hz: Option<f32>, // None while a replay paces the stream
+    pub measured_hz: f32, // Frames actually published over the last second
+}
+
+/// Stamps outgoing frames and measures the rate they leave at.
+pub struct FrameStamper {
+    target_hz: Option<f32>,
+    started: Instant,
+    seq: u64,
+    window_start: Instant,
+    window_frames: u32,
+    measured_hz: f32,
+    low: bool,
+}
+
+impl FrameStamper {
+    pub fn new(target_hz: Option<f32>) -> Self {
+        let now = Instant::now();
+        Self {
+            target_hz,
+            started: now,
+            seq: 0,
+            window_start: now,
+            window_frames: 0,
+            measured_hz: 0.0,
+            low: false,
+        }
+    }
+
+    pub fn stamp(&mut self, state: &mut GlobalState, audio_ms: Option<f64>) {
+        self.stamp_at(state, audio_ms, Instant::now());
+    }
+
+    fn stamp_at(&mut self, state: &mut GlobalState, audio_ms: Option<f64>, now: Instant) {
+        self.window_frames += 1;
+        let window = now - self.window_start;
+        if window >= RATE_WINDOW {
+            self.measured_hz = self.window_frames as f32 / window.as_secs_f32();
+            self.window_start = now;
+            self.window_frames = 0;
+            self.report();
+        }
+
+        state.frame = FrameStamp {
+            seq: self.seq,
+            mono_ms: (now - self.started).as_secs_f64() * 1000.0,
+            audio_ms,
+            target_hz: self.target_hz,
+            measured_hz: self.measured_hz,
+        };
+        self.seq += 1;
+    }
+
+    /// Logs once when the rate falls behind the target and once when it recovers.
+    fn report(&mut self) {
+        let Some(target_hz) = self.target_hz else { return };
+        let low = self.measured_hz < target_hz * LOW_RATE_RATIO;
+        if low && !self.low {
+            warn!(event = "state_rate_low", target_hz, measured_hz = self.measured_hz);
+        } else if !low && self.low {
+            info!(event = "state_rate_recovered", target_hz, measured_hz = self.measured_hz);
+        }
+        self.low = low;
+    }
+}
+
+/// Position of `sample_clock` in milliseconds; None without a capture clock.
+pub fn audio_ms(sample_clock: u64, sample_rate: u32) -> Option<f64> {
+    (sample_rate > 0).then(|| sample_clock as f64 * 1000.0 / sample_rate as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_is_clamped_and_defaults_on_bad_input() {
+        assert_eq!(parse_rate(None), DEFAULT_RATE_HZ);
+        for bad in ["fast", "NaN", "inf", "0", "-30"] {
+            assert_eq!(parse_rate(Some(bad)), DEFAULT_RATE_HZ, "{}", bad);
+        }
+        assert_eq!(parse_rate(Some("30")), 30.0);
+        assert_eq!(parse_rate(Some("0.5")), 1.0);
+        assert_eq!(parse_rate(Some("1000")), MAX_RATE_HZ);
+    }
+
+    #[tokio::test]
+    async fn interval_ticks_at_the_rate() {
+        let interval = TickerConfig { rate_hz: 50.0 }.interval();
+        assert_eq!(interval.period(), Duration::from_millis(20));
+    }
+
+    /// Stamps `frames` frames spread evenly over one second from `start`.
+    fn second(stamper: &mut FrameStamper, start: Instant, frames: u64) -> Vec<FrameStamp> {
+        (1..=frames)
+            .map(|i| {
+                let mut state = GlobalState::default();
+                let at = start + Duration::from_millis(i * 1000 / frames);
+                stamper.stamp_at(&mut state, Some(i as f64), at);
+                state.frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stamps_are_sequential_and_monotonic() {
+        let mut stamper = FrameStamper::new(Some(60.0));
+        let start = stamper.started;
+        let stamps = second(&mut stamper, start, 60);
+        for (i, pair) in stamps.windows(2).enumerate() {
+            assert_eq!(pair[1].seq, pair[0].seq + 1);
+            assert!(pair[1].mono_ms > pair[0].mono_ms);
+            assert_eq!(pair[1].audio_ms, Some(i as f64 + 2.0));
+        }
+        assert_eq!(stamps[0].seq, 0);
+        assert_eq!(stamps[0].target_hz, Some(60.0));
+    }
+
+    #[test]
+    fn measures_the_rate_over_each_window() {
+        let mut stamper = FrameStamper::new(Some(60.0));
+        let start = stamper.started;
+        let stamps = second(&mut stamper, start, 60);
+        assert_eq!(stamps[58].measured_hz, 0.0);
+        assert_eq!(stamps[59].measured_hz, 60.0);
+        assert!(!stamper.low);
+
+        let stamps = second(&mut stamper, start + RATE_WINDOW, 30);
+        assert_eq!(stamps[29].measured_hz, 30.0);
+        assert!(stamper.low);
+        second(&mut stamper, start + RATE_WINDOW * 2, 60);
+        assert!(!stamper.low);
+    }
+
+    #[test]
+    fn audio_time_needs_a_sample_rate() {
+        assert_eq!(audio_ms(96_000, 48_000), Some(2000.0));
+        assert_eq!(audio_ms(96_000, 0), None);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::overrides::{self, OverrideRequest, OverrideStore};
+use crate::scenes::SceneEngine;
+use crate::state_machine::{GlobalState, VibePhase, VibeState};
+use anyhow::{anyhow, bail, ensure};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// When a cue fires on its own. Only the cue after the current one is
+/// watched, so cues always fire in list order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Waits for `go`.
+    #[default]
+    Manual,
+    /// Milliseconds since the show started.
+    AtMs(u64),
+    /// Beats since the show started.
+    AtBeat(u64),
+    /// Milliseconds since the previous cue fired.
+    AfterMs(u64),
+    /// Beats since the previous cue fired.
+    AfterBeats(u64),
+    /// The set enters this phase.
+    Phase(VibePhase),
+    /// The vibe state changes to this state.
+    Vibe(VibeState),
+    /// A pipeline event with this name (rules or scripts).
+    Event(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cue {
+    pub name: String,
+    #[serde(default)]
+    pub trigger: Trigger,
+    /// Scene to recall; `release_scene` hands back to bindings instead.
+    #[serde(default)]
+    pub scene: Option<String>,
+    #[serde(default)]
+    pub release_scene: bool,
+    #[serde(default)]
+    pub crossfade_ms: Option<u64>,
+    /// Shorthands for a `theme` / `directive` override.
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub directive: Option<String>,
+    #[serde(default)]
+    pub overrides: Vec<OverrideRequest>,
+    /// Override fields to clear before applying this cue; `"*"` clears all.
+    #[serde(default)]
+    pub clear_overrides: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CueList {
+    pub cues: Vec<Cue>,
+}
+
+impl CueList {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let list: CueList = serde_json::from_str(text)?;
+        let mut names = HashSet::new();
+        for cue in &list.cues {
+            ensure!(!cue.name.trim().is_empty(), "cue with empty name");
+            ensure!(names.insert(cue.name.as_str()), "duplicate cue '{}'", cue.name);
+            ensure!(
+                !(cue.scene.is_some() && cue.release_scene),
+                "{}: scene and release_scene are exclusive",
+                cue.name
+            );
+            for req in &cue.overrides {
+                overrides::validate(req).map_err(|e| anyhow!("{}: {}", cue.name, e))?;
+            }
+        }
+        Ok(list)
+    }
+}
+
+/// Jump target: list index or cue name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CueRef {
+    Index(usize),
+    Name(String),
+}
+
+/// Published as `GlobalState.cue` while a cue list is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CueStatus {
+    /// Last fired cue; `None` before the first `go`.
+    pub index: Option<usize>,
+    pub name: Option<String>,
+    pub next: Option<String>,
+    pub total: usize,
+    pub running: bool,
+    pub since_ms: u64,
+}
+
+struct Inner {
+    list: CueList,
+    current: Option<usize>,
+    pending: Option<usize>, // Requested through the API, fired on the next frame
+    running: bool,
+    started: Instant,
+    start_beat: u64,
+    fired: Instant,
+    fired_beat: u64,
+    last_phase: VibePhase,
+    last_vibe: VibeState,
+}
+
+/// Cue list playback (the zone's cue file, `CUES_PATH`) against the wall clock and the beat clock.
+/// Cues act through the scene bank and the override store, so anything a cue
+/// sets can still be changed by hand.
+pub struct Timeline {
+    path: Option<PathBuf>,
+    inner: Mutex<Inner>,
+}
+
+impl Timeline {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let list = match &path {
+            Some(p) => match std::fs::read_to_string(p)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| CueList::parse(&text))
+            {
+                Ok(list) => {
+                    println!("🎞️ [Timeline] Loaded {} cue(s) from {:?}", list.cues.len(), p);
+                    list
+                }
+                Err(e) => {
+                    println!("⚠️ [Timeline] {:?} rejected: {}", p, e);
+                    CueList::default()
+                }
+            },
+            None => CueList::default(),
+        };
+
+        let now = Instant::now();
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                list,
+                current: None,
+                pending: None,
+                running: false,
+                started: now,
+                start_beat: 0,
+                fired: now,
+                fired_beat: 0,
+                last_phase: VibePhase::default(),
+                last_vibe: VibeState::Chill,
+            }),
+        }
+    }
+
+    pub fn cues(&self) -> Vec<Cue> {
+        self.inner.lock().unwrap().list.cues.clone()
+    }
+
+    /// Re-reads `CUES_PATH` and rewinds to before the first cue.
+    pub fn reload(&self) -> anyhow::Result<usize> {
+        let path = self.path.as_ref().ok_or_else(|| anyhow!("CUES_PATH is not set"))?;
+        let list = CueList::parse(&std::fs::read_to_string(path)?)?;
+        let count = list.cues.len();
+        let mut inner = self.inner.lock().unwrap();
+        inner.list = list;
+        inner.current = None;
+        inner.pending = None;
+        inner.running = false;
+        info!(event = "cues_reloaded", count);
+        Ok(count)
+    }
+
+    /// Fires the next cue (the first one starts the show).
+    pub fn go(&self) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let next = inner.pending.or(inner.current).map_or(0, |i| i + 1);
+        ensure!(next < inner.list.cues.len(), "end of cue list");
+        inner.pending = Some(next);
+        inner.running = true;
+        Ok(())
+    }
+
+    /// Re-fires the cue before the current one.
+    pub fn back(&self) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let prev = inner.pending.or(inner.current).and_then(|i| i.checked_sub(1));
+        inner.pending = Some(prev.ok_or_else(|| anyhow!("already at the first cue"))?);
+        Ok(())
+    }
+
+    pub fn jump(&self, target: &CueRef) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let index = match target {
+            CueRef::Index(i) if *i < inner.list.cues.len() => *i,
+            CueRef::Name(name) => match inner.list.cues.iter().position(|c| &c.name == name) {
+                Some(i) => i,
+                None => bail!("unknown cue '{}'", name),
+            },
+            CueRef::Index(i) => bail!("no cue at index {}", i),
+        };
+        inner.pending = Some(index);
+        inner.running = true;
+        Ok(())
+    }
+
+    /// Halts automatic triggers. What the fired cues set stays in place.
+    pub fn stop(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.running = false;
+        inner.pending = None;
+    }
+
+    pub fn status(&self) -> Option<CueStatus> {
+        let inner = self.inner.lock().unwrap();
+        Self::publish(&inner)
+    }
+
+    /// Fires at most one cue per frame, before scenes and overrides are applied.
+    pub fn apply(&self, state: &mut GlobalState, scenes: &SceneEngine, store: &OverrideStore) {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        let now = Instant::now();
+        let beat = state.beat.count;
+
+        let entered_phase = state.phase != inner.last_phase;
+        let entered_vibe = state.state != inner.last_vibe;
+        inner.last_phase = state.phase;
+        inner.last_vibe = state.state;
+
+        let requested = inner.pending.take();
+        let automatic = || {
+            let next = inner.current? + 1;
+            let cue = inner.list.cues.get(next)?;
+            let fire = match &cue.trigger {
+                Trigger::Manual => false,
+                Trigger::AtMs(ms) => now - inner.started >= Duration::from_millis(*ms),
+                Trigger::AtBeat(n) => beat.saturating_sub(inner.start_beat) >= *n,
+                Trigger::AfterMs(ms) => now - inner.fired >= Duration::from_millis(*ms),
+                Trigger::AfterBeats(n) => beat.saturating_sub(inner.fired_beat) >= *n,
+                Trigger::Phase(p) => entered_phase && state.phase == *p,
+                Trigger::Vibe(v) => entered_vibe && state.state == *v,
+                Trigger::Event(name) => state.events.iter().any(|e| &e.name == name),
+            };
+            fire.then_some(next)
+        };
+        let fire = match requested {
+            Some(index) => Some(index),
+            None if inner.running => automatic(),
+            None => None,
+        };
+
+        if let Some(index) = fire {
+            let cue = &inner.list.cues[index];
+            if requested.is_some() {
+                // A cue fired by hand re-anchors the show clock, so the
+                // absolute triggers after it keep their spacing
+                if inner.current.is_none() {
+                    inner.started = now;
+                    inner.start_beat = beat;
+                }
+                match cue.trigger {
+                    Trigger::AtMs(ms) => {
+                        inner.started = now.checked_sub(Duration::from_millis(ms)).unwrap_or(now)
+                    }
+                    Trigger::AtBeat(n) => inner.start_beat = beat.saturating_sub(n),
+                    _ => {}
+                }
+            }
+            Self::fire(cue, scenes, store);
+            inner.current = Some(index);
+            inner.fired = now;
+            inner.fired_beat = beat;
+            if index + 1 == inner.list.cues.len() {
+                inner.running = false;
+            }
+        }
+
+        state.cue = Self::publish(inner);
+    }
+
+    fn fire(cue: &Cue, scenes: &SceneEngine, store: &OverrideStore) {
+        info!(event = "cue_fired", cue = %cue.name);
+        let set_by = format!("cue:{}", cue.name);
+
+        for field in &cue.clear_overrides {
+            if field == "*" {
+                store.clear_all();
+            } else {
+                store.clear(field);
+            }
+        }
+        if cue.release_scene {
+            scenes.release(cue.crossfade_ms);
+        }
+        if let Some(scene) = &cue.scene {
+            if let Err(e) = scenes.recall(scene, cue.crossfade_ms) {
+                warn!(event = "cue_failed", cue = %cue.name, error = %e);
+            }
+        }
+
+        let shorthands = [("theme", &cue.theme), ("directive", &cue.directive)];
+        let requests = shorthands
+            .into_iter()
+            .filter_map(|(field, text)| {
+                text.as_ref().map(|t| OverrideRequest {
+                    field: field.to_string(),
+                    value: Some(t.clone().into()),
+                    duration_ms: None,
+                    beats: None,
+                })
+            })
+            .chain(cue.overrides.iter().cloned());
+        for req in requests {
+            if let Err(e) = store.set(req, &set_by) {
+                warn!(event = "cue_failed", cue = %cue.name, error = %e);
+            }
+        }
+    }
+
+    fn publish(inner: &Inner) -> Option<CueStatus> {
+        if inner.list.cues.is_empty() {
+            return None;
+        }
+        let name_at = |i: usize| inner.list.cues.get(i).map(|c| c.name.clone());
+        Some(CueStatus {
+            index: inner.current,
+            name: inner.current.and_then(name_at),
+            next: name_at(inner.current.map_or(0, |i| i + 1)),
+            total: inner.list.cues.len(),
+            running: inner.running,
+            since_ms: inner.current.map_or(0, |_| inner.fired.elapsed().as_millis() as u64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUES: &str = r#"{ "cues": [
+        { "name": "intro", "theme": "INTRO" },
+        { "name": "build", "trigger": { "after_beats": 4 }, "theme": "BUILD" },
+        { "name": "drop", "trigger": { "phase": "Drop" }, "theme": "DROP" }
+    ] }"#;
+
+    fn timeline() -> Timeline {
+        let timeline = Timeline::load(None);
+        timeline.inner.lock().unwrap().list = CueList::parse(CUES).unwrap();
+        timeline
+    }
+
+    fn fired(state: &GlobalState) -> Option<usize> {
+        state.cue.as_ref().and_then(|c| c.index)
+    }
+
+    #[test]
+    fn cues_fire_in_list_order() {
+        let timeline = timeline();
+        // Never saved to, so the file is not created
+        let scenes = SceneEngine::load(std::env::temp_dir().join("timeline-test-scenes.json"));
+        let store = OverrideStore::default();
+        let mut state = GlobalState::default();
+        let step = |state: &mut GlobalState| {
+            timeline.apply(state, &scenes, &store);
+            store.apply(state);
+        };
+
+        // Nothing fires before the first go
+        state.phase = VibePhase::Drop;
+        step(&mut state);
+        assert_eq!(fired(&state), None);
+
+        timeline.go().unwrap();
+        step(&mut state);
+        assert_eq!(fired(&state), Some(0));
+        assert_eq!(state.ai_theme, "INTRO");
+
+        // Only the next cue is watched: entering Drop does not skip ahead
+        state.beat.count = 3;
+        state.phase = VibePhase::Build;
+        step(&mut state);
+        state.phase = VibePhase::Drop;
+        step(&mut state);
+        assert_eq!(fired(&state), Some(0));
+
+        state.beat.count = 4;
+        step(&mut state);
+        assert_eq!(fired(&state), Some(1));
+        assert_eq!(state.ai_theme, "BUILD");
+
+        // Already in Drop: the phase cue waits for the next entry
+        step(&mut state);
+        assert_eq!(fired(&state), Some(1));
+        state.phase = VibePhase::Build;
+        step(&mut state);
+        state.phase = VibePhase::Drop;
+        step(&mut state);
+        assert_eq!(fired(&state), Some(2));
+        assert_eq!(state.ai_theme, "DROP");
+
+        // The show ends with its last cue
+        assert!(state.cue.as_ref().is_some_and(|c| !c.running));
+        assert!(timeline.go().is_err());
+    }
+
+    #[test]
+    fn back_and_jump_refire_cues() {
+        let timeline = timeline();
+        let scenes = SceneEngine::load(std::env::temp_dir().join("timeline-test-scenes.json"));
+        let store = OverrideStore::default();
+        let mut state = GlobalState::default();
+
+        assert!(timeline.back().is_err());
+        timeline.jump(&CueRef::Name("drop".to_string())).unwrap();
+        timeline.apply(&mut state, &scenes, &store);
+        assert_eq!(fired(&state), Some(2));
+
+        timeline.back().unwrap();
+        timeline.apply(&mut state, &scenes, &store);
+        assert_eq!(fired(&state), Some(1));
+
+        assert!(timeline.jump(&CueRef::Index(3)).is_err());
+        assert!(timeline.jump(&CueRef::Name("outro".to_string())).is_err());
+    }
+}
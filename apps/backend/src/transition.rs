@@ -0,0 +1,168 @@
+use crate::color::Palette;
+use crate::state_machine::GlobalState;
+use std::time::{Duration, Instant};
+
+const MAX_FADE_BEATS: f32 = 64.0;
+
+#[derive(Debug, Clone)]
+pub struct TransitionConfig {
+    pub duration: Duration,
+    /// When set, fades last this many beats and start on the next beat.
+    pub beats: Option<f32>,
+}
+
+impl TransitionConfig {
+    /// `PALETTE_FADE_MS` (default 2000, 0 = instant) and `PALETTE_FADE_BEATS`
+    /// (up to 64; anything else is ignored).
+    pub fn from_env() -> Self {
+        let ms = std::env::var("PALETTE_FADE_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(2000);
+        let beats = std::env::var("PALETTE_FADE_BEATS").ok().and_then(|v| parse_beats(&v));
+        Self { duration: Duration::from_millis(ms), beats }
+    }
+}
+
+fn parse_beats(value: &str) -> Option<f32> {
+    value.parse::<f32>().ok().filter(|b| b.is_finite() && *b > 0.0 && *b <= MAX_FADE_BEATS)
+}
+
+struct Fade {
+    from: Palette,
+    queued: Instant,
+    queued_beat: u64,
+    started: Option<Instant>, // None while waiting for the next beat
+    duration: Duration,
+}
+
+/// Crossfades the AI palette in OKLab whenever the director writes a new one.
+/// The director's colours are republished as `ai_target_*_color`; the
+/// `ai_*_color` fields carry the interpolated colours.
+pub struct PaletteTransition {
+    config: TransitionConfig,
+    target: Option<Palette>,
+    shown: Option<Palette>,
+    fade: Option<Fade>,
+}
+
+impl PaletteTransition {
+    pub fn new(config: TransitionConfig) -> Self {
+        Self { config, target: None, shown: None, fade: None }
+    }
+
+    pub fn apply(&mut self, state: &mut GlobalState) {
+        let now = Instant::now();
+        let target = Palette {
+            primary: state.ai_primary_color.clone(),
+            secondary: state.ai_secondary_color.clone(),
+        };
+        state.ai_target_primary_color = target.primary.clone();
+        state.ai_target_secondary_color = target.secondary.clone();
+
+        if self.target.as_ref() != Some(&target) {
+            // A tempo too slow to give a representable length falls back to the fixed time
+            let duration = self
+                .config
+                .beats
+                .filter(|_| state.bpm > 0.0)
+                .and_then(|beats| Duration::try_from_secs_f32(beats * 60.0 / state.bpm).ok())
+                .unwrap_or(self.config.duration);
+            // Fade from what is on screen, which may itself be mid-fade
+            self.fade = self.shown.clone().filter(|_| !duration.is_zero()).map(|from| Fade {
+                from,
+                queued: now,
+                queued_beat: state.beat.count,
+                started: self.config.beats.is_none().then_some(now),
+                duration,
+            });
+            self.target = Some(target.clone());
+        }
+
+        let mut current = target;
+        if let Some(fade) = &mut self.fade {
+            if fade.started.is_none() {
+                // Without a beat clock (silence) start after one beat period anyway
+                let period = Duration::from_secs_f32(60.0 / state.bpm.max(1.0));
+                if state.beat.count != fade.queued_beat || now - fade.queued >= period {
+                    fade.started = Some(now);
+                }
+            }
+            let t = fade
+                .started
+                .map_or(0.0, |start| (now - start).as_secs_f32() / fade.duration.as_secs_f32());
+            if t >= 1.0 {
+                self.fade = None;
+            } else {
+                current = fade.from.lerp(&current, t);
+            }
+        }
+
+        state.ai_primary_color = current.primary.clone();
+        state.ai_secondary_color = current.secondary.clone();
+        self.shown = Some(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(ms: u64, beats: Option<f32>) -> TransitionConfig {
+        TransitionConfig { duration: Duration::from_millis(ms), beats }
+    }
+
+    fn frame(fade: &mut PaletteTransition, primary: &str, bpm: f32, beat: u64) -> GlobalState {
+        let mut state = GlobalState {
+            ai_primary_color: primary.to_string(),
+            ai_secondary_color: "#000000".to_string(),
+            bpm,
+            ..GlobalState::default()
+        };
+        state.beat.count = beat;
+        fade.apply(&mut state);
+        state
+    }
+
+    #[test]
+    fn fades_from_what_is_shown() {
+        let mut fade = PaletteTransition::new(config(10_000, None));
+        assert_eq!(frame(&mut fade, "#FF0000", 120.0, 0).ai_primary_color, "#FF0000");
+
+        let state = frame(&mut fade, "#0000FF", 120.0, 0);
+        assert_eq!(state.ai_target_primary_color, "#0000FF");
+        assert_eq!(state.ai_primary_color, "#FF0000");
+    }
+
+    #[test]
+    fn zero_duration_switches_instantly() {
+        let mut fade = PaletteTransition::new(config(0, None));
+        frame(&mut fade, "#FF0000", 120.0, 0);
+        assert_eq!(frame(&mut fade, "#0000FF", 120.0, 0).ai_primary_color, "#0000FF");
+    }
+
+    #[test]
+    fn beat_fades_wait_for_the_next_beat() {
+        let mut fade = PaletteTransition::new(config(0, Some(64.0)));
+        frame(&mut fade, "#FF0000", 120.0, 0);
+        frame(&mut fade, "#0000FF", 120.0, 0);
+        assert!(fade.fade.as_ref().is_some_and(|f| f.started.is_none()));
+        assert_eq!(fade.fade.as_ref().map(|f| f.duration), Some(Duration::from_secs(32)));
+
+        frame(&mut fade, "#0000FF", 120.0, 1);
+        assert!(fade.fade.as_ref().is_some_and(|f| f.started.is_some()));
+    }
+
+    #[test]
+    fn unrepresentable_beat_lengths_fall_back_to_the_fixed_time() {
+        let mut fade = PaletteTransition::new(config(2000, Some(64.0)));
+        frame(&mut fade, "#FF0000", f32::MIN_POSITIVE, 0);
+        frame(&mut fade, "#0000FF", f32::MIN_POSITIVE, 0);
+        assert_eq!(fade.fade.as_ref().map(|f| f.duration), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn fade_beats_must_be_finite_and_in_range() {
+        assert_eq!(parse_beats("4"), Some(4.0));
+        for value in ["0", "-2", "NaN", "inf", "1e30", "65", "four"] {
+            assert_eq!(parse_beats(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Legacy behaviour: a 0.1 change in mean energy across the ~4 s between the
+// ends of a 5 s window, i.e. about 0.025 per second
+const DEFAULT_SLOPE_THRESHOLD: f32 = 0.025;
+const DEFAULT_WINDOWS_MS: [u64; 3] = [1500, 5000, 20000];
+
+/// Serialized as the strings clients already know ("RISING" etc.).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TrendDirection {
+    Rising,
+    Falling,
+    #[default]
+    Stable,
+}
+
+impl TrendDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrendDirection::Rising => "RISING",
+            TrendDirection::Falling => "FALLING",
+            TrendDirection::Stable => "STABLE",
+        }
+    }
+}
+
+/// Least-squares trend of the mean band energy over one horizon.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct EnergyTrend {
+    pub direction: TrendDirection,
+    /// Energy change per second.
+    pub slope: f32,
+    /// Change of slope per second (late half of the window vs. early half).
+    pub acceleration: f32,
+    /// Configured horizon.
+    pub window_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct EnergyTrends {
+    pub short: EnergyTrend,
+    pub medium: EnergyTrend,
+    pub long: EnergyTrend,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrendConfig {
+    pub windows: [Duration; 3], // short, medium, long
+    pub slope_threshold: f32,
+}
+
+impl TrendConfig {
+    /// `TREND_WINDOWS_MS="short,medium,long"` and `TREND_SLOPE_THRESHOLD` (per second).
+    pub fn from_env() -> Self {
+        let windows = std::env::var("TREND_WINDOWS_MS")
+            .ok()
+            .and_then(|v| {
+                let ms: Vec<u64> = v.split(',').filter_map(|s| s.trim().parse().ok()).collect();
+                <[u64; 3]>::try_from(ms).ok()
+            })
+            .filter(|ms| ms.iter().all(|m| *m > 0))
+            .unwrap_or(DEFAULT_WINDOWS_MS);
+        let slope_threshold = std::env::var("TREND_SLOPE_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SLOPE_THRESHOLD);
+
+        Self { windows: windows.map(Duration::from_millis), slope_threshold }
+    }
+}
+
+/// Keeps enough energy history for the longest horizon and fits each one.
+pub struct TrendAnalyzer {
+    config: TrendConfig,
+    history: VecDeque<(Duration, f32)>,
+}
+
+impl TrendAnalyzer {
+    pub fn new(config: TrendConfig) -> Self {
+        Self { config, history: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, now: Duration, energy: f32) {
+        let keep = self.config.windows.iter().max().copied().unwrap_or_default();
+        while self.history.front().is_some_and(|(t, _)| now.saturating_sub(*t) > keep) {
+            self.history.pop_front();
+        }
+        self.history.push_back((now, energy));
+    }
+
+    pub fn trends(&self, now: Duration) -> EnergyTrends {
+        let [short, medium, long] = self.config.windows.map(|w| self.fit(now, w));
+        EnergyTrends { short, medium, long }
+    }
+
+    fn fit(&self, now: Duration, window: Duration) -> EnergyTrend {
+        let start = now.saturating_sub(window);
+        let points: Vec<(f32, f32)> = self
+            .history
+            .iter()
+            .filter(|(t, _)| *t >= start)
+            .map(|(t, e)| (t.saturating_sub(start).as_secs_f32(), *e))
+            .collect();
+
+        let mut trend = EnergyTrend { window_ms: window.as_millis() as u64, ..Default::default() };
+        // Until half the horizon is covered the fit is too noisy to call a direction
+        let covered = points.first().zip(points.last()).map(|(a, b)| b.0 - a.0).unwrap_or(0.0);
+        if covered < window.as_secs_f32() / 2.0 {
+            return trend;
+        }
+
+        let Some(slope) = regression_slope(&points) else { return trend };
+        let half = window.as_secs_f32() / 2.0;
+        let (early, late): (Vec<_>, Vec<_>) = points.iter().copied().partition(|(x, _)| *x < half);
+        if let (Some(a), Some(b)) = (regression_slope(&early), regression_slope(&late)) {
+            trend.acceleration = (b - a) / half;
+        }
+
+        trend.slope = slope;
+        trend.direction = if slope > self.config.slope_threshold {
+            TrendDirection::Rising
+        } else if slope < -self.config.slope_threshold {
+            TrendDirection::Falling
+        } else {
+            TrendDirection::Stable
+        };
+        trend
+    }
+}
+
+/// Ordinary least-squares slope of `y` over `x`.
+fn regression_slope(points: &[(f32, f32)]) -> Option<f32> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f32;
+    let mean_x = points.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f32>() / n;
+    let (mut cov, mut var) = (0.0f32, 0.0f32);
+    for (x, y) in points {
+        cov += (x - mean_x) * (y - mean_y);
+        var += (x - mean_x) * (x - mean_x);
+    }
+    (var > 0.0).then(|| cov / var)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyzer() -> TrendAnalyzer {
+        TrendAnalyzer::new(TrendConfig {
+            windows: [1000, 4000, 10000].map(Duration::from_millis),
+            slope_threshold: 0.025,
+        })
+    }
+
+    // Feeds `energy(t)` every 100 ms up to `until_ms`
+    fn feed(trend: &mut TrendAnalyzer, from_ms: u64, until_ms: u64, energy: impl Fn(f32) -> f32) {
+        for ms in (from_ms..=until_ms).step_by(100) {
+            trend.push(Duration::from_millis(ms), energy(ms as f32 / 1000.0));
+        }
+    }
+
+    #[test]
+    fn fits_the_slope_of_a_ramp() {
+        let mut trend = analyzer();
+        feed(&mut trend, 0, 10_000, |t| 0.1 + 0.05 * t);
+        let trends = trend.trends(Duration::from_secs(10));
+        for t in [trends.short, trends.medium, trends.long] {
+            assert!((t.slope - 0.05).abs() < 1e-3, "{:?}", t);
+            assert!(t.acceleration.abs() < 1e-2, "{:?}", t);
+            assert_eq!(t.direction, TrendDirection::Rising);
+        }
+        assert_eq!(trends.medium.window_ms, 4000);
+    }
+
+    #[test]
+    fn horizons_see_different_trends() {
+        let mut trend = analyzer();
+        // Flat for 9 s, then a steep fall in the last second
+        feed(&mut trend, 0, 9000, |_| 0.5);
+        feed(&mut trend, 9100, 10_000, |t| 0.5 - 0.3 * (t - 9.0));
+        let trends = trend.trends(Duration::from_secs(10));
+        assert_eq!(trends.short.direction, TrendDirection::Falling);
+        assert!((trends.short.slope + 0.3).abs() < 0.05, "{:?}", trends.short);
+        assert_eq!(trends.long.direction, TrendDirection::Stable);
+        assert!(trends.long.acceleration < 0.0);
+    }
+
+    #[test]
+    fn small_slopes_are_stable() {
+        let mut trend = analyzer();
+        feed(&mut trend, 0, 10_000, |t| 0.5 + 0.01 * t);
+        let long = trend.trends(Duration::from_secs(10)).long;
+        assert!(long.slope > 0.0);
+        assert_eq!(long.direction, TrendDirection::Stable);
+    }
+
+    #[test]
+    fn waits_until_half_the_horizon_is_covered() {
+        let mut trend = analyzer();
+        feed(&mut trend, 0, 1900, |t| t);
+        let trends = trend.trends(Duration::from_millis(1900));
+        assert_eq!(trends.short.direction, TrendDirection::Rising);
+        assert_eq!(trends.long, EnergyTrend { window_ms: 10_000, ..EnergyTrend::default() });
+    }
+
+    #[test]
+    fn keeps_only_the_longest_horizon() {
+        let mut trend = analyzer();
+        feed(&mut trend, 0, 30_000, |_| 0.5);
+        assert!(trend.history.len() <= 101);
+        assert_eq!(trend.history.front().unwrap().0, Duration::from_secs(20));
+    }
+}
@@ -0,0 +1,492 @@
+use crate::state_machine::{VibePhase, VibeState};
+use crate::trend::TrendDirection;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Published in `GlobalState` whenever the vibe or phase changes. `seq` increases
+/// by one per transition so clients that skip frames can still tell a new one apart.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Transition<S> {
+    pub seq: u64,
+    pub from: S,
+    pub to: S,
+    pub reason: String,
+    pub at_ms: u64,   // Pipeline clock
+    pub unix_ms: u64, // Wall clock
+}
+
+pub type VibeTransition = Transition<VibeState>;
+pub type PhaseTransition = Transition<VibePhase>;
+
+impl<S> Transition<S> {
+    fn new(seq: u64, from: S, to: S, reason: String, now: Duration) -> Self {
+        Self {
+            seq,
+            from,
+            to,
+            reason,
+            at_ms: now.as_millis() as u64,
+            unix_ms: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+                as u64,
+        }
+    }
+}
+
+/// Entry thresholds escalate, lower exit thresholds de-escalate (hysteresis).
+/// A candidate state must hold for its confirm time, and the current state
+/// must have lasted its minimum dwell, before a transition is taken.
+/// Loaded from the `vibe` section of the rule file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VibeConfig {
+    pub chaos_enter_flux: f32,
+    pub chaos_enter_low: f32,
+    pub chaos_exit_flux: f32,
+    pub chaos_exit_low: f32,
+    pub build_enter_flux: f32,
+    pub build_enter_mid: f32,
+    pub build_exit_flux: f32,
+    pub build_exit_mid: f32,
+    pub escalate_confirm_ms: u64,
+    pub release_confirm_ms: u64,
+    pub min_dwell_chaos_ms: u64,
+    pub min_dwell_build_ms: u64,
+    pub min_dwell_chill_ms: u64,
+}
+
+impl Default for VibeConfig {
+    fn default() -> Self {
+        Self {
+            chaos_enter_flux: 0.6,
+            chaos_enter_low: 0.8,
+            chaos_exit_flux: 0.45,
+            chaos_exit_low: 0.65,
+            build_enter_flux: 0.3,
+            build_enter_mid: 0.5,
+            build_exit_flux: 0.2,
+            build_exit_mid: 0.4,
+            escalate_confirm_ms: 80,
+            release_confirm_ms: 500,
+            min_dwell_chaos_ms: 2000,
+            min_dwell_build_ms: 1000,
+            min_dwell_chill_ms: 500,
+        }
+    }
+}
+
+impl VibeConfig {
+    fn min_dwell(&self, state: VibeState) -> Duration {
+        Duration::from_millis(match state {
+            VibeState::Chaos => self.min_dwell_chaos_ms,
+            VibeState::Build => self.min_dwell_build_ms,
+            VibeState::Chill => self.min_dwell_chill_ms,
+        })
+    }
+
+    /// Exit thresholds must sit at or below their entry thresholds, or the
+    /// machine would leave a state it is still entitled to.
+    pub fn validate(&self) -> Result<(), String> {
+        let pairs = [
+            ("chaos flux", self.chaos_enter_flux, self.chaos_exit_flux),
+            ("chaos low", self.chaos_enter_low, self.chaos_exit_low),
+            ("build flux", self.build_enter_flux, self.build_exit_flux),
+            ("build mid", self.build_enter_mid, self.build_exit_mid),
+        ];
+        for (name, enter, exit) in pairs {
+            if exit > enter {
+                return Err(format!("{} exit threshold {} is above entry {}", name, exit, enter));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct VibeMachine {
+    config: VibeConfig,
+    current: VibeState,
+    entered_at: Duration,
+    candidate: Option<(VibeState, Duration)>,
+    seq: u64,
+    last_transition: Option<VibeTransition>,
+}
+
+impl VibeMachine {
+    pub fn new(config: VibeConfig) -> Self {
+        Self {
+            config,
+            current: VibeState::Chill,
+            entered_at: Duration::ZERO,
+            candidate: None,
+            seq: 0,
+            last_transition: None,
+        }
+    }
+
+    pub fn last_transition(&self) -> Option<&VibeTransition> {
+        self.last_transition.as_ref()
+    }
+
+    /// Swaps thresholds in place (rule hot reload); the current state is kept.
+    pub fn set_config(&mut self, config: VibeConfig) {
+        self.config = config;
+    }
+
+    pub fn update(&mut self, now: Duration, low: f32, mid: f32, flux: f32) -> VibeState {
+        let (desired, reason) = self.desired(low, mid, flux);
+
+        if desired == self.current {
+            self.candidate = None;
+            return self.current;
+        }
+
+        let since = match self.candidate {
+            Some((state, since)) if state == desired => since,
+            _ => {
+                self.candidate = Some((desired, now));
+                now
+            }
+        };
+
+        let confirm = Duration::from_millis(if rank(desired) > rank(self.current) {
+            self.config.escalate_confirm_ms
+        } else {
+            self.config.release_confirm_ms
+        });
+        let dwelled = now.saturating_sub(self.entered_at) >= self.config.min_dwell(self.current);
+
+        if dwelled && now.saturating_sub(since) >= confirm {
+            self.transition(now, desired, reason);
+        }
+        self.current
+    }
+
+    fn desired(&self, low: f32, mid: f32, flux: f32) -> (VibeState, String) {
+        let c = &self.config;
+        let in_chaos = self.current == VibeState::Chaos;
+        let in_build_or_above = rank(self.current) >= rank(VibeState::Build);
+
+        let above = |name: &str, value: f32, threshold: f32| {
+            format!("{} {:.2} > {:.2}", name, value, threshold)
+        };
+
+        if flux > c.chaos_enter_flux {
+            return (VibeState::Chaos, above("spectral_flux", flux, c.chaos_enter_flux));
+        }
+        if low > c.chaos_enter_low {
+            return (VibeState::Chaos, above("low_energy", low, c.chaos_enter_low));
+        }
+        if in_chaos && (flux > c.chaos_exit_flux || low > c.chaos_exit_low) {
+            return (VibeState::Chaos, String::new());
+        }
+        if flux > c.build_enter_flux {
+            return (VibeState::Build, above("spectral_flux", flux, c.build_enter_flux));
+        }
+        if mid > c.build_enter_mid {
+            return (VibeState::Build, above("mid_energy", mid, c.build_enter_mid));
+        }
+        if in_build_or_above && (flux > c.build_exit_flux || mid > c.build_exit_mid) {
+            let reason = if in_chaos { "energy below chaos exit thresholds" } else { "" };
+            return (VibeState::Build, reason.to_string());
+        }
+        (VibeState::Chill, "energy below build exit thresholds".to_string())
+    }
+
+    fn transition(&mut self, now: Duration, to: VibeState, reason: String) {
+        self.seq += 1;
+        let event = Transition::new(self.seq, self.current, to, reason, now);
+
+        #[cfg(debug_assertions)]
+        println!("[STATE] Vibe {:?} -> {:?} ({})", event.from, event.to, event.reason);
+
+        self.current = to;
+        self.entered_at = now;
+        self.candidate = None;
+        self.last_transition = Some(event);
+    }
+}
+
+fn rank(state: VibeState) -> u8 {
+    match state {
+        VibeState::Chill => 0,
+        VibeState::Build => 1,
+        VibeState::Chaos => 2,
+    }
+}
+
+// --- Song Phase Tracking ---
+
+/// Thresholds for the phase tracker. Energies are the smoothed band average.
+#[derive(Debug, Clone)]
+pub struct PhaseConfig {
+    pub idle_level: f32,
+    pub idle_hold: Duration,
+    /// Time constant of the long-term energy average used for structure.
+    pub structure_window: Duration,
+    /// Breakdown becomes Outro once energy sits below this fraction of the
+    /// long-term average for `outro_hold`.
+    pub outro_ratio: f32,
+    pub outro_hold: Duration,
+    pub min_dwell: Duration,
+}
+
+impl Default for PhaseConfig {
+    fn default() -> Self {
+        Self {
+            idle_level: 0.02,
+            idle_hold: Duration::from_secs(3),
+            structure_window: Duration::from_secs(30),
+            outro_ratio: 0.6,
+            outro_hold: Duration::from_secs(20),
+            min_dwell: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Derives the musical phase from the coarse vibe, the energy trend and a
+/// long-term energy average. Drops come from Chaos; what follows a drop
+/// depends on whether energy falls away (Breakdown, then Outro) or rebuilds.
+pub struct PhaseTracker {
+    config: PhaseConfig,
+    current: VibePhase,
+    entered_at: Duration,
+    last_update: Option<Duration>,
+    long_avg: f32,
+    quiet_since: Option<Duration>,
+    low_since: Option<Duration>,
+    had_drop: bool,
+    seq: u64,
+    last_transition: Option<PhaseTransition>,
+}
+
+impl PhaseTracker {
+    pub fn new(config: PhaseConfig) -> Self {
+        Self {
+            config,
+            current: VibePhase::Idle,
+            entered_at: Duration::ZERO,
+            last_update: None,
+            long_avg: 0.0,
+            quiet_since: None,
+            low_since: None,
+            had_drop: false,
+            seq: 0,
+            last_transition: None,
+        }
+    }
+
+    pub fn last_transition(&self) -> Option<&PhaseTransition> {
+        self.last_transition.as_ref()
+    }
+
+    pub fn update(
+        &mut self,
+        now: Duration,
+        vibe: VibeState,
+        trend: TrendDirection,
+        energy: f32,
+    ) -> VibePhase {
+        let dt = self.last_update.map(|t| now.saturating_sub(t).as_secs_f32()).unwrap_or(0.0);
+        self.last_update = Some(now);
+        let window = self.config.structure_window.as_secs_f32().max(0.001);
+        self.long_avg += (energy - self.long_avg) * (1.0 - (-dt / window).exp());
+
+        let quiet = energy < self.config.idle_level;
+        self.quiet_since = if quiet { self.quiet_since.or(Some(now)) } else { None };
+        let low = energy < self.long_avg * self.config.outro_ratio;
+        self.low_since = if low { self.low_since.or(Some(now)) } else { None };
+
+        if let Some((to, reason)) = self.next(now, vibe, trend) {
+            let dwelled = now.saturating_sub(self.entered_at) >= self.config.min_dwell;
+            // Silence and drops are taken immediately, everything else respects the dwell
+            if dwelled || to == VibePhase::Idle || to == VibePhase::Drop {
+                self.transition(now, to, reason);
+            }
+        }
+        self.current
+    }
+
+    fn next(
+        &self,
+        now: Duration,
+        vibe: VibeState,
+        trend: TrendDirection,
+    ) -> Option<(VibePhase, String)> {
+        let held = |since: Option<Duration>, hold: Duration| {
+            since.is_some_and(|t| now.saturating_sub(t) >= hold)
+        };
+        let rising = trend == TrendDirection::Rising;
+        let falling = trend == TrendDirection::Falling;
+
+        let silent = held(self.quiet_since, self.config.idle_hold);
+        let fading = self.had_drop && held(self.low_since, self.config.outro_hold);
+
+        let to = match self.current {
+            _ if self.current != VibePhase::Idle && silent => {
+                (VibePhase::Idle, "signal below idle level".to_string())
+            }
+            VibePhase::Idle if self.quiet_since.is_none() => {
+                (VibePhase::Intro, "signal detected".to_string())
+            }
+            VibePhase::Idle => return None,
+            VibePhase::Drop if vibe != VibeState::Chaos => {
+                if falling || vibe == VibeState::Chill {
+                    (VibePhase::Breakdown, "energy released after drop".to_string())
+                } else {
+                    (VibePhase::Build, "energy rebuilding after drop".to_string())
+                }
+            }
+            VibePhase::Drop => return None,
+            _ if vibe == VibeState::Chaos => {
+                (VibePhase::Drop, format!("chaos reached from {:?}", self.current))
+            }
+            VibePhase::Intro | VibePhase::Breakdown | VibePhase::Outro
+                if vibe == VibeState::Build || rising =>
+            {
+                (VibePhase::Build, "energy building".to_string())
+            }
+            VibePhase::Breakdown if fading => {
+                (VibePhase::Outro, "energy below long-term average".to_string())
+            }
+            VibePhase::Build if vibe == VibeState::Chill && !rising => {
+                if self.had_drop {
+                    (VibePhase::Breakdown, "build collapsed".to_string())
+                } else {
+                    (VibePhase::Intro, "build collapsed before first drop".to_string())
+                }
+            }
+            _ => return None,
+        };
+        Some(to)
+    }
+
+    fn transition(&mut self, now: Duration, to: VibePhase, reason: String) {
+        if to == VibePhase::Drop {
+            self.had_drop = true;
+        }
+        if to == VibePhase::Idle {
+            // A new track or set starts from scratch after silence
+            self.had_drop = false;
+        }
+
+        self.seq += 1;
+        let event = Transition::new(self.seq, self.current, to, reason, now);
+
+        #[cfg(debug_assertions)]
+        println!("[STATE] Phase {:?} -> {:?} ({})", event.from, event.to, event.reason);
+
+        self.current = to;
+        self.entered_at = now;
+        self.last_transition = Some(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn escalation_waits_for_its_confirm_time() {
+        let mut vibe = VibeMachine::new(VibeConfig::default());
+        assert_eq!(vibe.update(ms(1000), 0.0, 0.0, 0.7), VibeState::Chill);
+        assert_eq!(vibe.update(ms(1050), 0.0, 0.0, 0.7), VibeState::Chill);
+        assert_eq!(vibe.update(ms(1080), 0.0, 0.0, 0.7), VibeState::Chaos);
+
+        let t = vibe.last_transition().unwrap();
+        assert_eq!((t.seq, t.from, t.to), (1, VibeState::Chill, VibeState::Chaos));
+        assert_eq!(t.reason, "spectral_flux 0.70 > 0.60");
+    }
+
+    #[test]
+    fn exit_thresholds_hold_the_state() {
+        let mut vibe = VibeMachine::new(VibeConfig::default());
+        vibe.update(ms(1000), 0.0, 0.0, 0.7);
+        vibe.update(ms(1100), 0.0, 0.0, 0.7);
+        assert_eq!(vibe.last_transition().unwrap().to, VibeState::Chaos);
+
+        // Below entry but above exit: stays in Chaos long past dwell and confirm
+        for t in (2000..6000).step_by(100) {
+            assert_eq!(vibe.update(ms(t), 0.0, 0.0, 0.5), VibeState::Chaos);
+        }
+        // Below exit, still above Build entry
+        vibe.update(ms(6000), 0.0, 0.0, 0.35);
+        assert_eq!(vibe.update(ms(6500), 0.0, 0.0, 0.35), VibeState::Build);
+    }
+
+    #[test]
+    fn minimum_dwell_delays_the_release() {
+        let mut vibe = VibeMachine::new(VibeConfig::default());
+        vibe.update(ms(1000), 0.0, 0.0, 0.7);
+        assert_eq!(vibe.update(ms(1100), 0.0, 0.0, 0.7), VibeState::Chaos);
+
+        // Quiet right after entering: confirm passes at 1700 ms, dwell only at 3100 ms
+        for t in (1200..3100).step_by(100) {
+            assert_eq!(vibe.update(ms(t), 0.0, 0.0, 0.0), VibeState::Chaos);
+        }
+        assert_eq!(vibe.update(ms(3100), 0.0, 0.0, 0.0), VibeState::Chill);
+    }
+
+    #[test]
+    fn a_changing_candidate_restarts_the_confirm_time() {
+        let mut vibe = VibeMachine::new(VibeConfig::default());
+        vibe.update(ms(1000), 0.0, 0.6, 0.0); // Build candidate
+        vibe.update(ms(1060), 0.0, 0.0, 0.7); // Chaos candidate from here
+        assert_eq!(vibe.update(ms(1100), 0.0, 0.0, 0.7), VibeState::Chill);
+        assert_eq!(vibe.update(ms(1140), 0.0, 0.0, 0.7), VibeState::Chaos);
+    }
+
+    #[test]
+    fn rejects_exit_thresholds_above_entry() {
+        let config = VibeConfig { chaos_exit_flux: 0.7, ..VibeConfig::default() };
+        assert!(config.validate().is_err());
+        assert!(VibeConfig::default().validate().is_ok());
+    }
+
+    fn secs(s: f32) -> Duration {
+        Duration::from_secs_f32(s)
+    }
+
+    #[test]
+    fn phases_follow_the_vibe_and_respect_the_dwell() {
+        let mut phase = PhaseTracker::new(PhaseConfig::default());
+        let stable = TrendDirection::Stable;
+        assert_eq!(phase.update(secs(10.0), VibeState::Chill, stable, 0.5), VibePhase::Intro);
+
+        // Build needs the 2 s dwell in Intro
+        assert_eq!(phase.update(secs(11.0), VibeState::Build, stable, 0.5), VibePhase::Intro);
+        assert_eq!(phase.update(secs(12.0), VibeState::Build, stable, 0.5), VibePhase::Build);
+
+        // Drops are taken at once
+        assert_eq!(phase.update(secs(12.1), VibeState::Chaos, stable, 0.9), VibePhase::Drop);
+        let t = phase.last_transition().unwrap();
+        assert_eq!(t.reason, "chaos reached from Build");
+
+        assert_eq!(phase.update(secs(13.0), VibeState::Chill, stable, 0.3), VibePhase::Drop);
+        assert_eq!(phase.update(secs(14.1), VibeState::Chill, stable, 0.3), VibePhase::Breakdown);
+        assert_eq!(phase.last_transition().unwrap().seq, 4);
+    }
+
+    #[test]
+    fn a_rebuild_after_the_drop_goes_back_to_build() {
+        let mut phase = PhaseTracker::new(PhaseConfig::default());
+        phase.update(secs(10.0), VibeState::Chill, TrendDirection::Stable, 0.5);
+        phase.update(secs(10.5), VibeState::Chaos, TrendDirection::Stable, 0.9);
+        let next = phase.update(secs(13.0), VibeState::Build, TrendDirection::Rising, 0.7);
+        assert_eq!(next, VibePhase::Build);
+        assert_eq!(phase.last_transition().unwrap().reason, "energy rebuilding after drop");
+    }
+
+    #[test]
+    fn sustained_silence_returns_to_idle() {
+        let mut phase = PhaseTracker::new(PhaseConfig::default());
+        phase.update(secs(10.0), VibeState::Chill, TrendDirection::Stable, 0.5);
+        phase.update(secs(20.0), VibeState::Chill, TrendDirection::Stable, 0.0);
+        let held = phase.update(secs(22.9), VibeState::Chill, TrendDirection::Stable, 0.0);
+        assert_eq!(held, VibePhase::Intro);
+        let idle = phase.update(secs(23.0), VibeState::Chill, TrendDirection::Stable, 0.0);
+        assert_eq!(idle, VibePhase::Idle);
+    }
+}
@@ -1,32 +1,70 @@
+use crate::auth::Operator;
+use crate::crowd::{ClientTelemetry, CrowdMonitor};
+use crate::history::{HistoryQuery, StateHistory};
+use crate::lfo::{LfoBank, LfoSet};
+use crate::overrides::OverrideRequest;
+use crate::params::ParamSchema;
+use crate::recorder::Recorder;
+use crate::rules::RuleEngine;
+use crate::scenes::{Scene, SceneBinding};
+use crate::show::ShowControl;
+use crate::spectrum::{SpectrumFrame, SpectrumQuery};
 use crate::state_machine::GlobalState;
+use crate::timeline::CueRef;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Path, Query, State,
     },
+    http::StatusCode,
     response::IntoResponse,
-    routing::get,
-    Router,
+    routing::{delete, get, post, put},
+    Json, Router,
 };
 use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
 use futures_util::{stream::StreamExt, SinkExt};
 
 pub struct AppState {
+    pub zone: String,
+    pub device: String,
     pub tx: broadcast::Sender<GlobalState>,
+    pub tx_spectrum: broadcast::Sender<Arc<SpectrumFrame>>,
     pub director: Arc<crate::llm_engine::LlmDirector>,
+    pub recorder: Arc<Recorder>,
+    pub rules: Arc<RuleEngine>,
+    pub lfos: Arc<LfoBank>,
+    pub show: Arc<ShowControl>,
+    pub history: Arc<StateHistory>,
+    pub crowd: Arc<CrowdMonitor>,
 }
 
-pub async fn start_server(
-    tx: broadcast::Sender<GlobalState>,
-    director: Arc<crate::llm_engine::LlmDirector>,
-) {
-    let app_state = Arc::new(AppState { tx, director });
+/// Serves every zone under `/ws/{zone}` and `/api/v1/zones/{zone}/...`. The
+/// first zone also answers on the unprefixed routes, so single-zone clients
+/// keep working unchanged.
+pub async fn start_server(zones: Vec<AppState>) {
+    let zones: Vec<Arc<AppState>> = zones.into_iter().map(Arc::new).collect();
+    let listing: Vec<serde_json::Value> = zones
+        .iter()
+        .enumerate()
+        .map(|(i, z)| serde_json::json!({ "name": z.zone, "device": z.device, "default": i == 0 }))
+        .collect();
 
-    let app = Router::new()
-        .route("/ws", get(ws_handler))
-        .route("/api/v1/ai/metrics", get(metrics_handler))
-        .with_state(app_state);
+    let default_zone = zones[0].clone();
+    let listing = Json(serde_json::json!({ "success": true, "data": listing }));
+    let mut app = Router::new()
+        .route("/api/v1/zones", get(move || async move { listing.clone() }))
+        .merge(stream_routes("/ws").with_state(default_zone.clone()))
+        .nest("/api/v1", api_routes().with_state(default_zone));
+    for zone in &zones {
+        app = app
+            .merge(stream_routes(&format!("/ws/{}", zone.zone)).with_state(zone.clone()))
+            .nest(
+                &format!("/api/v1/zones/{}", zone.zone),
+                api_routes().with_state(zone.clone()),
+            );
+    }
 
     let port = std::env::var("PORT").expect("PORT environment variable must be set");
     let addr: SocketAddr = format!("0.0.0.0:{}", port).parse().unwrap();
@@ -36,29 +74,408 @@ pub async fn start_server(
     axum::serve(listener, app).await.unwrap();
 }
 
+fn stream_routes(prefix: &str) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(prefix, get(ws_handler))
+        .route(&format!("{}/spectrum", prefix), get(spectrum_ws_handler))
+}
+
+/// Per-zone API, mounted under `/api/v1` and `/api/v1/zones/{zone}`.
+fn api_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ai/metrics", get(metrics_handler))
+        .route("/recorder", get(recorder_status_handler))
+        .route("/recorder/start", post(recorder_start_handler))
+        .route("/recorder/stop", post(recorder_stop_handler))
+        .route("/rules", get(rules_status_handler))
+        .route("/rules/reload", post(rules_reload_handler))
+        .route(
+            "/overrides",
+            get(overrides_list_handler).post(override_set_handler).delete(overrides_clear_handler),
+        )
+        .route("/overrides/:field", delete(override_clear_handler))
+        .route("/scenes", get(scenes_list_handler))
+        .route("/scenes/release", post(scene_release_handler))
+        .route("/scenes/:name", put(scene_save_handler).delete(scene_delete_handler))
+        .route("/scenes/:name/capture", post(scene_capture_handler))
+        .route("/scenes/:name/recall", post(scene_recall_handler))
+        .route("/scene-bindings", put(scene_bindings_handler))
+        .route("/params/schema", get(params_schema_handler))
+        .route("/history", get(history_handler))
+        .route("/crowd", get(crowd_handler))
+        .route("/lfos", get(lfos_list_handler).put(lfos_replace_handler))
+        .route("/lfos/reload", post(lfos_reload_handler))
+        .route("/timeline", get(timeline_handler))
+        .route("/timeline/go", post(timeline_go_handler))
+        .route("/timeline/back", post(timeline_back_handler))
+        .route("/timeline/jump", post(timeline_jump_handler))
+        .route("/timeline/stop", post(timeline_stop_handler))
+        .route("/timeline/reload", post(timeline_reload_handler))
+}
+
 async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let metrics = state.director.metrics.get_snapshot();
     axum::Json(metrics)
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+#[derive(serde::Deserialize, Debug, Default)]
+struct RecorderStartRequest {
+    label: Option<String>,
+}
+
+async fn recorder_status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.recorder.status())
+}
+
+async fn recorder_start_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<RecorderStartRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    match state.recorder.start(req.label).await {
+        Ok(()) => {
+            tracing::info!(event = "recorder_started", by = %operator.username);
+            (StatusCode::OK, Json(serde_json::json!(state.recorder.status())))
+        }
+        Err(e) => api_error(StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+async fn recorder_stop_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.recorder.stop() {
+        Ok(()) => {
+            tracing::info!(event = "recorder_stopped", by = %operator.username);
+            (StatusCode::OK, Json(serde_json::json!(state.recorder.status())))
+        }
+        Err(e) => api_error(StatusCode::SERVICE_UNAVAILABLE, e),
+    }
+}
+
+async fn rules_status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.rules.status())
+}
+
+async fn rules_reload_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.rules.reload() {
+        Ok(()) => {
+            tracing::info!(event = "rules_reloaded", by = %operator.username);
+            (StatusCode::OK, Json(serde_json::json!({ "success": true })))
+        }
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
+}
+
+async fn params_schema_handler() -> impl IntoResponse {
+    Json(serde_json::json!({ "success": true, "data": ParamSchema::global().params }))
+}
+
+async fn overrides_list_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.show.overrides.list())
+}
+
+async fn override_set_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<OverrideRequest>,
+) -> impl IntoResponse {
+    match state.show.overrides.set(req, &operator.username) {
+        Ok(active) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "data": active }))),
+        Err(e) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() })),
+        ),
+    }
+}
+
+async fn override_clear_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Path(field): Path<String>,
+) -> impl IntoResponse {
+    if state.show.overrides.clear(&field) {
+        (StatusCode::OK, Json(serde_json::json!({ "success": true })))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "success": false, "error": "No override for that field" })),
+        )
+    }
+}
+
+async fn overrides_clear_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let cleared = state.show.overrides.clear_all();
+    Json(serde_json::json!({ "success": true, "data": { "cleared": cleared } }))
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct SceneFadeRequest {
+    crossfade_ms: Option<u64>,
+}
+
+fn api_error(status: StatusCode, e: impl ToString) -> (StatusCode, Json<serde_json::Value>) {
+    let code = status.as_u16();
+    (status, Json(serde_json::json!({ "success": false, "error": e.to_string(), "code": code })))
+}
+
+async fn scenes_list_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let bank = state.show.scenes.bank();
+    let active = state.show.scenes.active();
+    Json(serde_json::json!({
+        "success": true,
+        "data": { "scenes": bank.scenes, "bindings": bank.bindings, "active": active }
+    }))
+}
+
+async fn scene_save_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(scene): Json<Scene>,
+) -> impl IntoResponse {
+    match state.show.scenes.save(&name, scene.clone()) {
+        Ok(()) => {
+            tracing::info!(event = "scene_saved", scene = %name, by = %operator.username);
+            (StatusCode::OK, Json(serde_json::json!({ "success": true, "data": scene })))
+        }
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
+}
+
+async fn scene_capture_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    body: Option<Json<SceneFadeRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    match state.show.scenes.capture(&name, req.crossfade_ms.unwrap_or(0)) {
+        Ok(scene) => {
+            tracing::info!(event = "scene_captured", scene = %name, by = %operator.username);
+            (StatusCode::OK, Json(serde_json::json!({ "success": true, "data": scene })))
+        }
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
+}
+
+async fn scene_delete_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.show.scenes.delete(&name) {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Ok(false) => api_error(StatusCode::NOT_FOUND, "No scene with that name"),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn scene_recall_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    body: Option<Json<SceneFadeRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    match state.show.scenes.recall(&name, req.crossfade_ms) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => api_error(StatusCode::NOT_FOUND, e),
+    }
+}
+
+async fn scene_release_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<SceneFadeRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    state.show.scenes.release(req.crossfade_ms);
+    Json(serde_json::json!({ "success": true }))
+}
+
+async fn scene_bindings_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Json(bindings): Json<Vec<SceneBinding>>,
+) -> impl IntoResponse {
+    match state.show.scenes.set_bindings(bindings) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
 }
 
-// Incoming Telemetry Schema
 #[derive(serde::Deserialize, Debug)]
-struct ClientTelemetry {
-    boredom_score: f32,
-    // Add other cues here: human_activity, etc.
+#[serde(deny_unknown_fields)]
+struct CueJumpRequest {
+    cue: CueRef,
+}
+
+fn timeline_result(result: anyhow::Result<()>) -> (StatusCode, Json<serde_json::Value>) {
+    match result {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => api_error(StatusCode::CONFLICT, e),
+    }
+}
+
+async fn timeline_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let timeline = &state.show.timeline;
+    Json(serde_json::json!({
+        "success": true,
+        "data": { "cues": timeline.cues(), "status": timeline.status() }
+    }))
 }
 
-async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
+async fn timeline_go_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    timeline_result(state.show.timeline.go())
+}
+
+async fn timeline_back_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    timeline_result(state.show.timeline.back())
+}
+
+async fn timeline_jump_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CueJumpRequest>,
+) -> impl IntoResponse {
+    match state.show.timeline.jump(&req.cue) {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true }))),
+        Err(e) => api_error(StatusCode::NOT_FOUND, e),
+    }
+}
+
+async fn timeline_stop_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    state.show.timeline.stop();
+    Json(serde_json::json!({ "success": true }))
+}
+
+async fn timeline_reload_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.show.timeline.reload() {
+        Ok(count) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "data": { "cues": count } })),
+        ),
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
+}
+
+async fn history_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    match state.history.query(&query) {
+        Ok(data) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "data": data }))),
+        Err(e) => api_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+async fn crowd_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "success": true, "data": state.crowd.snapshot() }))
+}
+
+async fn lfos_list_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let set = state.lfos.snapshot();
+    Json(serde_json::json!({
+        "success": true,
+        "data": { "path": state.lfos.path(), "lfos": set.lfos }
+    }))
+}
+
+async fn lfos_replace_handler(
+    operator: Operator,
+    State(state): State<Arc<AppState>>,
+    Json(set): Json<LfoSet>,
+) -> impl IntoResponse {
+    let count = set.lfos.len();
+    match state.lfos.replace(set) {
+        Ok(()) => {
+            tracing::info!(event = "lfos_replaced", count, by = %operator.username);
+            (StatusCode::OK, Json(serde_json::json!({ "success": true, "data": { "lfos": count } })))
+        }
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
+}
+
+async fn lfos_reload_handler(
+    _operator: Operator,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.lfos.reload() {
+        Ok(count) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "data": { "lfos": count } })),
+        ),
+        Err(e) => api_error(StatusCode::UNPROCESSABLE_ENTITY, e),
+    }
+}
+
+// Newest frames kept when a client asks for more history than this
+const MAX_BACKLOG_FRAMES: usize = 1500;
+
+/// `/ws?history_secs=60` sends the kept frames of the last minute before the live stream.
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(default)]
+struct StateStreamQuery {
+    history_secs: Option<u64>,
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(query): Query<StateStreamQuery>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, state, query))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>, query: StateStreamQuery) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.tx.subscribe();
+    // Taken after subscribing so no frame falls between backlog and live stream
+    let mut backlog = query
+        .history_secs
+        .map(|secs| state.history.recent(Duration::from_secs(secs)))
+        .unwrap_or_default();
+    if backlog.len() > MAX_BACKLOG_FRAMES {
+        backlog.drain(..backlog.len() - MAX_BACKLOG_FRAMES);
+    }
 
     // 1. Spawn Sender Task (Server -> Client)
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        for msg in backlog {
+            let json = serde_json::to_string(&*msg).unwrap();
+            if sender.send(Message::Text(json)).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            let msg = match rx.recv().await {
+                Ok(msg) => msg,
+                // A client still catching up on the backlog skips to the newest frames
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
             let json = serde_json::to_string(&msg).unwrap();
             if sender.send(Message::Text(json.into())).await.is_err() {
                 break;
@@ -67,13 +484,15 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
     });
 
     // 2. Spawn Receiver Task (Client -> Server)
-    let director_ref = state.director.clone();
+    let crowd = state.crowd.clone();
+    let client_id = crowd.connect();
+    let crowd_ref = crowd.clone();
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
                 if let Ok(telemetry) = serde_json::from_str::<ClientTelemetry>(&text) {
-                    //Pass to AI Director
-                    director_ref.update_boredom(telemetry.boredom_score).await;
+                    // The crowd monitor decides when the director reacts
+                    crowd_ref.report(client_id, telemetry);
                 }
             }
         }
@@ -84,4 +503,52 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
         _ = (&mut send_task) => recv_task.abort(),
         _ = (&mut recv_task) => send_task.abort(),
     };
+    crowd.disconnect(client_id);
+}
+
+async fn spectrum_ws_handler(
+    ws: WebSocketUpgrade,
+    Query(query): Query<SpectrumQuery>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let query = query.clamped();
+    ws.on_upgrade(move |socket| handle_spectrum_socket(socket, state, query))
+}
+
+// Binary stream of downsampled waveform + magnitude frames (layout in spectrum.rs)
+async fn handle_spectrum_socket(socket: WebSocket, state: Arc<AppState>, query: SpectrumQuery) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut rx = state.tx_spectrum.subscribe();
+    let interval = Duration::from_secs_f32(1.0 / query.fps);
+
+    let mut send_task = tokio::spawn(async move {
+        let mut last_sent: Option<Instant> = None;
+        loop {
+            let frame = match rx.recv().await {
+                Ok(frame) => frame,
+                // Slow clients just skip frames
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            if last_sent.is_some_and(|t| t.elapsed() < interval) {
+                continue;
+            }
+            last_sent = Some(Instant::now());
+
+            if sender.send(Message::Binary(query.encode(&frame))).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Drain incoming messages so close frames are observed
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(_)) = receiver.next().await {}
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+    };
 }
@@ -0,0 +1,341 @@
+use crate::audio_engine::{AudioEngine, AudioFeatures};
+use crate::clock::ManualClock;
+use crate::crowd::{CrowdConfig, CrowdMonitor};
+use crate::genre::GenreTaxonomy;
+use crate::history::{HistoryConfig, StateHistory};
+use crate::lfo::LfoBank;
+use crate::llm_engine::{AiContext, DirectorConfig, LlmDirector};
+use crate::publish::{Analysis, FrameFinisher, FrameInputs};
+use crate::recorder::{Recorder, RecorderConfig};
+use crate::replay::{self, ReplayConfig};
+use crate::rules::RuleEngine;
+use crate::show::ShowControl;
+use crate::state_machine::{AudioMetadata, GlobalState, Overmind, OvermindConfig, VibeState};
+use crate::telemetry::Telemetry;
+use crate::ticker::{audio_ms, TickerConfig};
+use crate::websocket::AppState;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::time::{sleep, Duration};
+use tracing::info;
+
+/// One pipeline: capture device, Overmind, director and broadcast channel.
+/// Unset paths mean "built-in" or "none", as when the variable is unset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneConfig {
+    pub name: String,
+    /// Part of the loopback device name to capture; the first loopback device if unset.
+    #[serde(default)]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub rules: Option<PathBuf>,
+    #[serde(default)]
+    pub lfos: Option<PathBuf>,
+    /// Defaults to `scenes-<name>.json`.
+    #[serde(default)]
+    pub scenes: Option<PathBuf>,
+    #[serde(default)]
+    pub cues: Option<PathBuf>,
+    #[serde(default)]
+    pub scripts: Option<PathBuf>,
+    /// Defaults to `recordings/<name>`.
+    #[serde(default)]
+    pub recordings: Option<PathBuf>,
+    /// Recorded session to replay instead of capturing.
+    #[serde(default)]
+    pub replay: Option<PathBuf>,
+    #[serde(default)]
+    pub director: DirectorConfig,
+}
+
+impl ZoneConfig {
+    /// The single zone used without `ZONES_PATH`, configured by the usual variables.
+    fn from_env() -> Self {
+        let path = |key: &str| std::env::var(key).ok().map(PathBuf::from);
+        Self {
+            name: "main".to_string(),
+            device: std::env::var("AUDIO_DEVICE").ok(),
+            rules: path("RULES_PATH"),
+            lfos: path("LFOS_PATH"),
+            scenes: Some(path("SCENES_PATH").unwrap_or("scenes.json".into())),
+            cues: path("CUES_PATH"),
+            scripts: path("SCRIPTS_DIR"),
+            recordings: path("RECORDER_DIR"),
+            replay: path("REPLAY_SESSION"),
+            director: DirectorConfig::default(),
+        }
+    }
+
+    fn scenes_path(&self) -> PathBuf {
+        self.scenes.clone().unwrap_or_else(|| format!("scenes-{}.json", self.name).into())
+    }
+
+    fn recordings_dir(&self) -> PathBuf {
+        self.recordings.clone().unwrap_or_else(|| PathBuf::from("recordings").join(&self.name))
+    }
+}
+
+/// Zones hosted by this backend. The first one is the default zone, also
+/// served on the unprefixed routes (`/ws`, `/api/v1/...`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneSet {
+    pub zones: Vec<ZoneConfig>,
+}
+
+impl ZoneSet {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let set: ZoneSet = serde_json::from_str(text)?;
+        let mut errors = Vec::new();
+        if set.zones.is_empty() {
+            errors.push("no zones".to_string());
+        }
+        let (mut names, mut scenes, mut recordings) =
+            (HashSet::new(), HashSet::new(), HashSet::new());
+        for zone in &set.zones {
+            let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+            if zone.name.is_empty() || !zone.name.chars().all(valid) || zone.name == "spectrum" {
+                errors.push(format!("invalid zone name '{}'", zone.name));
+            }
+            if !names.insert(zone.name.as_str()) {
+                errors.push(format!("{}: declared twice", zone.name));
+            }
+            if !scenes.insert(zone.scenes_path()) {
+                errors.push(format!("{}: scene file shared with another zone", zone.name));
+            }
+            if !recordings.insert(zone.recordings_dir()) {
+                errors.push(format!("{}: recording directory shared with another zone", zone.name));
+            }
+        }
+        if errors.is_empty() {
+            Ok(set)
+        } else {
+            bail!(errors.join("; "))
+        }
+    }
+
+    /// `ZONES_PATH` if set; otherwise one zone named `main`. An invalid zone
+    /// file stops startup, since falling back would capture the wrong rooms.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let Ok(path) = std::env::var("ZONES_PATH") else {
+            return Ok(Self { zones: vec![ZoneConfig::from_env()] });
+        };
+        let set = Self::parse(&std::fs::read_to_string(&path)?)?;
+        let names: Vec<&str> = set.zones.iter().map(|z| z.name.as_str()).collect();
+        println!("🗺️ [Zones] Loaded {:?} from {:?}", names, path);
+        Ok(set)
+    }
+}
+
+/// Starts one zone's capture, director loop and Overmind loop. The returned
+/// engine (if capture started) must be kept alive for as long as the zone runs.
+pub fn start(zone: ZoneConfig, telemetry: Arc<Telemetry>) -> (AppState, Option<AudioEngine>) {
+    println!("🗺️ [Zones] Starting zone '{}'", zone.name);
+
+    // 1. Audio Engine
+    let (tx_audio, mut rx_audio) = broadcast::channel(16);
+    // Raw waveform/spectrum frames, only produced while /ws/spectrum has subscribers
+    let (tx_spectrum, _) = broadcast::channel(8);
+
+    // Session recorder (idle until started via API)
+    let recorder_config =
+        RecorderConfig { dir: zone.recordings_dir(), ..RecorderConfig::from_env() };
+    let recorder = Recorder::new(recorder_config);
+
+    // Declarative vibe/glitch rules, hot-reloaded while running
+    let rules = RuleEngine::load(zone.rules.clone());
+    rules.spawn_hot_reload();
+
+    // Beat-synced modulators, editable through the API
+    let lfos = LfoBank::load(zone.lfos.clone());
+
+    // Operator scenes and overrides/locks, set through the authenticated API
+    let show = ShowControl::load(zone.scenes_path(), zone.cues.clone());
+
+    // Replay mode replaces the capture device with a recorded session
+    let replay_config = ReplayConfig::load(zone.replay.clone());
+
+    // Attempt to initialize Audio Engine, NO FALLBACK
+    let (audio_engine, audio_meta) = if let Some(cfg) = &replay_config {
+        println!("🔁 [Replay] Replay session set for '{}'. Skipping audio capture.", zone.name);
+        (None, cfg.audio_meta())
+    } else {
+        let device = zone.device.as_deref();
+        match AudioEngine::new(device, tx_audio.clone(), tx_spectrum.clone(), recorder.clone()) {
+            Ok((engine, meta)) => {
+                info!(event = "audio_engine_ready", zone = %zone.name, device = %meta.device_name);
+                (Some(engine), meta)
+            }
+            Err(e) => {
+                println!("⚠️ [Audio] Audio Engine Initialization Failed: {}", e);
+                println!("🛑 [Audio] Zone '{}' running in NO_AUDIO mode (0 data).", zone.name);
+
+                let dummy_meta = AudioMetadata {
+                    device_name: "NO_AUDIO_DEVICE".to_string(),
+                    sample_rate: 0,
+                    channels: 0,
+                };
+                (None, dummy_meta)
+            }
+        }
+    };
+    recorder.set_audio_format(&audio_meta);
+    let device_name = audio_meta.device_name.clone();
+
+    // 2. State Machine (The Overmind)
+    let (tx_state, _): (broadcast::Sender<GlobalState>, _) = broadcast::channel(16);
+
+    // Downsampled record of the published stream for late joiners and post-show queries
+    let history = StateHistory::spawn(HistoryConfig::from_env(), tx_state.subscribe());
+
+    // Initialize AI Director, starting from the boot look every frame has until it answers
+    let boot = AiContext::of(&GlobalState::default());
+    let llm_director = Arc::new(LlmDirector::new(boot).with_config(zone.director.clone()));
+
+    // Client boredom reports, aggregated into crowd-level chaos triggers
+    let crowd = CrowdMonitor::new(CrowdConfig::from_env());
+    crowd.spawn(llm_director.clone());
+
+    // Start AI Director Loop (Async)
+    let director_clone = llm_director.clone();
+    let mut rx_state_for_director = tx_state.subscribe();
+
+    // Replays carry their own AI context; the LLM is only consulted live
+    if replay_config.is_none() && zone.director.enabled {
+        let name = zone.name.clone();
+        tokio::spawn(async move {
+            println!("⚡ [LLM] Director Loop Started for '{}'.", name);
+            let interval = Duration::from_secs(director_clone.config.interval_secs);
+            loop {
+                // Wait for a state update to analyze (throttled)
+                if let Ok(state) = rx_state_for_director.recv().await {
+                    let genre_str = state.genre.as_str().to_string();
+                    let genre_notes = GenreTaxonomy::global().get(&state.genre).describe();
+                    let chaos = if state.state == VibeState::Chaos { 1.0 } else { 0.0 };
+                    let trend = state.energy_trend.as_str();
+
+                    // Consult Oracle with REAL data
+                    director_clone
+                        .consult_oracle(&genre_str, &genre_notes, chaos, trend, &state.mood)
+                        .await;
+                }
+                sleep(interval).await;
+            }
+        });
+    }
+
+    // 3. Start Overmind Loop
+    let overmind_config =
+        OvermindConfig { rules: rules.clone(), lfos: lfos.clone(), scripts_dir: zone.scripts };
+    let ticker_config = TickerConfig::from_env();
+    let tx_state_clone = tx_state.clone();
+    let director_ref = llm_director.clone();
+    let audio_running = audio_engine.is_some();
+    let recorder_ref = recorder.clone();
+    let show_ref = show.clone();
+    let crowd_ref = crowd.clone();
+    let name = zone.name.clone();
+
+    tokio::spawn(async move {
+        // Recorded sessions drive the state stream directly, paced by the recording
+        if let Some(cfg) = replay_config {
+            replay::run(cfg, audio_meta, overmind_config, show_ref, telemetry, tx_state_clone)
+                .await;
+            return;
+        }
+
+        // Live capture runs on the sample clock, the silent heartbeat on wall-clock time
+        let sample_clock = Arc::new(ManualClock::new());
+        let sample_rate = audio_meta.sample_rate;
+        let mut overmind = if audio_running {
+            Overmind::with_clock(audio_meta, &overmind_config, sample_clock.clone())
+        } else {
+            Overmind::new(audio_meta, &overmind_config)
+        };
+
+        // Analysis runs on every capture buffer; the ticker publishes the latest
+        // result at a fixed rate, with the events raised since the previous frame
+        println!("⏱️ [Ticker] Zone '{}' publishing at {} Hz", name, ticker_config.rate_hz);
+        let mut ticker = ticker_config.interval();
+        let mut finisher = FrameFinisher::new(
+            show_ref.clone(),
+            overmind_config.lfos.clone(),
+            Some(ticker_config.rate_hz),
+        );
+        let mut latest: Option<(AudioFeatures, GlobalState)> = None;
+        let mut pending_events = Vec::new();
+        let mut capturing = audio_running;
+
+        loop {
+            tokio::select! {
+                received = rx_audio.recv(), if capturing => match received {
+                    Ok(features) => {
+                        sample_clock.set_samples(features.sample_clock, sample_rate);
+                        overmind.pin_bpm(show_ref.overrides.pinned_bpm());
+                        let state = overmind.update(&features);
+                        recorder_ref.push_features(&features);
+                        pending_events.extend(state.events.iter().cloned());
+                        latest = Some((features, state));
+                    }
+                    // Skipped buffers only coarsen the analysis
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => {
+                        println!("🛑 [Audio] Capture ended in '{}'. Holding last state.", name);
+                        capturing = false;
+                    }
+                },
+                _ = ticker.tick() => {
+                    // Without capture the Overmind steps on the tick, a heartbeat of 0 energy
+                    if !audio_running {
+                        let features = AudioFeatures::default();
+                        overmind.pin_bpm(show_ref.overrides.pinned_bpm());
+                        let state = overmind.update(&features);
+                        recorder_ref.push_features(&features);
+                        pending_events.extend(state.events.iter().cloned());
+                        latest = Some((features, state));
+                    }
+                    let Some((features, state)) = &latest else { continue };
+                    let inputs = FrameInputs::new(
+                        &*director_ref.context.lock().await,
+                        crowd_ref.stats(),
+                    );
+                    let mut new_state = state.clone();
+                    new_state.events = std::mem::take(&mut pending_events);
+                    telemetry.apply(&mut new_state);
+                    finisher.apply(
+                        &mut new_state,
+                        &inputs,
+                        audio_ms(features.sample_clock, sample_rate),
+                    );
+
+                    // Log the published frame with what it was made from; its
+                    // buffers were logged as they were analysed
+                    recorder_ref.push_frame(&new_state, &Analysis::of(state), &inputs);
+
+                    // Broadcast new state to all connected clients
+                    let _ = tx_state_clone.send(new_state);
+                }
+            }
+        }
+    });
+
+    let state = AppState {
+        zone: zone.name,
+        device: device_name,
+        tx: tx_state,
+        tx_spectrum,
+        director: llm_director,
+        recorder,
+        rules,
+        lfos,
+        show,
+        history,
+        crowd,
+    };
+    (state, audio_engine)
+}
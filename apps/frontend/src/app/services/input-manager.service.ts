@@ -632,7 +632,7 @@ export class InputManagerService implements OnDestroy {
       const now = Date.now();
       if (now - this.lastTelemetrySend > 1000) { // 1Hz throttle
           if (this.ws && this.ws.readyState === WebSocket.OPEN) {
-              this.ws.send(JSON.stringify({ boredom_score: score }));
+              this.ws.send(JSON.stringify({ boredom_score: score, visible: !document.hidden }));
               this.lastTelemetrySend = now;
           }
       }